@@ -1,7 +1,8 @@
 """An SSH library for Python; written in Rust.
 """
 
-from typing import Optional
+import socket
+from typing import BinaryIO, Callable, Dict, Iterator, List, Optional, Tuple, Union
 
 
 class SessionException(Exception):
@@ -18,15 +19,96 @@ class SFTPException(Exception):
     ...
 
 
+class ChannelException(SessionException):
+    """Errors opening or using a channel. Unless the connection was lost, the session remains usable
+    when these are raised.
+    """
+
+    ...
+
+
+class PassphraseRequiredException(SessionException):
+    """Raised when a private key is encrypted but no passphrase is available for it.
+
+    The path to the key is available as the second element of `args`.
+    """
+
+    ...
+
+
+class PasswordExpiredException(SessionException):
+    """Raised when the server reports that a password has expired but no new password is
+    available to change it to.
+    """
+
+    ...
+
+
+class AuthenticationException(SessionException):
+    """Raised when every authentication method fails.
+
+    The names of the attempted methods (as used for the `order` of :class:`AuthMethods`) are
+    available as the second element of `args`, and the authentication methods the server still
+    allows (e.g. `publickey` or `password`) as the third.
+    """
+
+    ...
+
+
+class BadHostKeyException(SessionException):
+    """Raised when a server presents a host key that does not match the one in `known_hosts`,
+    which may indicate a man-in-the-middle attack.
+
+    The host name (`[host]:port` for ports other than 22) is available as the second element of
+    `args`, the expected :class:`HostKey` (or `None` if it is not known, e.g. when the host is not in
+    `known_hosts`) as the third, and the :class:`HostKey` the server presented as the fourth.
+    """
+
+    ...
+
+
+class NoValidConnectionsError(OSError):
+    """Raised when a host resolves to several addresses and connecting to each of them failed.
+
+    The attempts are available as the second element of `args`: a list of `(ip, port, error)` tuples,
+    in the order the addresses were tried, where `error` is the exception the attempt failed with.
+    """
+
+    ...
+
+
 class PasswordAuth:
     """Represents password based authentication.
     """
 
-    def __init__(self, password: str) -> None:
+    def __init__(
+            self,
+            password: Optional[str] = None,
+            new_password: Optional[str] = None,
+            new_password_callback: Optional[Callable[[str], Optional[str]]] = None,
+            provider: Optional[Callable[[], str]] = None,
+    ) -> None:
         """Creates a new password based authentication method.
 
+        If the server reports that the password has expired and no new password is available, a
+        :class:`PasswordExpiredException` is raised when connecting.
+
+        Instead of the password itself, a `provider` can be given, which is called each time the
+        password is needed (e.g. to fetch it from a secret manager), so that the password is not kept
+        in memory for the program's lifetime.
+
         Args:
-            password (str): The SSH password.
+            password (Optional[str], optional): The SSH password. Defaults to `None`.
+            new_password (Optional[str], optional): The new password to set if the server reports
+                that the password has expired. Defaults to `None`.
+            new_password_callback (Optional[Callable[[str], Optional[str]]], optional): Callable
+                invoked with the username when the password has expired and no new password was
+                provided. It should return the new password, or `None`. Defaults to `None`.
+            provider (Optional[Callable[[], str]], optional): Callable invoked without arguments each
+                time the password is needed. Only used if no password is given. Defaults to `None`.
+
+        Raises:
+            ValueError: If neither a password nor a provider is given.
         """
 
         ...
@@ -36,13 +118,148 @@ class PrivateKeyAuth:
     """Represents private-key based authentication.
     """
 
-    def __init__(self, private_key: str, passphrase: Optional[str] = None) -> None:
+    def __init__(
+            self,
+            private_key: str,
+            passphrase: Optional[str] = None,
+            passphrase_callback: Optional[Callable[[str], Optional[str]]] = None,
+            cert_file: Optional[str] = None,
+    ) -> None:
         """Creates a new private-key based authentication method.
 
+        If the key is encrypted and no passphrase is available, a :class:`PassphraseRequiredException`
+        is raised when connecting. PuTTY (PPK) keys are converted automatically (see
+        :func:`convert_ppk` for the supported versions). Keys of a type that `libssh2` does not
+        support (see :func:`supported_key_types`) fail with an explanatory :class:`SessionException`.
+
+        Args:
+            private_key (str): The path to the private-key file, in PEM, OpenSSH or PuTTY (PPK)
+                format.
+            passphrase (Optional[str], optional): The passphrase for the private-key file.
+                Defaults to `None`.
+            passphrase_callback (Optional[Callable[[str], Optional[str]]], optional): Callable invoked
+                with the path to the private-key file when the key is encrypted and no passphrase was
+                provided. It should return the passphrase, or `None`. Defaults to `None`.
+            cert_file (Optional[str], optional): The path to the OpenSSH certificate signed for the
+                key. Defaults to the key's path with `-cert.pub` appended, if that file exists.
+        """
+
+        ...
+
+
+class PrivateKeyMemoryAuth:
+    """Represents private-key based authentication with key material held in memory, e.g. when it
+    comes from a secrets vault or an environment variable.
+    """
+
+    def __init__(self, private_key: Union[str, bytes], passphrase: Optional[str] = None) -> None:
+        """Creates a new in-memory private-key based authentication method.
+
+        **NOTE**: This is only supported on Unix platforms.
+
+        Args:
+            private_key (Union[str, bytes]): The private key, in PEM or OpenSSH format.
+            passphrase (Optional[str], optional): The passphrase for the private key. Defaults to
+                `None`.
+        """
+
+        ...
+
+
+class AgentAuth:
+    """Represents SSH-agent based authentication.
+
+    Every identity held by the agent is tried in turn, unless a specific identity is selected. On
+    Windows, both Pageant and the built-in OpenSSH agent (`\\\\.\\pipe\\openssh-ssh-agent`) are
+    supported; elsewhere, the agent is located through the `SSH_AUTH_SOCK` environment variable,
+    unless an explicit socket path is given.
+
+    Security-key (FIDO2) identities, such as `sk-ssh-ed25519@openssh.com` keys, are supported: the
+    agent performs the signature, which may require touching the key.
+    """
+
+    def __init__(self, identity: Optional[str] = None, socket_path: Optional[str] = None) -> None:
+        """Creates a new SSH-agent based authentication method.
+
+        Args:
+            identity (Optional[str], optional): The comment (e.g. `deploy@ci`) or SHA-256 fingerprint
+                (e.g. `SHA256:...`, as shown by `ssh-add -l`) of the only identity to try. Defaults to
+                `None`, which tries every identity; this may exceed the server's `MaxAuthTries` if the
+                agent holds many keys.
+            socket_path (Optional[str], optional): The path to the agent's UNIX socket, or its named
+                pipe on Windows (e.g. `\\\\.\\pipe\\openssh-ssh-agent`), for processes launched
+                without the usual environment. Defaults to `None`, which uses the `SSH_AUTH_SOCK`
+                environment variable, or the default agents on Windows.
+        """
+
+        ...
+
+
+class HostBasedAuth:
+    """Represents host-based authentication, where the client host's key vouches for the user.
+    """
+
+    def __init__(
+            self,
+            public_key: str,
+            private_key: str,
+            hostname: str,
+            passphrase: Optional[str] = None,
+            local_username: Optional[str] = None,
+    ) -> None:
+        """Creates a new host-based authentication method.
+
         Args:
-            private_key (str): The path to the private-key file.
+            public_key (str): The path to the client host's public-key file.
+            private_key (str): The path to the client host's private-key file.
+            hostname (str): The fully-qualified name of the client host, as known to the server.
             passphrase (Optional[str], optional): The passphrase for the private-key file.
                 Defaults to `None`.
+            local_username (Optional[str], optional): The name of the user on the client host.
+                Defaults to `None`, which uses the SSH username.
+        """
+
+        ...
+
+
+class KeyboardInteractiveAuth:
+    """Represents keyboard-interactive authentication with automatically generated responses.
+
+    Each prompt sent by the server is answered with the response of the first pattern that matches
+    it. Prompts that match no pattern, but match the one-time password pattern (e.g.
+    "Verification code:"), are answered with a TOTP code if a TOTP secret or generator is configured.
+    All other prompts are answered with an empty string.
+
+    If a handler is given, it answers all the prompts instead.
+    """
+
+    def __init__(
+            self,
+            responses: Optional[Dict[str, str]] = None,
+            totp_secret: Optional[str] = None,
+            totp_prompt: Optional[str] = None,
+            handler: Optional[Callable[[str, List[Tuple[str, bool]]], List[str]]] = None,
+            totp_generator: Optional[Callable[[], str]] = None,
+    ) -> None:
+        """Creates a new keyboard-interactive authentication method.
+
+        Args:
+            responses (Optional[Dict[str, str]], optional): Responses to prompts, keyed by regular
+                expressions that are searched for in the prompt. Patterns are tried in order.
+                Defaults to `None`.
+            totp_secret (Optional[str], optional): The base32-encoded TOTP secret used to answer
+                one-time password prompts. Defaults to `None`.
+            totp_prompt (Optional[str], optional): Regular expression used to recognise one-time
+                password prompts. Defaults to a pattern matching prompts that mention a code, token,
+                OTP, one-time password or verification.
+            handler (Optional[Callable[[str, List[Tuple[str, bool]]], List[str]]], optional):
+                Callable that answers the prompts instead. It is called with the instructions sent by
+                the server and a list of `(prompt, echo)` tuples (where `echo` indicates whether the
+                response may be displayed), and must return a list with one response per prompt.
+                Defaults to `None`.
+            totp_generator (Optional[Callable[[], str]], optional): Callable invoked without
+                arguments to generate the one-time password when no TOTP secret is given (e.g. to read
+                it from a hardware token). Defaults to `None`.
         """
 
         ...
@@ -56,14 +273,108 @@ class AuthMethods:
             self,
             password: Optional[PasswordAuth] = None,
             private_key: Optional[PrivateKeyAuth] = None,
+            keyboard_interactive: Optional[KeyboardInteractiveAuth] = None,
+            host_based: Optional[HostBasedAuth] = None,
+            agent: Optional[AgentAuth] = None,
+            private_key_memory: Optional[PrivateKeyMemoryAuth] = None,
+            order: Optional[List[str]] = None,
     ) -> None:
         """Creates a new instance of authentication methods.
 
+        By default, the methods are attempted in the order documented for :meth:`SSHClient.connect`.
+        Use `order` to change it, e.g. to try keys before a password and avoid account lockouts from
+        failed password attempts.
+
         Args:
             password (Optional[PasswordAuth], optional): The password based authentication method.
                 Defaults to `None`.
             private_key (Optional[PrivateKeyAuth], optional): The private-key based authentication method.
                 Defaults to `None`.
+            keyboard_interactive (Optional[KeyboardInteractiveAuth], optional): The keyboard-interactive
+                authentication method. Defaults to `None`.
+            host_based (Optional[HostBasedAuth], optional): The host-based authentication method.
+                Defaults to `None`.
+            agent (Optional[AgentAuth], optional): The SSH-agent based authentication method.
+                Defaults to `None`.
+            private_key_memory (Optional[PrivateKeyMemoryAuth], optional): The in-memory private-key
+                based authentication method. Defaults to `None`.
+            order (Optional[List[str]], optional): Names of the methods to attempt first, in order:
+                `"password"`, `"private_key"`, `"private_key_memory"`, `"agent"`, `"host_based"` or
+                `"keyboard_interactive"`. Methods that are not listed are attempted afterwards, in the
+                default order. Defaults to `None`.
+
+        Raises:
+            ValueError: If `order` contains an unknown method name.
+        """
+
+        ...
+
+
+class PtyOptions:
+    """Represents the options used when requesting a pseudo-terminal (PTY).
+    """
+
+    def __init__(
+            self,
+            term: Optional[str] = None,
+            width: Optional[int] = None,
+            height: Optional[int] = None,
+            width_px: Optional[int] = None,
+            height_px: Optional[int] = None,
+            modes: Optional[Dict[str, int]] = None,
+            merge_stderr: Optional[bool] = None,
+    ) -> None:
+        """Creates a new set of PTY options.
+
+        Args:
+            term (Optional[str], optional): The terminal type. Defaults to `vt100`.
+            width (Optional[int], optional): The width of the terminal (in characters). Defaults to 80.
+            height (Optional[int], optional): The height of the terminal (in characters). Defaults to 24.
+            width_px (Optional[int], optional): The width of the terminal (in pixels). Defaults to 0.
+            height_px (Optional[int], optional): The height of the terminal (in pixels). Defaults to 0.
+            modes (Optional[Dict[str, int]], optional): Terminal modes, keyed by their RFC 4254 names
+                (e.g. `{"ECHO": 0}` to disable echo). Defaults to `None`.
+            merge_stderr (Optional[bool], optional): Whether the command's `stderr` is merged into
+                `stdout`. Defaults to `True`. See :func:`SSHClient.exec_command` for what happens
+                otherwise.
+        """
+
+        ...
+
+
+class TcpOptions:
+    """Represents the socket options set on the TCP connection underlying an SSH session.
+    """
+
+    def __init__(
+            self,
+            nodelay: bool = False,
+            keepalive: Optional[float] = None,
+            send_buffer_size: Optional[int] = None,
+            recv_buffer_size: Optional[int] = None,
+    ) -> None:
+        """Creates a new set of TCP socket options.
+
+        **NOTE**: Buffer sizes are hints; the operating system may round or cap them. Socket options
+        other than `nodelay` are only supported on Unix platforms, and the keepalive interval can
+        only be configured on Linux and macOS.
+
+        Args:
+            nodelay (bool, optional): Whether to disable Nagle's algorithm (`TCP_NODELAY`), which
+                lowers the latency of interactive sessions. Defaults to `False`.
+            keepalive (Optional[float], optional): Interval between TCP keepalive probes (in
+                seconds). Enables `SO_KEEPALIVE`, with probes sent once the connection has been idle
+                for the interval, so that dead peers are detected and idle connections survive NAT
+                timeouts. Defaults to `None`.
+            send_buffer_size (Optional[int], optional): Size of the socket's send buffer (in bytes),
+                which can be raised for bulk transfers over links with a high bandwidth-delay
+                product. Defaults to `None`.
+            recv_buffer_size (Optional[int], optional): Size of the socket's receive buffer (in
+                bytes). Defaults to `None`.
+
+        Raises:
+            ValueError: If the keepalive interval is invalid or shorter than 1 second, or a buffer
+                size is 0.
         """
 
         ...
@@ -73,13 +384,25 @@ class ExecOutput:
     """Represents the output produced when running :func:`SSHClient.exec_command`.
     """
 
-    def write_stdin(self, data: str) -> None:
+    eof_received: bool
+    """Whether the server has signalled EOF on the channel, i.e. the command's output is finished.
+    This only reflects data already processed by the session; it never reads from the network."""
+
+    closed: bool
+    """Whether the channel has been torn down, either because the command finished and the channel
+    was closed, or because :func:`ExecOutput.close` was called."""
+
+    active: bool
+    """Whether the command is still running, i.e. the channel is open and its output is not finished
+    yet."""
+
+    def write_stdin(self, data: Union[str, bytes, bytearray]) -> None:
         """Writes the provided data to the `stdin` stream and closes it.
 
         **NOTE**: Future calls will discard the provided data without doing anything.
 
         Args:
-            data (str): The data to write to the stream.
+            data (Union[str, bytes, bytearray]): The data to write to the stream. Text is written as UTF-8.
 
         Returns:
             None
@@ -87,24 +410,118 @@ class ExecOutput:
 
         ...
 
-    def read_stdout(self) -> str:
+    def read_stdout(self, timeout: Optional[float] = None) -> str:
         """Reads the contents of the `stdout` stream and consumes it.
 
+        If the timeout expires before the stream is exhausted, a `TimeoutError` is raised. The data
+        read so far is retained and returned by the next call.
+
         **NOTE**: Future calls will return an empty string.
 
+        Args:
+            timeout (Optional[float], optional): The timeout (in seconds). Defaults to `None`, which
+                blocks until EOF.
+
         Returns:
             The contents of `stdout`.
+
+        Raises:
+            ValueError: If the timeout is negative or NaN.
         """
 
         ...
 
-    def read_stderr(self) -> str:
+    def read_stderr(self, timeout: Optional[float] = None) -> str:
         """Reads the contents of the `stderr` stream and consumes it.
 
+        If the timeout expires before the stream is exhausted, a `TimeoutError` is raised. The data
+        read so far is retained and returned by the next call.
+
         **NOTE**: Future calls will return an empty string.
 
+        Args:
+            timeout (Optional[float], optional): The timeout (in seconds). Defaults to `None`, which
+                blocks until EOF.
+
         Returns:
             The contents of `stderr`.
+
+        Raises:
+            ValueError: If the timeout is negative or NaN.
+        """
+
+        ...
+
+    def save_stdout(self, dest: Union[str, BinaryIO]) -> int:
+        """Streams the contents of the `stdout` stream into a local file and consumes it.
+
+        The data is written in chunks as it arrives, so it is never held in memory as a whole.
+
+        **NOTE**: Future calls will write nothing.
+
+        Args:
+            dest (Union[str, BinaryIO]): Path to the local file (which is created or truncated), or a
+                binary file-like object to write to.
+
+        Returns:
+            The number of bytes written.
+        """
+
+        ...
+
+    def save_stderr(self, dest: Union[str, BinaryIO]) -> int:
+        """Streams the contents of the `stderr` stream into a local file and consumes it.
+
+        The data is written in chunks as it arrives, so it is never held in memory as a whole.
+
+        **NOTE**: Future calls will write nothing.
+
+        Args:
+            dest (Union[str, BinaryIO]): Path to the local file (which is created or truncated), or a
+                binary file-like object to write to.
+
+        Returns:
+            The number of bytes written.
+        """
+
+        ...
+
+    def recv(self, n: int, timeout: Optional[float] = None) -> bytes:
+        """Receives at most `n` bytes from the `stdout` stream.
+
+        Returns an empty `bytes` object once the stream is exhausted. If no data arrives before the
+        timeout expires, a `TimeoutError` is raised.
+
+        Args:
+            n (int): The maximum number of bytes to receive.
+            timeout (Optional[float], optional): The timeout (in seconds). Defaults to `None`, which
+                blocks until data is available.
+
+        Returns:
+            The received data.
+
+        Raises:
+            ValueError: If the timeout is negative or NaN.
+        """
+
+        ...
+
+    def recv_stderr(self, n: int, timeout: Optional[float] = None) -> bytes:
+        """Receives at most `n` bytes from the `stderr` stream.
+
+        Returns an empty `bytes` object once the stream is exhausted. If no data arrives before the
+        timeout expires, a `TimeoutError` is raised.
+
+        Args:
+            n (int): The maximum number of bytes to receive.
+            timeout (Optional[float], optional): The timeout (in seconds). Defaults to `None`, which
+                blocks until data is available.
+
+        Returns:
+            The received data.
+
+        Raises:
+            ValueError: If the timeout is negative or NaN.
         """
 
         ...
@@ -122,6 +539,18 @@ class ExecOutput:
 
         ...
 
+    def poll(self) -> Optional[int]:
+        """Checks whether the command has finished, without blocking.
+
+        Output that arrives in the meantime is buffered and returned by later reads; nothing is
+        consumed or closed.
+
+        Returns:
+            The exit status if the command has finished, or `None` if it is still running.
+        """
+
+        ...
+
     def close(self) -> None:
         """Consumes all streams and closes the underlying channel if it exists and is active.
 
@@ -150,6 +579,9 @@ class File:
     def write(self, data: str) -> None:
         """Writes the specified data to the file.
 
+        In append mode, the data is always written at the end of the file, even if it was extended
+        by someone else since it was opened.
+
         Args:
             data (str): The data to write to the file.
 
@@ -159,178 +591,1672 @@ class File:
 
         ...
 
-
-class SFTPClient:
-    """The SFTP client.
-    """
-
-    def chdir(self, dir: Optional[str] = None) -> None:
-        """Changes the current working directory to the specified directory.
-
-        If the specified directory is `None`, then the current working directory is unset.
-
-        Once the current working directory is set, all SFTP operations will be relative to this path.
-
-        **NOTE**: SFTP does not have a concept of a "current working directory", and so, this function
-        tries to emulate it. Currently, only **absolute** paths are supported. This *MAY* change in the
-        future, but is not guaranteed.
+    def seek(self, offset: int, whence: int = 0) -> int:
+        """Changes the position of the handle.
 
         Args:
-            dir (str): The directory to change to.
+            offset (int): The offset, relative to the position indicated by `whence`.
+            whence (int, optional): `0` for the start of the file, `1` for the current position and `2`
+                for the end of the file. Defaults to `0`.
 
         Returns:
-            None
+            The new absolute position.
         """
 
         ...
 
-    def getcwd(self) -> Optional[str]:
-        """Returns the current working directory.
+    def tell(self) -> int:
+        """Returns the current position of the handle.
 
         Returns:
-            The current working directory.
+            The current position.
         """
 
         ...
 
-    def mkdir(self, dir: str, mode: int = 511) -> None:
-        ...
-
-    def remove(self, path: str) -> None:
-        ...
+    def readv(self, ranges: List[Tuple[int, int]]) -> List[bytes]:
+        """Reads several ranges of the file at once, keeping multiple requests in flight.
 
-    def rmdir(self, dir: str) -> None:
-        ...
+        A range that extends past the end of the file returns only the data up to the end.
 
-    def open(self, filename: str, mode: str = 'r') -> File:
-        """Opens a file on the remote server.
+        **NOTE**: The requests are spread over additional SFTP channels (as many as the server allows)
+        that are closed once the call returns. The position of the handle is not affected.
 
         Args:
-            filename (str): The name of the file (if file is in `cwd`) OR the path to the file.
-            mode (str, optional): Python-style file mode. Defaults to 'r'.
+            ranges (List[Tuple[int, int]]): The offset and length of each range to read.
 
         Returns:
-            The opened :class:`File`.
+            The data of each range, in the same order as the ranges.
         """
 
         ...
 
-    def file(self, filename: str, mode: str = 'r') -> File:
-        """Opens a file on the remote server.
+    def writev(self, chunks: List[Tuple[int, bytes]]) -> None:
+        """Writes data at several offsets of the file at once, keeping multiple requests in flight.
 
-        **NOTE**: This method is just an alias to :func:`SFTPClient.open` to mimic compatibility with paramiko.
+        **NOTE**: The requests are spread over additional SFTP channels (as many as the server allows)
+        that are closed once the call returns. The position of the handle is not affected, and the
+        offsets are honoured even in append mode.
 
         Args:
-            filename (str): The name of the file (if file is in `cwd`) OR the path to the file.
-            mode (str, optional): Python-style file mode. Defaults to 'r'.
+            chunks (List[Tuple[int, bytes]]): The offset and data of each write.
 
         Returns:
-            The opened :class:`File`.
+            None
         """
 
         ...
 
-    def get(self, remotepath: str, localpath: str) -> None:
-        """Copies a file from the remote server to the local host.
 
-        Args:
-            remotepath (str): The remote file path.
-            localpath (str): The local path to copy the file to.
+class FileStat:
+    """Attributes of a file on a remote server.
 
-        Returns:
-            None
-        """
+    Attributes that the server did not provide are `None`.
+    """
 
-        ...
+    size: Optional[int]
+    """The size of the file (in bytes)."""
 
-    def put(self, localpath: str, remotepath: str) -> None:
-        """Copies a local file to the remote server.
+    uid: Optional[int]
+    """The user ID of the file's owner."""
 
-        Args:
-            localpath (str): The path to the local file.
-            remotepath (str): The remote path to copy the file to.
+    gid: Optional[int]
+    """The group ID of the file's owner."""
 
-        Returns:
+    perm: Optional[int]
+    """The POSIX-style permissions (mode) of the file, including the file type bits."""
 
-        """
+    atime: Optional[int]
+    """The last access time of the file (as a UNIX timestamp)."""
 
-        ...
+    mtime: Optional[int]
+    """The last modification time of the file (as a UNIX timestamp)."""
 
-    def is_closed(self) -> bool:
-        """Checks if the SFTP session is closed.
+    def is_dir(self) -> bool:
+        """Checks if the file is a directory.
 
         Returns:
-            Whether SFTP session is closed.
+            Whether the file is a directory.
         """
 
         ...
 
-    def close(self) -> None:
-        """Closes the SFTP session.
+    def is_file(self) -> bool:
+        """Checks if the file is a regular file.
 
         Returns:
-            None
+            Whether the file is a regular file.
         """
 
         ...
 
 
-class SSHClient:
-    """The SSH client.
+class TreeComparison:
+    """The differences between a local and a remote directory tree, as reported by
+    :func:`SFTPClient.compare`.
+
+    All paths are relative to the roots of the compared trees.
     """
 
-    def __init__(self) -> None:
-        """Creates a new SSH client.
+    missing: List[str]
+    """Files that exist locally but not on the remote server."""
+
+    extra: List[str]
+    """Files that exist on the remote server but not locally."""
+
+    size_mismatch: List[str]
+    """Files whose sizes differ."""
+
+    mtime_mismatch: List[str]
+    """Files whose sizes match but whose modification times differ."""
+
+    checksum_mismatch: List[str]
+    """Files whose sizes match but whose contents differ (only populated when checksums are compared)."""
+
+    def is_identical(self) -> bool:
+        """Checks if the trees are identical (as far as the comparison could tell).
+
+        Returns:
+            Whether the trees are identical.
         """
 
         ...
 
-    def connect(
+
+class WatchEvent:
+    """A change to a remote file, as reported by :class:`FileWatcher`.
+    """
+
+    path: str
+    """The path of the file that changed."""
+
+    kind: str
+    """The kind of change: `"created"`, `"deleted"` or `"modified"` (size or modification time
+    changed)."""
+
+    old: Optional[FileStat]
+    """The attributes of the file before the change (`None` if it was created)."""
+
+    new: Optional[FileStat]
+    """The attributes of the file after the change (`None` if it was deleted)."""
+
+
+class FileWatcher:
+    """An iterator over changes to a remote file or directory, created by :func:`SFTPClient.watch`.
+
+    Each iteration blocks until at least one change is detected.
+    """
+
+    def __iter__(self) -> Iterator[WatchEvent]:
+        ...
+
+    def __next__(self) -> WatchEvent:
+        ...
+
+
+class SFTPClient:
+    """The SFTP client.
+
+    The client can be shared by several threads: its operations are run one at a time, along with
+    the other operations on the SSH session.
+    """
+
+    def chdir(self, dir: Optional[str] = None) -> None:
+        """Changes the current working directory to the specified directory.
+
+        If the specified directory is `None`, then the current working directory is unset.
+
+        Once the current working directory is set, all SFTP operations will be relative to this path.
+
+        **NOTE**: SFTP does not have a concept of a "current working directory", and so, this function
+        tries to emulate it. Currently, only **absolute** paths are supported. This *MAY* change in the
+        future, but is not guaranteed.
+
+        Args:
+            dir (str): The directory to change to.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def getcwd(self) -> Optional[str]:
+        """Returns the current working directory.
+
+        Returns:
+            The current working directory.
+        """
+
+        ...
+
+    def stat(self, path: str) -> FileStat:
+        """Retrieves the attributes of a file on the remote server, following symbolic links.
+
+        If the stat cache is enabled (see :func:`SFTPClient.set_stat_cache`), cached attributes are
+        returned while they are still fresh.
+
+        Args:
+            path (str): The path to the file.
+
+        Returns:
+            The attributes of the file.
+        """
+
+        ...
+
+    def lstat(self, path: str) -> FileStat:
+        """Retrieves the attributes of a file on the remote server, without following symbolic links.
+
+        **NOTE**: Results of this method are never cached.
+
+        Args:
+            path (str): The path to the file.
+
+        Returns:
+            The attributes of the file.
+        """
+
+        ...
+
+    def chmod(self, path: str, mode: int, recursive: bool = False) -> None:
+        """Changes the permissions of a file on the remote server.
+
+        **NOTE**: When applied recursively, symbolic links are neither followed nor modified.
+
+        Args:
+            path (str): The path to the file.
+            mode (int): POSIX-style permissions to apply.
+            recursive (bool, optional): Whether to apply the permissions to everything beneath the path.
+                Defaults to `False`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def chown(self, path: str, uid: int, gid: int, recursive: bool = False) -> None:
+        """Changes the owner and group of a file on the remote server.
+
+        **NOTE**: When applied recursively, symbolic links are neither followed nor modified.
+
+        Args:
+            path (str): The path to the file.
+            uid (int): The user ID of the new owner.
+            gid (int): The group ID of the new group.
+            recursive (bool, optional): Whether to apply the ownership to everything beneath the path.
+                Defaults to `False`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def watch(self, path: str, interval: float = 1.0) -> FileWatcher:
+        """Watches a remote file or directory for changes, by polling its attributes.
+
+        Returns an iterator that yields a :class:`WatchEvent` whenever a file is created, deleted or
+        modified (its size or modification time changes). When `path` is a directory, its direct
+        entries are watched. The polling runs on a dedicated SFTP channel.
+
+        Args:
+            path (str): The remote path to watch.
+            interval (float, optional): The interval between polls (in seconds). Defaults to 1.
+
+        Returns:
+            An iterator over the changes.
+
+        Raises:
+            ValueError: If the interval is not a positive number.
+        """
+
+        ...
+
+    def compare(self, local_dir: str, remote_dir: str, checksum: bool = False) -> TreeComparison:
+        """Compares a local directory tree with a remote one, without transferring any file contents.
+
+        Files are matched by their paths relative to the two roots and compared by size and
+        modification time. Optionally, files whose sizes match can also be compared by checksum,
+        which requires `sha1sum` on the server.
+
+        **NOTE**: Only regular files are compared. Symbolic links are not followed.
+
+        Args:
+            local_dir (str): The root of the local tree.
+            remote_dir (str): The root of the remote tree.
+            checksum (bool, optional): Whether to compare checksums of files whose sizes match.
+                Defaults to `False`.
+
+        Returns:
+            The differences between the trees.
+        """
+
+        ...
+
+    def batch_stat(self, paths: List[str]) -> List[Optional[FileStat]]:
+        """Retrieves the attributes of many files at once, following symbolic links.
+
+        Requests are pipelined over several SFTP channels (as many as the server allows) instead of
+        waiting for each round trip in turn, which is much faster for large numbers of paths.
+
+        Args:
+            paths (List[str]): The paths to the files.
+
+        Returns:
+            The attributes in the same order as the paths, with `None` for paths that do not exist.
+        """
+
+        ...
+
+    def batch_remove(self, paths: List[str], ignore_missing: bool = False) -> None:
+        """Removes many files at once.
+
+        Requests are pipelined over several SFTP channels (as many as the server allows) instead of
+        waiting for each round trip in turn, which is much faster for large numbers of paths.
+
+        Every path is attempted, even if removing some of them fails; the first failure is raised
+        afterwards.
+
+        **NOTE**: This only works for files. For directories, use :func:`SFTPClient.rmdir`.
+
+        Args:
+            paths (List[str]): The paths to the files to remove.
+            ignore_missing (bool, optional): Whether paths that do not exist are silently skipped.
+                Defaults to `False`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def du(self, path: str) -> Tuple[int, int]:
+        """Computes the total size of the regular files in a remote tree.
+
+        **NOTE**: Symbolic links are not followed and do not contribute to the totals.
+
+        Args:
+            path (str): The root of the remote tree.
+
+        Returns:
+            A tuple of the total size (in bytes) and the number of regular files.
+        """
+
+        ...
+
+    def set_stat_cache(self, ttl: Optional[float] = None) -> None:
+        """Enables, reconfigures or disables the stat cache.
+
+        When enabled, the attributes returned by :func:`SFTPClient.stat` (and used by operations that
+        walk remote trees) are cached for `ttl` seconds. Entries are dropped automatically when the
+        path is modified through this client (including writes to files opened with it), but changes
+        made by other clients are only picked up once the entries expire or are invalidated
+        explicitly.
+
+        Reconfiguring the cache discards all cached entries.
+
+        Args:
+            ttl (Optional[float], optional): How long (in seconds) cached attributes remain valid.
+                Defaults to `None`, which disables the cache.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If the TTL is negative or NaN.
+        """
+
+        ...
+
+    def invalidate_stat_cache(self, path: Optional[str] = None) -> None:
+        """Drops cached attributes.
+
+        If a path is given, only the entries for that path, everything beneath it and its parent
+        directory are dropped. Otherwise, the entire cache is cleared.
+
+        Args:
+            path (Optional[str], optional): The path to invalidate. Defaults to `None`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def mkdir(self, dir: str, mode: Optional[int] = None, exist_ok: bool = False) -> None:
+        """Creates a folder on the remote server with the specified numeric mode.
+
+        The mode is a plain integer, so it is best written as an octal literal (e.g. `0o755` rather
+        than `493`). Only the permission bits (`0o0000` to `0o7777`) may be set.
+
+        Servers usually apply their umask to the requested mode. When a mode is given explicitly
+        and the created folder ends up with different permissions, they are corrected afterwards.
+
+        Args:
+            dir (str): The directory to create.
+            mode (Optional[int], optional): POSIX-style permissions for the newly-created folder.
+                Defaults to `None`, which requests `0o777` (subject to the server's umask).
+            exist_ok (bool, optional): Whether to succeed silently if the directory already exists.
+                Defaults to `False`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def remove(self, path: str) -> None:
+        ...
+
+    def rmdir(self, dir: str) -> None:
+        ...
+
+    def rename(self, oldpath: str, newpath: str, overwrite: bool = True) -> None:
+        """Renames a file or folder on the remote server.
+
+        If the destination exists and `overwrite` is `True`, the rename is first attempted as is
+        (which replaces the destination atomically on servers that support it). If the server
+        refuses, the destination is removed and the rename retried, which is **not** atomic.
+
+        Args:
+            oldpath (str): The current path.
+            newpath (str): The new path.
+            overwrite (bool, optional): Whether to replace an existing destination. Defaults to `True`.
+
+        Raises:
+            FileExistsError: If the destination exists and `overwrite` is `False`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def open(self, filename: str, mode: str = 'r') -> File:
+        """Opens a file on the remote server.
+
+        Args:
+            filename (str): The name of the file (if file is in `cwd`) OR the path to the file.
+            mode (str, optional): Python-style file mode. Defaults to 'r'.
+
+        Returns:
+            The opened :class:`File`.
+        """
+
+        ...
+
+    def file(self, filename: str, mode: str = 'r') -> File:
+        """Opens a file on the remote server.
+
+        **NOTE**: This method is just an alias to :func:`SFTPClient.open` to mimic compatibility with paramiko.
+
+        Args:
+            filename (str): The name of the file (if file is in `cwd`) OR the path to the file.
+            mode (str, optional): Python-style file mode. Defaults to 'r'.
+
+        Returns:
+            The opened :class:`File`.
+        """
+
+        ...
+
+    def get(self, remotepath: str, localpath: str) -> None:
+        """Copies a file from the remote server to the local host.
+
+        Args:
+            remotepath (str): The remote file path.
+            localpath (str): The local path to copy the file to.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def put(self, localpath: str, remotepath: str) -> None:
+        """Copies a local file to the remote server.
+
+        Args:
+            localpath (str): The path to the local file.
+            remotepath (str): The remote path to copy the file to.
+
+        Returns:
+
+        """
+
+        ...
+
+    def get_compressed(self, remotepath: str, localpath: str) -> None:
+        """Copies a file from the remote server to the local host, compressing it with `gzip` on the
+        server and decompressing it locally as it arrives.
+
+        This greatly reduces transfer times for text-heavy files on slow links.
+
+        The file is downloaded to a temporary file next to `localpath`, which only replaces it once
+        the transfer has succeeded.
+
+        **NOTE**: This requires `gzip` to be available on the server.
+
+        Args:
+            remotepath (str): The remote file path.
+            localpath (str): The local path to copy the file to.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def put_compressed(self, localpath: str, remotepath: str) -> None:
+        """Copies a local file to the remote server, compressing it locally and decompressing it with
+        `gzip` on the server as it arrives.
+
+        This greatly reduces transfer times for text-heavy files on slow links.
+
+        **NOTE**: This requires `gzip` to be available on the server.
+
+        Args:
+            localpath (str): The path to the local file.
+            remotepath (str): The remote path to copy the file to.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def get_dir(self, remotepath: str, localpath: str, symlinks: str = "follow") -> None:
+        """Recursively copies a remote directory tree to the local host.
+
+        Missing local directories are created; existing files are overwritten.
+
+        Args:
+            remotepath (str): The remote directory.
+            localpath (str): The local directory to copy the tree to.
+            symlinks (str, optional): How symbolic links are handled: `"follow"` copies their targets
+                in their place, `"skip"` ignores them and `"preserve"` recreates them locally (which
+                is only supported on Unix). Following a link back to a directory that is being copied
+                raises an error. Defaults to `"follow"`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def put_dir(self, localpath: str, remotepath: str, symlinks: str = "follow") -> None:
+        """Recursively copies a local directory tree to the remote server.
+
+        Missing remote directories are created; existing files are overwritten.
+
+        Args:
+            localpath (str): The local directory.
+            remotepath (str): The remote directory to copy the tree to.
+            symlinks (str, optional): How symbolic links are handled: `"follow"` copies their targets
+                in their place, `"skip"` ignores them and `"preserve"` recreates them on the server.
+                Following a link back to a directory that is being copied raises an error. Defaults
+                to `"follow"`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def is_closed(self) -> bool:
+        """Checks if the SFTP session is closed.
+
+        Returns:
+            Whether SFTP session is closed.
+        """
+
+        ...
+
+    def close(self) -> None:
+        """Closes the SFTP session.
+
+        Returns:
+            None
+        """
+
+        ...
+
+
+class ForwardedChannel:
+    """A bidirectional channel opened by the server, e.g. for a connection to a forwarded port.
+    """
+
+    def recv(self, n: int, timeout: Optional[float] = None) -> bytes:
+        """Receives at most `n` bytes from the channel.
+
+        Returns an empty `bytes` object once the channel has reached EOF. If no data arrives before
+        the timeout expires, a `TimeoutError` is raised.
+
+        Args:
+            n (int): The maximum number of bytes to receive.
+            timeout (Optional[float], optional): The timeout (in seconds). Defaults to `None`, which
+                blocks until data is available.
+
+        Returns:
+            The received data.
+
+        Raises:
+            ValueError: If the timeout is negative or NaN.
+        """
+
+        ...
+
+    def send(self, data: Union[str, bytes, bytearray]) -> None:
+        """Sends the data over the channel.
+
+        Args:
+            data (Union[str, bytes, bytearray]): The data to send. Text is sent as UTF-8.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def eof(self) -> bool:
+        """Checks if the remote end has finished sending data.
+
+        Returns:
+            Whether the remote end has finished sending data.
+        """
+
+        ...
+
+    def close(self) -> None:
+        """Closes the channel.
+
+        If the channel is already closed, then this function does nothing.
+
+        Returns:
+            None
+        """
+
+        ...
+
+
+class RemoteForward:
+    """A port on the remote server that forwards incoming connections to the client.
+    """
+
+    port: int
+    """The port the server is listening on. When port 0 was requested, this is the port that the
+    server chose."""
+
+    def accept(self, timeout: Optional[float] = None) -> ForwardedChannel:
+        """Waits for the next incoming connection and returns its channel.
+
+        If no connection arrives before the timeout expires, a `TimeoutError` is raised.
+
+        Args:
+            timeout (Optional[float], optional): The timeout (in seconds). Defaults to `None`, which
+                waits indefinitely.
+
+        Returns:
+            The channel for the incoming connection.
+
+        Raises:
+            ValueError: If the timeout is negative or NaN.
+        """
+
+        ...
+
+
+class TransportMetrics:
+    """Transport-level statistics of an SSH session, as reported by :func:`SSHClient.metrics`.
+    """
+
+    bytes_sent: Optional[int]
+    """Bytes sent over the connection and acknowledged by the server (`None` if the platform does not
+    report it)."""
+
+    bytes_received: Optional[int]
+    """Bytes received over the connection (`None` if the platform does not report it)."""
+
+    channels_opened: int
+    """Number of channels opened by the client for commands and SFTP sessions."""
+
+    uptime: float
+    """Time since the session was established (in seconds)."""
+
+
+class NegotiatedMethods:
+    """The algorithms negotiated for an SSH session, as reported by
+    :func:`SSHClient.negotiated_methods`. Algorithms that may differ per direction are reported for both
+    the client-to-server (`_cs`) and the server-to-client (`_sc`) direction.
+    """
+
+    kex: str
+    """The key exchange algorithm (e.g. `curve25519-sha256`)."""
+
+    host_key: str
+    """The host key algorithm (e.g. `ssh-ed25519`)."""
+
+    cipher_cs: str
+    """The cipher from the client to the server (e.g. `aes256-gcm@openssh.com`)."""
+
+    cipher_sc: str
+    """The cipher from the server to the client."""
+
+    mac_cs: str
+    """The MAC algorithm from the client to the server (e.g. `hmac-sha2-256`)."""
+
+    mac_sc: str
+    """The MAC algorithm from the server to the client."""
+
+    compression_cs: str
+    """The compression algorithm from the client to the server (e.g. `none` or `zlib@openssh.com`)."""
+
+    compression_sc: str
+    """The compression algorithm from the server to the client."""
+
+
+class HostKey:
+    """A server's host key, as returned by :func:`SSHClient.get_host_key`.
+    """
+
+    key_type: str
+    """The key type (e.g. `ssh-ed25519`)."""
+
+    base64: str
+    """The key in SSH wire format, base64-encoded (as it appears in `known_hosts` files)."""
+
+    md5_fingerprint: str
+    """The legacy MD5 fingerprint of the key (`MD5:` followed by colon-separated hexadecimal bytes)."""
+
+    sha256_fingerprint: str
+    """The SHA256 fingerprint of the key (`SHA256:` followed by the unpadded base64 digest)."""
+
+
+class KnownHosts:
+    """The host keys of one or more `known_hosts` files.
+
+    Hashed host names (`|1|...`, as written with OpenSSH's `HashKnownHosts yes`) are supported, as are
+    the `@revoked` and `@cert-authority` markers.
+    """
+
+    def __init__(self, filename: Optional[str] = None) -> None:
+        """Creates a new :class:`KnownHosts`, optionally loading a `known_hosts` file.
+
+        Args:
+            filename (Optional[str], optional): The path to the `known_hosts` file to load.
+        """
+
+        ...
+
+    def load(self, filename: str) -> None:
+        """Loads the entries of a `known_hosts` file, in addition to the already known host keys.
+
+        Args:
+            filename (str): The path to the `known_hosts` file.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def check(self, host: str, key: Union[HostKey, str, bytes], port: Optional[int] = None) -> str:
+        """Checks a host key against the known host keys.
+
+        Args:
+            host (str): The host name or address.
+            key (Union[HostKey, str, bytes]): The host key: a :class:`HostKey`, a key in OpenSSH format
+                (`<type> <base64>`) or a key in SSH wire format.
+            port (Optional[int], optional): The SSH port. Defaults to 22.
+
+        **NOTE**: Validating certificates requires the `cryptography` package, installed with the
+        `certificates` extra (`pip install russh[certificates]`).
+
+        Returns:
+            `"match"` if the key is known for the host (or is a valid certificate signed by a CA marked
+            as `@cert-authority` for it), `"mismatch"` if the host is known with a different key of the
+            same type, `"revoked"` if the key or its CA is marked as `@revoked`,
+            `"invalid_certificate"` if the key is a certificate signed by a trusted CA but is not valid
+            for the host, or `"not_found"` otherwise.
+
+        Raises:
+            ValueError: If the key is malformed.
+        """
+
+        ...
+
+    def add(
+            self,
+            host: str,
+            key: Union[HostKey, str, bytes],
+            port: Optional[int] = None,
+            hashed: bool = False,
+    ) -> None:
+        """Adds a host key for a host. The key is only written to a file by :func:`KnownHosts.save`.
+
+        Args:
+            host (str): The host name or address.
+            key (Union[HostKey, str, bytes]): The host key: a :class:`HostKey`, a key in OpenSSH format
+                (`<type> <base64>`) or a key in SSH wire format.
+            port (Optional[int], optional): The SSH port. Defaults to 22.
+            hashed (bool, optional): Whether to hash the host name, like OpenSSH's
+                `HashKnownHosts yes`, so that the file does not reveal which hosts were connected to.
+                Defaults to `False`.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If the key is malformed.
+        """
+
+        ...
+
+    def save(self, filename: Optional[str] = None) -> None:
+        """Writes the loaded and added entries to a `known_hosts` file, replacing its contents.
+
+        Comments and entries that are not understood are written back unchanged.
+
+        Args:
+            filename (Optional[str], optional): The path to the `known_hosts` file. Defaults to the most
+                recently loaded file.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If no file name is given and no file was loaded.
+        """
+
+        ...
+
+
+class HostKeyStore:
+    """Base class for application-managed host key stores (e.g. backed by a database), which clients
+    consult instead of `known_hosts` files (see :func:`SSHClient.set_host_key_store`).
+
+    Subclasses implement :func:`HostKeyStore.get` and :func:`HostKeyStore.set`; any other object with
+    these methods can be used as well.
+    """
+
+    def __init__(self) -> None:
+        """Creates a new :class:`HostKeyStore`.
+        """
+
+        ...
+
+    def get(self, host: str, port: int) -> Optional[Union[HostKey, str, bytes]]:
+        """Returns the known host key of a host, or `None` if the host is not known.
+
+        Args:
+            host (str): The host name or address.
+            port (int): The SSH port.
+
+        Returns:
+            The host key: a :class:`HostKey`, a key in OpenSSH format (`<type> <base64>`) or a key in
+            SSH wire format.
+        """
+
+        ...
+
+    def set(self, host: str, port: int, key: HostKey) -> None:
+        """Stores the host key of a host, when a client accepts an unknown host with an
+        :class:`AutoAddPolicy`.
+
+        Args:
+            host (str): The host name or address.
+            port (int): The SSH port.
+            key (HostKey): The host key.
+
+        Returns:
+            None
+        """
+
+        ...
+
+
+class RejectPolicy:
+    """Missing host key policy that refuses to connect to hosts whose key is not in `known_hosts`.
+    """
+
+    def __init__(self) -> None:
+        """Creates a new :class:`RejectPolicy`.
+        """
+
+        ...
+
+
+class AutoAddPolicy:
+    """Missing host key policy that accepts hosts whose key is not in `known_hosts`, and adds their key
+    to it.
+    """
+
+    hashed: bool
+    """Whether the host names of added keys are hashed."""
+
+    def __init__(self, hashed: bool = False) -> None:
+        """Creates a new :class:`AutoAddPolicy`.
+
+        Args:
+            hashed (bool, optional): Whether to hash the host names of added keys, like OpenSSH's
+                `HashKnownHosts yes`. Defaults to `False`.
+        """
+
+        ...
+
+
+class WarningPolicy:
+    """Missing host key policy that accepts hosts whose key is not in `known_hosts`, emitting a
+    warning.
+    """
+
+    def __init__(self) -> None:
+        """Creates a new :class:`WarningPolicy`.
+        """
+
+        ...
+
+
+class SSHConfig:
+    """The host-specific options of OpenSSH client configuration files (`~/.ssh/config`).
+
+    `Host` blocks are supported, with `HostName`, `User`, `Port`, `IdentityFile` and `ProxyJump`
+    options; other options, `Match` blocks and `Include` directives are ignored.
+    """
+
+    def __init__(self, filename: Optional[str] = None) -> None:
+        """Creates a new :class:`SSHConfig`, optionally loading a configuration file.
+
+        Args:
+            filename (Optional[str], optional): Path to a configuration file (e.g. `~/.ssh/config`).
+                Defaults to `None`.
+        """
+
+        ...
+
+    def load(self, filename: str) -> None:
+        """Loads a configuration file. Its blocks are consulted after those of the already loaded
+        files.
+
+        Args:
+            filename (str): The path to the configuration file.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def lookup(self, host: str) -> Dict[str, Union[str, int, List[str]]]:
+        """Returns the options that apply to a host.
+
+        As with OpenSSH, the first value found for an option wins, except for `IdentityFile`, whose
+        values accumulate. `%h`, `%r`, `%u`, `%d`, `%%` and `~` are expanded in `HostName` and
+        `IdentityFile` values.
+
+        Args:
+            host (str): The host name, as given by the user.
+
+        Returns:
+            Dict[str, Union[str, int, List[str]]]: The options, with the keys `hostname` (the host
+                itself if no `HostName` is set), and `user`, `port`, `identityfile` (a list of paths)
+                and `proxyjump` if they are set.
+        """
+
+        ...
+
+
+class JumpHost:
+    """A jump host (bastion) to reach a server through, like OpenSSH's `ProxyJump`.
+    """
+
+    host: str
+    """The jump host's name or address."""
+
+    username: Optional[str]
+    """The SSH username on the jump host."""
+
+    port: Optional[int]
+    """The jump host's SSH port."""
+
+    def __init__(
             self,
             host: str,
-            username: str,
-            auth: AuthMethods,
-            port: int = 22,
-            timeout: int = 30,
+            username: Optional[str] = None,
+            auth: Optional[AuthMethods] = None,
+            port: Optional[int] = None,
+    ) -> None:
+        """Creates a new :class:`JumpHost`.
+
+        Arguments that are omitted fall back to the defaults registered for the jump host with
+        :func:`set_default`, if any.
+
+        Args:
+            host (str): The jump host's name or address.
+            username (Optional[str], optional): The SSH username on the jump host. Defaults to the local
+                user.
+            auth (Optional[AuthMethods], optional): The authentication methods to use on the jump host.
+                Defaults to the default private keys.
+            port (Optional[int], optional): The jump host's SSH port. Defaults to 22.
+        """
+
+        ...
+
+
+class SSHClient:
+    """The SSH client.
+
+    The client can be shared by several threads: operations on its session, including those made
+    through the command outputs, SFTP clients, files and forwarded ports opened on it, are run one at
+    a time. Threads waiting for their turn release the GIL.
+    """
+
+    def __init__(self) -> None:
+        """Creates a new SSH client.
+        """
+
+        ...
+
+    def connect(
+            self,
+            host: str,
+            username: Optional[str] = None,
+            auth: Optional[AuthMethods] = None,
+            port: Optional[int] = None,
+            timeout: Optional[int] = None,
+            family: Optional[str] = None,
+            resolver: Optional[Callable[[str], List[Tuple[str, int]]]] = None,
+            look_for_keys: bool = True,
+            expected_fingerprint: Optional[str] = None,
+            host_key_algorithms: Optional[List[str]] = None,
+            source_address: Optional[Tuple[str, int]] = None,
+            proxy: Optional[str] = None,
+            jump: Optional[Union[JumpHost, List[JumpHost]]] = None,
+            use_ssh_config: bool = False,
+            compress: bool = False,
+            algorithms: Optional[Dict[str, List[str]]] = None,
+            tcp_options: Optional[TcpOptions] = None,
     ) -> None:
         """Establishes an SSH connection and sets the created session on the client.
 
-        If multiple authentication methods are specified, then they are all attempted one at a time
-        (until one succeeds) in the following order:
+        If multiple authentication methods are specified, then they are all attempted one at a time
+        (until one succeeds) in the following order, unless :class:`AuthMethods` specifies another one:
+
+        :class:`PasswordAuth` > :class:`PrivateKeyAuth` > :class:`PrivateKeyMemoryAuth` >
+        :class:`AgentAuth` > :class:`HostBasedAuth` > :class:`KeyboardInteractiveAuth`
+
+        If all the authentication methods fail, an :class:`AuthenticationException` is raised, carrying
+        the message from the last attempted method. More specific errors, such as a
+        :class:`PassphraseRequiredException` or a timeout, are raised unchanged.
+
+        If the server requires several methods (e.g. a public key *and* a password), the remaining
+        methods are attempted again after each partial success, until the session is fully
+        authenticated.
+
+        Before authenticating, the server's host key is verified against `~/.ssh/known_hosts` and the
+        files loaded with :func:`SSHClient.load_host_keys`. If the host is known with a different key,
+        a :class:`BadHostKeyException` is raised; unknown hosts are handled according to the missing host
+        key policy (see :func:`SSHClient.set_missing_host_key_policy`). Host certificates signed by a CA
+        added with :func:`SSHClient.add_host_ca` are validated instead. A host key store set with
+        :func:`SSHClient.set_host_key_store` is consulted instead of the `known_hosts` files. A host key
+        verifier set with :func:`SSHClient.set_host_key_verifier` replaces these checks, and
+        `expected_fingerprint`, if given, replaces all of them.
+
+        Arguments that are omitted fall back to the defaults registered for the host with
+        :func:`set_default`, if any.
+
+        Args:
+            host (str): The host name or address. IPv6 addresses may be enclosed in brackets (e.g.
+                `"[::1]"`). A `unix://` URL (e.g. `"unix:///run/sshd.sock"`) connects to a server
+                listening on a Unix socket instead (only supported on Unix platforms), whose host key
+                is looked up in `known_hosts` under the URL; `port` is then only used for that lookup.
+            username (Optional[str], optional): The SSH username. Defaults to `None`, which uses the
+                local user (like `ssh host` does).
+            auth (Optional[AuthMethods], optional): The authentication methods to use. Required unless
+                defaults provide them, or the default private keys are looked for.
+            port (Optional[int], optional): The SSH port. Defaults to 22.
+            timeout (Optional[int], optional): The timeout for the TCP connection (in seconds).
+                Defaults to 30.
+            family (Optional[str], optional): The address family to connect over: `"any"`, `"ipv4"`
+                or `"ipv6"`. Defaults to `"any"`, in which case connection attempts to the host's IPv6
+                and IPv4 addresses are raced, so that a broken path does not delay the connection. If
+                the host has several addresses and none can be connected to, a
+                :class:`NoValidConnectionsError` listing every attempt is raised.
+            resolver (Optional[Callable[[str], List[Tuple[str, int]]]], optional): Callable used
+                instead of the system resolver. It is called with the host and must return a list of
+                `(ip, port)` tuples to connect to; `port` is then ignored. Defaults to `None`.
+            look_for_keys (bool, optional): Whether to try the default private keys
+                (`~/.ssh/id_ed25519`, `~/.ssh/id_ecdsa` and `~/.ssh/id_rsa`, like OpenSSH does) when no
+                authentication methods are provided. Keys protected by a passphrase are skipped.
+                Defaults to `True`.
+            expected_fingerprint (Optional[str], optional): SHA256 fingerprint of the server's host
+                key, as printed by `ssh-keygen -l` (e.g.
+                `"SHA256:nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8"`). If the key does not match, a
+                :class:`BadHostKeyException` is raised before authenticating.
+            host_key_algorithms (Optional[List[str]], optional): Host key algorithms to negotiate, in
+                order of preference (e.g. `["ssh-ed25519", "rsa-sha2-512"]`), so that the server
+                presents the key that is in `known_hosts`. Algorithms that are not listed are disabled.
+                List a `*-cert-v01@openssh.com` algorithm first to have the server present its host
+                certificate. Defaults to the `libssh2` defaults.
+            source_address (Optional[Tuple[str, int]], optional): `(ip, port)` tuple of the local
+                address to connect from, e.g. on hosts with several interfaces where firewalls only
+                allow a specific source address. Use port 0 to let the system pick a port. Only
+                addresses of the same family are connected to. Only supported on Unix platforms.
+            proxy (Optional[str], optional): URL of a proxy server to tunnel the connection through,
+                for networks where direct connections are blocked:
+                `socks5://[user[:password]@]host[:port]` for a SOCKS5 proxy (the port defaults to
+                1080), or `http://[user[:password]@]host[:port]` for an HTTP proxy supporting the
+                `CONNECT` method (the port defaults to 8080). Special characters in the username and
+                password must be percent-encoded. The proxy resolves the host; `family`, `resolver`
+                and `source_address` then apply to the connection to the proxy.
+            jump (Optional[Union[JumpHost, List[JumpHost]]], optional): :class:`JumpHost` (bastion) to
+                reach the host through, or list of jump hosts to go through in order, like OpenSSH's
+                `ProxyJump`. Each jump host's key is verified like the host's. The connection options
+                (`timeout`, `family`, `resolver`, `source_address` and `proxy`) then apply to the
+                connection to the first jump host. Only supported on Unix platforms.
+            use_ssh_config (bool, optional): Whether to apply the options of `~/.ssh/config` that
+                match the host (see :class:`SSHConfig`): `HostName`, `User`, `Port`, `IdentityFile`
+                (used instead of the default private keys when no authentication methods are
+                provided) and `ProxyJump`. Arguments that are given take precedence. Defaults to
+                `False`.
+            compress (bool, optional): Whether to request `zlib` compression of the transport during
+                negotiation, which speeds up bulk transfers over slow links. The connection is not
+                compressed if the server does not support it. Defaults to `False`.
+            algorithms (Optional[Dict[str, List[str]]], optional): Algorithms to negotiate, as a
+                dictionary mapping a type of algorithms (`"kex"`, `"cipher"`, `"mac"` or
+                `"compression"`) to the algorithms of that type, in order of preference (e.g.
+                `{"cipher": ["chacha20-poly1305@openssh.com", "aes256-gcm@openssh.com"]}`), so that
+                legacy algorithms can be disabled per policy. Algorithms that are not listed are
+                disabled; types that are not given keep the `libssh2` defaults. Also applies to the
+                jump hosts. Defaults to `None`.
+            tcp_options (Optional[TcpOptions], optional): Socket options for the TCP connection, e.g.
+                `TCP_NODELAY` for interactive sessions or larger buffers for bulk transfers. When going
+                through a proxy or jump hosts, they apply to the connection to the proxy or the first
+                jump host. Defaults to `None`, which keeps the system defaults.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def connect_socket(
+            self,
+            sock: Union[socket.socket, int],
+            host: str,
+            username: Optional[str] = None,
+            auth: Optional[AuthMethods] = None,
+            port: Optional[int] = None,
+            look_for_keys: bool = True,
+            expected_fingerprint: Optional[str] = None,
+            host_key_algorithms: Optional[List[str]] = None,
+            compress: bool = False,
+            algorithms: Optional[Dict[str, List[str]]] = None,
+    ) -> None:
+        """Establishes an SSH session over an already connected socket and sets it on the client, e.g.
+        when the connection is made through a custom proxy, or for testing.
+
+        Authentication works like with :func:`SSHClient.connect`. The socket's file descriptor is
+        duplicated, so the socket may be closed afterwards without affecting the session; it is
+        switched to blocking mode.
+
+        **NOTE**: Clients connected this way cannot be cloned with :func:`SSHClient.clone`. Only
+        supported on Unix platforms.
+
+        Args:
+            sock (Union[socket.socket, int]): The connected socket: a socket object (e.g. a
+                `socket.socket`) or a raw file descriptor.
+            host (str): The host name or address the socket is connected to, used to verify the host
+                key and to look up defaults.
+            username (Optional[str], optional): The SSH username. Defaults to the local user.
+            auth (Optional[AuthMethods], optional): The authentication methods to use. Required
+                unless defaults provide them, or the default private keys are looked for.
+            port (Optional[int], optional): The SSH port the socket is connected to, used to verify
+                the host key. Defaults to 22.
+            look_for_keys (bool, optional): Whether to try the default private keys when no
+                authentication methods are provided. Defaults to `True`.
+            expected_fingerprint (Optional[str], optional): SHA256 fingerprint of the server's host
+                key (see :func:`SSHClient.connect`). Defaults to `None`.
+            host_key_algorithms (Optional[List[str]], optional): Host key algorithms to negotiate, in
+                order of preference (see :func:`SSHClient.connect`). Defaults to `None`.
+            compress (bool, optional): Whether to request `zlib` compression of the transport.
+                Defaults to `False`.
+            algorithms (Optional[Dict[str, List[str]]], optional): Algorithms to negotiate, by type
+                (see :func:`SSHClient.connect`). Defaults to `None`.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If the file descriptor is invalid.
+        """
+
+        ...
+
+    def auth_methods(
+            self,
+            host: str,
+            username: str,
+            port: Optional[int] = None,
+            timeout: Optional[int] = None,
+    ) -> List[str]:
+        """Returns the authentication methods that a server accepts for a user (e.g. `publickey`,
+        `password` or `keyboard-interactive`), by connecting to it and making a `none`
+        authentication attempt.
+
+        The connection is closed again afterwards; the client itself is not affected. Returns an
+        empty list if the server accepts the `none` method, i.e. requires no authentication.
+
+        Arguments that are omitted fall back to the defaults registered for the host with
+        :func:`set_default`, if any.
+
+        Args:
+            host (str): The host name or address.
+            username (str): The SSH username.
+            port (Optional[int], optional): The SSH port. Defaults to 22.
+            timeout (Optional[int], optional): The timeout for the TCP connection (in seconds).
+                Defaults to 30.
+
+        Returns:
+            List[str]: The names of the accepted authentication methods.
+        """
+
+        ...
+
+    def clone(self) -> "SSHClient":
+        """Opens a new, independent session to the same destination, authenticating again with the
+        same arguments that this client was connected with.
+
+        The new client does not share any channels with this one, so it can be used in parallel
+        (e.g. from another thread). The channel retry policy is copied over.
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Returns:
+            The new client.
+        """
+
+        ...
+
+    def check_public_keys(
+            self,
+            host: str,
+            username: str,
+            public_keys: List[str],
+            port: Optional[int] = None,
+            timeout: Optional[int] = None,
+    ) -> List[str]:
+        """Returns the public keys that a server would accept for a user, by connecting to it and
+        asking about each key without signing anything.
+
+        This lets tools pick the right key out of many before authenticating. Note that servers may
+        still count rejected keys towards their limit of authentication attempts (`MaxAuthTries`).
+
+        The connection is closed again afterwards; the client itself is not affected. Arguments that
+        are omitted fall back to the defaults registered for the host with :func:`set_default`, if
+        any.
+
+        Args:
+            host (str): The host name or address.
+            username (str): The SSH username.
+            public_keys (List[str]): The public keys to check, each either the path to a public-key
+                file or a key in OpenSSH format (e.g. `ssh-ed25519 AAAA...`).
+            port (Optional[int], optional): The SSH port. Defaults to 22.
+            timeout (Optional[int], optional): The timeout for the TCP connection (in seconds).
+                Defaults to 30.
+
+        Returns:
+            List[str]: The accepted keys, as given in `public_keys`.
+
+        Raises:
+            ValueError: If one of the public keys is malformed.
+        """
+
+        ...
+
+    def negotiated_methods(self) -> NegotiatedMethods:
+        """Returns the algorithms negotiated for the session (key exchange, host key, cipher, MAC and
+        compression), e.g. for compliance audits or to debug interoperability issues.
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Returns:
+            NegotiatedMethods: The negotiated algorithms.
+        """
+
+        ...
+
+    def is_active(self, timeout: Optional[float] = None) -> bool:
+        """Checks whether the session is still usable, by making a round trip to the server: a channel
+        is opened and closed right away. Unlike checking that :func:`SSHClient.connect` was called,
+        this detects connections that were dropped or whose server stopped responding, e.g. before
+        queuing work on the client.
+
+        The server refusing the channel (e.g. because of its `MaxSessions` limit) still counts as a
+        response.
+
+        Args:
+            timeout (Optional[float], optional): Time to wait for the server to respond (in seconds).
+                Defaults to 5.
+
+        Returns:
+            bool: Whether the server responded. `False` if there is no active SSH session.
+
+        Raises:
+            ValueError: If the timeout is not a positive, finite number.
+        """
+
+        ...
+
+    def get_auth_banner(self) -> Optional[str]:
+        """Returns the banner the server sent before authentication (e.g. legal notices or warnings).
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Returns:
+            The banner, or `None` if the server did not send one.
+        """
+
+        ...
+
+    def get_server_banner(self) -> str:
+        """Returns the identification string the server sent at the start of the connection (e.g.
+        `"SSH-2.0-OpenSSH_9.6"`), e.g. to log or audit server versions.
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Returns:
+            The identification string.
+        """
+
+        ...
+
+    def get_host_key(self) -> HostKey:
+        """Returns the host key the server presented during the handshake, e.g. to log or display its
+        fingerprint for out-of-band verification.
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Returns:
+            The host key.
+        """
+
+        ...
+
+    def metrics(self) -> TransportMetrics:
+        """Returns transport-level statistics of the session, e.g. for exporting to a monitoring
+        system.
+
+        **NOTE**: Byte counts come from the operating system's TCP statistics, which are currently only
+        available on Linux. `libssh2` does not report key re-exchanges, so they are not included.
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Returns:
+            The session's statistics.
+        """
+
+        ...
+
+    def open_sftp(self, auto_reopen: bool = False) -> SFTPClient:
+        """Opens an SFTP session using the SSH session.
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Args:
+            auto_reopen (bool, optional): Whether to transparently reopen the SFTP subsystem on the
+                next operation if its channel dies while the SSH session survives (e.g. when the
+                server restarts the subsystem). The operation that discovers the lost channel still
+                fails. Defaults to `False`.
+
+        Returns:
+            The SFTP client.
+        """
+
+        ...
+
+    def put_sudo(
+            self,
+            localpath: str,
+            remotepath: str,
+            owner: Optional[str] = None,
+            mode: int = 0o644,
+            sudo_password: Optional[str] = None,
+    ) -> None:
+        """Uploads a local file to a path that the SSH user cannot write to (e.g. under `/etc`).
+
+        The file is first copied over SFTP to a temporary file, in a private directory created with
+        `mktemp -d` so that other users on the server cannot tamper with it. It is then moved into
+        place, and has its owner and permissions set, with `sudo`. The temporary directory is removed
+        afterwards, whether or not the upload succeeded.
+
+        **NOTE**: This requires a POSIX shell and `sudo` on the server. If `sudo_password` is not
+        given, `sudo` must not ask for a password.
+
+        Args:
+            localpath (str): The path to the local file.
+            remotepath (str): The remote path to move the file to.
+            owner (Optional[str], optional): The owner of the file, as `user` or `user:group`.
+                Defaults to the user `sudo` runs commands as (usually `root`).
+            mode (int, optional): The permissions of the file. Defaults to `0o644`.
+            sudo_password (Optional[str], optional): The password to give `sudo` if it asks for one.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def set_channel_retry(self, retries: int = 0, delay: Optional[float] = None) -> None:
+        """Configures how opening a channel is retried after the server refuses it (e.g. when its
+        `MaxSessions` limit is reached). Other failures, such as a lost connection, are not retried.
+
+        If opening a channel still fails after all retries, a :class:`ChannelException` is raised.
+        Unless the connection was lost, the session itself remains usable.
+
+        Args:
+            retries (int, optional): Number of times to retry. Defaults to 0 (no retries).
+            delay (Optional[float], optional): Delay between attempts (in seconds). Defaults to 1.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If the delay is negative or NaN.
+        """
+
+        ...
+
+    def load_host_keys(self, filename: str) -> None:
+        """Loads the host keys of a `known_hosts` file, which are used in addition to
+        `~/.ssh/known_hosts` to verify the servers the client connects to.
+
+        The file is read again on every connection, so changes to it are picked up.
+
+        Args:
+            filename (str): The path to the `known_hosts` file.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def set_missing_host_key_policy(
+            self,
+            policy: Union[RejectPolicy, AutoAddPolicy, WarningPolicy],
+    ) -> None:
+        """Sets what the client does when connecting to a host whose key is not in `known_hosts`.
+
+        With an :class:`AutoAddPolicy`, the key is added to the most recently loaded file (see
+        :func:`SSHClient.load_host_keys`), or to `~/.ssh/known_hosts`. With a :class:`RejectPolicy`,
+        a :class:`BadHostKeyException` is raised. The default is a :class:`WarningPolicy`.
+
+        Args:
+            policy (Union[RejectPolicy, AutoAddPolicy, WarningPolicy]): The policy.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def set_host_key_verifier(
+            self,
+            verifier: Optional[Callable[[str, str, bytes, str], bool]],
+    ) -> None:
+        """Sets a callable that verifies the host keys of the servers the client connects to, for
+        applications with their own trust store (e.g. a database, or a prompt asking the user).
+
+        The verifier replaces the `known_hosts` checks and the missing host key policy. It is called
+        with the host name (`[host]:port` for ports other than 22), the key type, the key in SSH wire
+        format and its SHA256 fingerprint, and must return `True` to accept the key; otherwise a
+        :class:`BadHostKeyException` is raised. Exceptions raised by the verifier are propagated.
+
+        Args:
+            verifier (Optional[Callable[[str, str, bytes, str], bool]]): The verifier, or `None` to go
+                back to checking `known_hosts`.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def add_host_ca(self, key: Union[HostKey, str, bytes], hosts: Optional[List[str]] = None) -> None:
+        """Trusts a CA to sign the host certificates of the servers the client connects to, in
+        addition to the `@cert-authority` entries of `known_hosts`.
+
+        Servers presenting a certificate signed by the CA are accepted if the certificate is valid for
+        the host: it must be a host certificate, list the host among its principals (if it lists any),
+        be within its validity period and carry a valid signature. Otherwise, a
+        :class:`BadHostKeyException` is raised.
+
+        **NOTE**: Servers only present their certificate if a certificate algorithm is preferred (see
+        the `host_key_algorithms` argument of :func:`SSHClient.connect`). Verifying the signature
+        requires the `cryptography` package, installed with the `certificates` extra
+        (`pip install russh[certificates]`).
+
+        Args:
+            key (Union[HostKey, str, bytes]): The CA's public key: a :class:`HostKey`, a key in OpenSSH
+                format (`<type> <base64>`) or a key in SSH wire format.
+            hosts (Optional[List[str]], optional): Patterns of the hosts the CA is trusted for, as in
+                `known_hosts` files (e.g. `"*.example.com"`). Defaults to all hosts.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If the key or a host pattern is invalid.
+        """
+
+        ...
+
+    def set_host_key_store(self, store: Optional[HostKeyStore]) -> None:
+        """Sets an application-managed host key store (see :class:`HostKeyStore`), e.g. backed by a
+        database, which is consulted instead of the `known_hosts` files.
+
+        The store holds one key per host and port: a different key is rejected with a
+        :class:`BadHostKeyException`, and unknown hosts are handled according to the missing host key
+        policy, with an :class:`AutoAddPolicy` adding their key to the store. Exceptions raised by the
+        store are propagated.
+
+        Args:
+            store (Optional[HostKeyStore]): The store, or `None` to go back to checking `known_hosts`.
+
+        Returns:
+            None
+        """
 
-        :class:`PasswordAuth` > :class:`PrivateKeyAuth`
+        ...
 
-        If all the authentication methods fail, the exception from the last attempted method is raised.
+    def set_client_banner(self, banner: Optional[str] = None) -> None:
+        """Sets the identification string sent to servers at the start of later connections, instead
+        of the `libssh2` one (e.g. `"SSH-2.0-libssh2_1.11.0"`), e.g. for intrusion detection systems
+        that only allow specific clients.
 
         Args:
-            host (str): The host name or address.
-            username (str): The SSH username.
-            auth (str): The authentication methods to use.
-            port (int, optional): The SSH port. Defaults to 22.
-            timeout (int, optional): The timeout for the TCP connection (in seconds). Defaults to 30.
+            banner (Optional[str], optional): The identification string, e.g.
+                `"SSH-2.0-MyClient_1.0"`. The `SSH-2.0-` prefix is added if it is missing. `None`
+                restores the `libssh2` one.
 
         Returns:
             None
+
+        Raises:
+            ValueError: If the banner is invalid.
         """
 
         ...
 
-    def open_sftp(self) -> SFTPClient:
-        """Opens an SFTP session using the SSH session.
+    def set_timeout(self, seconds: Optional[float] = None) -> None:
+        """Sets the timeout for blocking operations on the session (e.g. reading a command's output,
+        writing to a channel or SFTP calls), so that they fail with a `TimeoutError` instead of
+        hanging forever when the server stops responding. By default, there is no timeout.
 
-        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+        The timeout applies to the current session, including its open channels and SFTP clients, and
+        to the sessions established by later calls to :func:`SSHClient.connect` (including
+        authentication). It is unrelated to the `timeout` argument of :func:`SSHClient.connect`,
+        which only applies to the TCP connection.
+
+        **NOTE**: Operations that take their own timeout (e.g. :func:`ExecOutput.read_stdout`) use it
+        instead while they run.
+
+        Args:
+            seconds (Optional[float], optional): The timeout (in seconds). `None` or 0 disables the
+                timeout. Timeouts longer than about 49 days are capped.
 
         Returns:
-            The SFTP client.
+            None
+
+        Raises:
+            ValueError: If the timeout is negative or not finite.
+        """
+
+        ...
+
+    def set_connect_retry(
+            self,
+            retries: int = 0,
+            delay: Optional[float] = None,
+            jitter: Optional[float] = None,
+    ) -> None:
+        """Configures how :func:`SSHClient.connect` retries transient failures while connecting (e.g. a
+        connection reset during the handshake, which is common with load-balanced bastions).
+
+        Connection errors, timeouts and handshake failures are retried with an exponential backoff:
+        the delay doubles after every retry, plus a random jitter. Authentication failures are never
+        retried.
+
+        Args:
+            retries (int, optional): Number of times to retry. Defaults to 0 (no retries).
+            delay (Optional[float], optional): Delay before the first retry (in seconds). Defaults to 1.
+            jitter (Optional[float], optional): Maximum random delay added to every retry delay (in
+                seconds). Defaults to 0.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If the delay or jitter is negative or NaN.
+        """
+
+        ...
+
+    def set_reconnect_policy(self, retries: int = 0, delay: Optional[float] = None) -> None:
+        """Configures how the session is re-established when the connection to the server is lost (e.g.
+        after a network outage or a server restart).
+
+        When starting a command with :func:`SSHClient.exec_command` fails because the connection was
+        lost, the session is re-established with the arguments of the last :func:`SSHClient.connect`
+        call (authenticating again), and the command is started once more. SFTP clients opened
+        afterwards with :func:`SSHClient.open_sftp` likewise re-establish the session and run the
+        failed operation once more. Failed attempts to re-establish the session are retried with an
+        exponential backoff.
+
+        **NOTE**: Channels, streams and port forwards of the lost session are not restored.
+
+        Args:
+            retries (int, optional): Maximum number of attempts to re-establish the session. Defaults
+                to 0 (never re-establish it).
+            delay (Optional[float], optional): Delay before the first attempt (in seconds), doubled
+                for every further attempt. Defaults to 1.
+
+        Returns:
+            None
+
+        Raises:
+            ValueError: If the delay is negative or NaN.
         """
 
         ...
 
-    def exec_command(self, command: str) -> ExecOutput:
+    def exec_command(
+        self,
+        command: str,
+        pty: Optional[PtyOptions] = None,
+        stdin_file: Optional[Union[str, BinaryIO]] = None,
+        cwd: Optional[str] = None,
+    ) -> ExecOutput:
         """Executes a command using the established session and returns the output.
 
+        **NOTE**: When a PTY is allocated, the command's `stderr` is written to the terminal and so
+        ends up in `stdout`. If :class:`PtyOptions` disables `merge_stderr`, `stderr` is instead
+        redirected to a temporary file on the server (which requires a POSIX shell), and only becomes
+        readable once the command has finished.
+
         Args:
             command (str): The command to run.
+            pty (Optional[PtyOptions], optional): Options for allocating a pseudo-terminal (PTY) for the
+                command. Defaults to `None`, which allocates no PTY.
+            stdin_file (Optional[Union[str, BinaryIO]], optional): Local file path or file-like object
+                whose contents are streamed into the command's `stdin`, which is then closed. The source
+                is read in chunks, so it is never loaded into memory as a whole. Defaults to `None`.
+            cwd (Optional[str], optional): Remote directory to run the command in (which requires a
+                POSIX shell). A `FileNotFoundError` is raised if it does not exist. Defaults to the
+                user's login directory.
 
         Returns:
             The command's output.
@@ -338,11 +2264,491 @@ class SSHClient:
 
         ...
 
+    def request_port_forward(self, port: int = 0, address: Optional[str] = None) -> RemoteForward:
+        """Asks the server to listen on a port and forward incoming connections to the client.
+
+        Pass port 0 to let the server choose a free port; the chosen port is available through
+        :attr:`RemoteForward.port`.
+
+        Args:
+            port (int, optional): The port to listen on. Defaults to 0.
+            address (Optional[str], optional): The address to listen on. Defaults to `None`, which
+                leaves the choice to the server (usually all interfaces, subject to `GatewayPorts`).
+
+        Returns:
+            The remote forward.
+        """
+
+        ...
+
+    def environ(self) -> Dict[str, str]:
+        """Returns the environment variables seen by commands run on the remote server.
+
+        `env -0` is tried first (since it handles multi-line values unambiguously), falling back to
+        `printenv` and then `set` (for servers whose default shell is `cmd.exe`).
+
+        Returns:
+            The environment variables.
+        """
+
+        ...
+
+    def getenv(self, name: str) -> Optional[str]:
+        """Returns the value of an environment variable seen by commands run on the remote server.
+
+        Args:
+            name (str): The name of the environment variable.
+
+        Returns:
+            The value of the variable, or `None` if it is not set.
+        """
+
+        ...
+
+    def disconnect(self, message: Optional[str] = None) -> None:
+        """Disconnects from the server: sends it a disconnect message with an optional description,
+        then closes the connection. Channels and SFTP clients of the session stop working.
+
+        The session is closed even if sending the message fails, in which case the error is raised
+        afterwards.
+
+        Fails if there is no active SSH session (if :func:`SSHClient.connect` was not called).
+
+        Args:
+            message (Optional[str], optional): Human-readable reason for disconnecting, which servers
+                usually log (at most 256 bytes). Defaults to `None`.
+
+        Returns:
+            None
+        """
+
+        ...
+
     def close(self):
-        """Closes the underlying session.
+        """Closes the underlying session, notifying the server with a disconnect message (see
+        :func:`SSHClient.disconnect`). Does nothing if there is no active SSH session.
+
+        Operations that other threads are running on the session are completed first.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def __enter__(self) -> "SSHClient":
+        ...
+
+    def __exit__(self, exc_type, exc_value, traceback) -> bool:
+        ...
+
+
+class ConnectionPool:
+    """A pool of authenticated SSH sessions, reused across operations on the same host, port and
+    username, like OpenSSH's `ControlMaster`. This saves a TCP connection, a handshake and an
+    authentication for each of the many short commands an application may run.
+
+    A new session is opened when every session to the destination already has the maximum number of
+    channels open. Sessions that have not been used for a while are closed.
+    """
+
+    size: int
+    """The number of sessions in the pool."""
+
+    def __init__(
+            self,
+            max_channels: Optional[int] = None,
+            idle_timeout: Optional[float] = None,
+            timeout: Optional[int] = None,
+    ) -> None:
+        """Creates a new, empty :class:`ConnectionPool`.
+
+        Args:
+            max_channels (Optional[int], optional): Maximum number of channels open at once on a
+                session, which should not exceed the server's `MaxSessions`. Defaults to 10.
+            idle_timeout (Optional[float], optional): Time after which a session that has no channel
+                in use is closed (in seconds). Defaults to 300.
+            timeout (Optional[int], optional): The timeout for TCP connections (in seconds). Defaults
+                to 30.
+
+        Raises:
+            ValueError: If `max_channels` is 0, or `idle_timeout` is negative or NaN.
+        """
+
+        ...
+
+    def exec_command(
+            self,
+            host: str,
+            command: str,
+            username: Optional[str] = None,
+            auth: Optional[AuthMethods] = None,
+            port: Optional[int] = None,
+            pty: Optional[PtyOptions] = None,
+            cwd: Optional[str] = None,
+    ) -> ExecOutput:
+        """Executes a command on a pooled session to the host, and returns the output (see
+        :func:`SSHClient.exec_command`).
+
+        The command's channel counts towards the session's maximum until the output is closed (or the
+        command has finished and its exit status was read), or is no longer referenced.
+
+        Args:
+            host (str): The host name or address.
+            command (str): The command to run.
+            username (Optional[str], optional): The SSH username. Defaults to the local user.
+            auth (Optional[AuthMethods], optional): The authentication methods to use if a new
+                session has to be opened. Required unless defaults provide them, or the default
+                private keys can be used.
+            port (Optional[int], optional): The SSH port. Defaults to the registered defaults, or 22.
+            pty (Optional[PtyOptions], optional): Options for allocating a pseudo-terminal (PTY) for
+                the command. Defaults to `None`.
+            cwd (Optional[str], optional): Remote directory to run the command in. Defaults to
+                `None`.
+
+        Returns:
+            ExecOutput: The output of the command.
+        """
+
+        ...
+
+    def open_sftp(
+            self,
+            host: str,
+            username: Optional[str] = None,
+            auth: Optional[AuthMethods] = None,
+            port: Optional[int] = None,
+    ) -> SFTPClient:
+        """Opens an SFTP session on a pooled session to the host (see :func:`SSHClient.open_sftp`).
+
+        The SFTP session's channel counts towards the session's maximum until it is closed, or is no
+        longer referenced.
+
+        Args:
+            host (str): The host name or address.
+            username (Optional[str], optional): The SSH username. Defaults to the local user.
+            auth (Optional[AuthMethods], optional): The authentication methods to use if a new
+                session has to be opened.
+            port (Optional[int], optional): The SSH port. Defaults to the registered defaults, or 22.
+
+        Returns:
+            SFTPClient: The SFTP client.
+        """
+
+        ...
+
+    def reap(self) -> None:
+        """Closes the sessions that have no channel in use and have not been used for longer than the
+        idle timeout, as well as those whose connection to the server was lost.
+
+        This is done automatically whenever the pool hands out a channel.
+
+        Returns:
+            None
+        """
+
+        ...
+
+    def close(self) -> None:
+        """Closes every session in the pool.
+
+        Channels that are still in use are closed along with their session.
 
         Returns:
             None
         """
 
         ...
+
+
+class ConnectOptions:
+    """The options for connecting to a single host.
+    """
+
+    host: str
+    """The host name or address."""
+
+    port: Optional[int]
+    """The SSH port (`None` to use the registered defaults, or 22)."""
+
+    username: Optional[str]
+    """The SSH username (`None` to use the local user)."""
+
+    auth: Optional[str]
+    """Name of the credentials to authenticate with, looked up in the credentials passed to
+    :func:`ConnectOptions.connect`."""
+
+    groups: List[str]
+    """Names of the groups the host belongs to."""
+
+    def __init__(
+            self,
+            host: str,
+            port: Optional[int] = None,
+            username: Optional[str] = None,
+            auth: Optional[str] = None,
+            groups: Optional[List[str]] = None,
+    ) -> None:
+        """Creates new connection options.
+
+        Args:
+            host (str): The host name or address.
+            port (Optional[int], optional): The SSH port. Defaults to the registered defaults, or 22.
+            username (Optional[str], optional): The SSH username. Defaults to the local user.
+            auth (Optional[str], optional): Name of the credentials to authenticate with.
+            groups (Optional[List[str]], optional): Names of the groups the host belongs to.
+        """
+
+        ...
+
+    def connect(
+            self,
+            credentials: Optional[Dict[str, AuthMethods]] = None,
+            timeout: Optional[int] = None,
+    ) -> SSHClient:
+        """Connects a new SSH client to the host.
+
+        If the host has no `auth` reference, the authentication methods registered with
+        :func:`set_default` are used, falling back to the default private keys.
+
+        Args:
+            credentials (Optional[Dict[str, AuthMethods]], optional): Mapping of credential names to
+                authentication methods, used to resolve the host's `auth` reference.
+            timeout (Optional[int], optional): The timeout for the TCP connection (in seconds).
+                Defaults to 30.
+
+        Returns:
+            SSHClient: The connected client.
+        """
+
+        ...
+
+
+class Group:
+    """A named group of hosts from an inventory.
+    """
+
+    name: str
+    """The name of the group."""
+
+    hosts: List[ConnectOptions]
+    """The hosts in the group, in inventory order."""
+
+
+def wait(channels: List[ExecOutput], timeout: Optional[float] = None) -> List[ExecOutput]:
+    """Blocks until any of the given commands has output available to read or has finished.
+
+    Args:
+        channels (List[ExecOutput]): The command outputs to wait on.
+        timeout (Optional[float], optional): The timeout (in seconds). Defaults to `None`, which
+            waits indefinitely.
+
+    Returns:
+        The subset of `channels` that are ready, which is empty if the timeout expired first.
+
+    Raises:
+        ValueError: If the timeout is negative or NaN.
+    """
+
+    ...
+
+
+def copy_between(src_client: SSHClient, src_path: str, dst_client: SSHClient, dst_path: str) -> int:
+    """Copies a file from one server to another, streaming the data through the local host without
+    staging it on disk.
+
+    The destination file is created (or truncated) with the permissions of the source file.
+
+    Args:
+        src_client (SSHClient): The client connected to the source server.
+        src_path (str): The path to the file on the source server.
+        dst_client (SSHClient): The client connected to the destination server.
+        dst_path (str): The path to copy the file to on the destination server.
+
+    Returns:
+        The number of bytes copied.
+    """
+
+    ...
+
+
+def quote(arg: str) -> str:
+    """Quotes a string for safe use as a single word in a POSIX shell command.
+
+    Words made up only of characters that are never special to the shell are returned unchanged.
+
+    Args:
+        arg (str): The string to quote.
+
+    Returns:
+        The quoted string.
+    """
+
+    ...
+
+
+def quote_command(argv: List[str]) -> str:
+    """Builds a POSIX shell command line from a list of arguments, quoting each one as needed.
+
+    Args:
+        argv (List[str]): The command and its arguments.
+
+    Returns:
+        The command line.
+    """
+
+    ...
+
+
+def convert_ppk(
+        ppk_path: str,
+        openssh_path: Optional[str] = None,
+        passphrase: Optional[str] = None,
+) -> str:
+    """Converts a PuTTY private key (PPK format version 2 or 3) into the OpenSSH format, unencrypted.
+
+    RSA, DSA, ECDSA and Ed25519 keys are supported, as are keys encrypted with a passphrase in
+    version 2 of the format. The key's MAC is verified. :class:`PrivateKeyAuth` converts PPK files
+    automatically; this is only needed to keep a converted copy of the key.
+
+    Args:
+        ppk_path (str): The path to the PPK file.
+        openssh_path (Optional[str], optional): Path to write the converted key to (with `0600`
+            permissions on UNIX). Defaults to `None`.
+        passphrase (Optional[str], optional): The passphrase the PPK file is encrypted with.
+            Defaults to `None`.
+
+    Returns:
+        The converted private key.
+
+    Raises:
+        PassphraseRequiredException: If the key is encrypted and no passphrase was given.
+        ValueError: If the passphrase is wrong, or the key is malformed, encrypted in version 3 of
+            the format or of an unsupported type.
+    """
+
+    ...
+
+
+def supported_key_types() -> List[str]:
+    """Returns the types of keys that the `libssh2` build in use supports, as the supported
+    algorithms depend on how it was compiled.
+
+    Returns:
+        The key types, e.g. `ssh-ed25519`, `ecdsa-sha2-nistp256` or `ssh-rsa`.
+    """
+
+    ...
+
+
+def set_default(
+        host_pattern: str,
+        port: Optional[int] = None,
+        auth: Optional[AuthMethods] = None,
+        timeout: Optional[int] = None,
+        known_hosts: Optional[str] = None,
+) -> None:
+    """Registers connection defaults for hosts matching a pattern.
+
+    The defaults are used by :func:`SSHClient.connect` for any argument that is not passed
+    explicitly. Registering the same pattern again replaces its defaults. When several patterns
+    match a host, each setting is taken from the first pattern registered that provides it.
+
+    Args:
+        host_pattern (str): The host pattern. `*` matches any run of characters and `?` matches
+            exactly one; matching is case-insensitive.
+        port (Optional[int], optional): The default SSH port.
+        auth (Optional[AuthMethods], optional): The default authentication methods.
+        timeout (Optional[int], optional): The default TCP connection timeout (in seconds).
+        known_hosts (Optional[str], optional): A `known_hosts` file used to verify the host key, in
+            addition to `~/.ssh/known_hosts` and the files loaded with
+            :func:`SSHClient.load_host_keys`. Host keys added by an :class:`AutoAddPolicy` are written
+            to it.
+
+    Returns:
+        None
+    """
+
+    ...
+
+
+def clear_defaults(host_pattern: Optional[str] = None) -> None:
+    """Removes registered connection defaults.
+
+    Args:
+        host_pattern (Optional[str], optional): The pattern to remove the defaults of. Defaults to
+            removing all defaults.
+
+    Returns:
+        None
+    """
+
+    ...
+
+
+def set_audit_hook(
+        hook: Optional[Callable[[str, str, str, float, Optional[int]], None]] = None,
+) -> None:
+    """Registers a callable that is invoked for every command executed by any client.
+
+    The hook is called with `(host, username, command, timestamp, exit_status)` once the command's
+    exit status is known, where `timestamp` is when the command was started (as a UNIX
+    timestamp). If the command's output is closed or discarded before it finishes, `exit_status`
+    is `None`. Exceptions raised by the hook are reported but otherwise ignored.
+
+    Args:
+        hook (Optional[Callable[[str, str, str, float, Optional[int]], None]], optional): The
+            callable to register. Defaults to `None`, which removes the current hook.
+
+    Returns:
+        None
+    """
+
+    ...
+
+
+def set_host_key_change_hook(
+        hook: Optional[Callable[[str, Optional[HostKey], Optional[HostKey], float], None]] = None,
+) -> None:
+    """Registers a callable that is invoked whenever a client connects to a known host that presents
+    a different host key than the known one (as opposed to a host that is not known at all), e.g. to
+    alert on a possible man-in-the-middle attack.
+
+    The hook is called with `(host, expected_key, actual_key, timestamp)`, where `host` is the host
+    name (`[host]:port` for ports other than 22), the keys are :class:`HostKey` objects and
+    `timestamp` is when the change was detected (as a UNIX timestamp). It only observes the change:
+    the connection still fails with a :class:`BadHostKeyException`. Exceptions raised by the hook
+    are reported but otherwise ignored.
+
+    Args:
+        hook (Optional[Callable[[str, Optional[HostKey], Optional[HostKey], float], None]], optional):
+            The callable to register. Defaults to `None`, which removes the current hook.
+
+    Returns:
+        None
+    """
+
+    ...
+
+
+def load_inventory(path: str, format: Optional[str] = None) -> Dict[str, Group]:
+    """Loads a host inventory and returns its groups, keyed by name.
+
+    Every host entry has a `host` and optionally a `port`, `user`, `auth` (the name of the
+    credentials to authenticate with) and `groups`. JSON and YAML inventories are either a list of
+    host entries or a mapping with such a list under `hosts`; in INI inventories, every section is
+    a host entry named after the host, with `groups` given as a comma-separated list. Every host is
+    also part of the `all` group.
+
+    **NOTE**: YAML inventories require the `PyYAML` package.
+
+    Args:
+        path (str): Path to the inventory file.
+        format (Optional[str], optional): The format of the inventory: `"json"`, `"yaml"` or
+            `"ini"`. Defaults to guessing it from the file extension.
+
+    Returns:
+        Dict[str, Group]: The groups of the inventory.
+    """
+
+    ...
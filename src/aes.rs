@@ -0,0 +1,218 @@
+//! AES-256 decryption, used to read passphrase-protected PuTTY private keys.
+
+/// Block size of AES (in bytes).
+pub const BLOCK_SIZE: usize = 16;
+/// Number of rounds of AES-256.
+const ROUNDS: usize = 14;
+/// The AES S-box.
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+/// The inverse of the AES S-box.
+const INV_SBOX: [u8; 256] = invert(&SBOX);
+
+/// Computes the inverse of a substitution box.
+///
+/// # Arguments
+///
+/// * `sbox` - The substitution box.
+const fn invert(sbox: &[u8; 256]) -> [u8; 256] {
+    let mut inverse = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        inverse[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+
+    inverse
+}
+
+/// Multiplies an element of GF(2^8) by `x`.
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+/// Multiplies two elements of GF(2^8).
+fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+
+        a = xtime(a);
+        b >>= 1;
+    }
+
+    product
+}
+
+/// Expands a 256-bit key into the round keys.
+///
+/// # Arguments
+///
+/// * `key` - The key.
+fn expand_key(key: &[u8; 32]) -> [[u8; BLOCK_SIZE]; ROUNDS + 1] {
+    let mut words = [[0u8; 4]; 4 * (ROUNDS + 1)];
+    let mut rcon = 1;
+
+    for (word, chunk) in words.iter_mut().zip(key.chunks(4)) {
+        word.copy_from_slice(chunk);
+    }
+
+    for i in 8..words.len() {
+        let mut temp = words[i - 1];
+
+        if i % 8 == 0 {
+            temp.rotate_left(1);
+            temp = temp.map(|b| SBOX[b as usize]);
+            temp[0] ^= rcon;
+            rcon = xtime(rcon);
+        } else if i % 8 == 4 {
+            temp = temp.map(|b| SBOX[b as usize]);
+        }
+
+        for (j, b) in temp.iter().enumerate() {
+            words[i][j] = words[i - 8][j] ^ b;
+        }
+    }
+
+    let mut round_keys = [[0; BLOCK_SIZE]; ROUNDS + 1];
+    for (round_key, words) in round_keys.iter_mut().zip(words.chunks(4)) {
+        for (chunk, word) in round_key.chunks_mut(4).zip(words) {
+            chunk.copy_from_slice(word);
+        }
+    }
+
+    round_keys
+}
+
+/// Decrypts a single block in place.
+///
+/// # Arguments
+///
+/// * `round_keys` - The expanded key.
+/// * `block` - The block to decrypt.
+fn decrypt_block(round_keys: &[[u8; BLOCK_SIZE]; ROUNDS + 1], block: &mut [u8]) {
+    for round in (0..=ROUNDS).rev() {
+        for (b, k) in block.iter_mut().zip(round_keys[round]) {
+            *b ^= k;
+        }
+
+        if round == 0 {
+            break;
+        }
+
+        if round < ROUNDS {
+            // InvMixColumns.
+            for column in block.chunks_mut(4) {
+                let [a, b, c, d] = [column[0], column[1], column[2], column[3]];
+                column[0] = gmul(a, 14) ^ gmul(b, 11) ^ gmul(c, 13) ^ gmul(d, 9);
+                column[1] = gmul(a, 9) ^ gmul(b, 14) ^ gmul(c, 11) ^ gmul(d, 13);
+                column[2] = gmul(a, 13) ^ gmul(b, 9) ^ gmul(c, 14) ^ gmul(d, 11);
+                column[3] = gmul(a, 11) ^ gmul(b, 13) ^ gmul(c, 9) ^ gmul(d, 14);
+            }
+        }
+
+        // InvShiftRows and InvSubBytes. The state is stored column by column.
+        let state: [u8; BLOCK_SIZE] = block.try_into().unwrap();
+        for (i, b) in block.iter_mut().enumerate() {
+            let (row, column) = (i % 4, i / 4);
+            *b = INV_SBOX[state[row + 4 * ((column + 4 - row) % 4)] as usize];
+        }
+    }
+}
+
+/// Decrypts data in place with AES-256 in CBC mode.
+///
+/// Any trailing partial block is left untouched.
+///
+/// # Arguments
+///
+/// * `key` - The key.
+/// * `iv` - The initialisation vector.
+/// * `data` - The data to decrypt.
+pub fn aes256_cbc_decrypt(key: &[u8; 32], iv: &[u8; BLOCK_SIZE], data: &mut [u8]) {
+    let round_keys = expand_key(key);
+    let mut previous = *iv;
+
+    for block in data.chunks_exact_mut(BLOCK_SIZE) {
+        let ciphertext: [u8; BLOCK_SIZE] = (*block).try_into().unwrap();
+        decrypt_block(&round_keys, block);
+
+        for (b, p) in block.iter_mut().zip(previous) {
+            *b ^= p;
+        }
+
+        previous = ciphertext;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Example from FIPS 197, appendix C.3.
+    #[test]
+    fn decrypts_fips197_block() {
+        let key: [u8; 32] =
+            unhex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")
+                .try_into()
+                .unwrap();
+        let mut block = unhex("8ea2b7ca516745bfeafc49904b496089");
+
+        decrypt_block(&expand_key(&key), &mut block);
+
+        assert_eq!(block, unhex("00112233445566778899aabbccddeeff"));
+    }
+
+    /// Example from NIST SP 800-38A, appendix F.2.6.
+    #[test]
+    fn decrypts_sp800_38a_cbc() {
+        let key: [u8; 32] =
+            unhex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4")
+                .try_into()
+                .unwrap();
+        let iv: [u8; 16] = unhex("000102030405060708090a0b0c0d0e0f")
+            .try_into()
+            .unwrap();
+        let mut data = unhex(
+            "f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d\
+             39f23369a9d9bacfa530e26304231461b2eb05e2c39be9fcda6c19078c6a9d1b",
+        );
+
+        aes256_cbc_decrypt(&key, &iv, &mut data);
+
+        assert_eq!(
+            data,
+            unhex(
+                "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                 30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+            )
+        );
+    }
+}
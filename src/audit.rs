@@ -0,0 +1,98 @@
+//! Auditing of the commands executed on remote servers.
+
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+
+/// The registered audit hook, if any.
+static AUDIT_HOOK: Mutex<Option<PyObject>> = Mutex::new(None);
+
+/// A command executed on a remote server, waiting to be reported to the audit hook once its exit
+/// status is known.
+pub struct AuditRecord {
+    /// The host the command was executed on.
+    host: String,
+    /// The user the command was executed as.
+    username: String,
+    /// The command.
+    command: String,
+    /// When the command was started (as a UNIX timestamp).
+    timestamp: f64,
+}
+
+impl AuditRecord {
+    /// Creates a new [`AuditRecord`] for a command that is being started now.
+    ///
+    /// Returns `None` if no audit hook is registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host the command is executed on.
+    /// * `username` - The user the command is executed as.
+    /// * `command` - The command.
+    pub fn start(host: &str, username: &str, command: &str) -> Option<Self> {
+        AUDIT_HOOK.lock().unwrap().as_ref()?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs_f64())
+            .unwrap_or(0.0);
+
+        Some(Self {
+            host: host.to_string(),
+            username: username.to_string(),
+            command: command.to_string(),
+            timestamp,
+        })
+    }
+
+    /// Reports the command to the audit hook.
+    ///
+    /// Exceptions raised by the hook are reported as unraisable, so that auditing never interferes
+    /// with the command itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `exit_status` - The exit status of the command, or `None` if it is unknown (e.g. because
+    ///   the command's output was closed before it finished).
+    pub fn finish(self, py: Python<'_>, exit_status: Option<i32>) {
+        let Some(hook) = AUDIT_HOOK
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|hook| hook.clone_ref(py))
+        else {
+            return;
+        };
+
+        let args = (
+            self.host,
+            self.username,
+            self.command,
+            self.timestamp,
+            exit_status,
+        );
+
+        if let Err(err) = hook.call1(py, args) {
+            err.write_unraisable(py, None);
+        }
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (hook=None))]
+/// Registers a callable that is invoked for every command executed by any client.
+///
+/// The hook is called with `(host, username, command, timestamp, exit_status)` once the command's
+/// exit status is known, where `timestamp` is when the command was started (as a UNIX timestamp).
+/// If the command's output is closed or discarded before it finishes, `exit_status` is `None`.
+/// Exceptions raised by the hook are reported but otherwise ignored.
+///
+/// # Arguments
+///
+/// * `hook` - The callable to register. Defaults to `None`, which removes the current hook.
+pub fn set_audit_hook(hook: Option<PyObject>) {
+    *AUDIT_HOOK.lock().unwrap() = hook;
+}
@@ -0,0 +1,394 @@
+//! Parsing and validation of OpenSSH host certificates (`*-cert-v01@openssh.com` host keys).
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::exceptions::PyImportError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyLong};
+
+use crate::keys;
+use crate::ssh::SessionException;
+
+/// Suffix of the key types of OpenSSH certificates.
+const CERT_TYPE_SUFFIX: &str = "-cert-v01@openssh.com";
+/// Certificate type of host certificates (as opposed to user certificates).
+const HOST_CERT: u32 = 2;
+
+/// An OpenSSH certificate.
+pub struct Certificate {
+    /// The certified public key, in SSH wire format.
+    pub public_key: Vec<u8>,
+    /// The certificate type (user or host).
+    cert_type: u32,
+    /// The names the certificate is valid for (any name if empty).
+    principals: Vec<String>,
+    /// Start of the validity period (seconds since the epoch).
+    valid_after: u64,
+    /// End of the validity period (seconds since the epoch).
+    valid_before: u64,
+    /// Whether the certificate has critical options, none of which are defined for hosts.
+    has_critical_options: bool,
+    /// The key of the CA that signed the certificate, in SSH wire format.
+    pub signature_key: Vec<u8>,
+    /// The CA's signature, in SSH wire format.
+    signature: Vec<u8>,
+    /// The signed part of the certificate (everything up to the signature).
+    signed_data: Vec<u8>,
+}
+
+/// Reads a big-endian `u32` from the start of `data`, returning it and the remaining data.
+///
+/// # Arguments
+///
+/// * `data` - The data to read from.
+fn read_u32(data: &[u8]) -> Option<(u32, &[u8])> {
+    Some((
+        u32::from_be_bytes(data.get(..4)?.try_into().ok()?),
+        &data[4..],
+    ))
+}
+
+/// Reads a big-endian `u64` from the start of `data`, returning it and the remaining data.
+///
+/// # Arguments
+///
+/// * `data` - The data to read from.
+fn read_u64(data: &[u8]) -> Option<(u64, &[u8])> {
+    Some((
+        u64::from_be_bytes(data.get(..8)?.try_into().ok()?),
+        &data[8..],
+    ))
+}
+
+impl Certificate {
+    /// Parses a certificate in SSH wire format.
+    ///
+    /// Returns `None` if the key is not a certificate, or is malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `blob` - The certificate, in SSH wire format.
+    pub fn parse(blob: &[u8]) -> Option<Self> {
+        let key_type = keys::public_key_type(blob)?.strip_suffix(CERT_TYPE_SUFFIX)?;
+        let (_, rest) = keys::read_string(blob)?;
+        let (_nonce, mut rest) = keys::read_string(rest)?;
+
+        // The certified key's fields follow the nonce, in the same order as in a plain key.
+        let fields = match key_type {
+            "ssh-ed25519" => 1,
+            "ssh-rsa" => 2,
+            "ssh-dss" => 4,
+            key_type if key_type.starts_with("ecdsa-sha2-") => 2,
+            _ => return None,
+        };
+        let mut public_key = Vec::new();
+        keys::write_string(&mut public_key, key_type.as_bytes());
+
+        for _ in 0..fields {
+            let (field, next) = keys::read_string(rest)?;
+            keys::write_string(&mut public_key, field);
+            rest = next;
+        }
+
+        let (_serial, rest) = read_u64(rest)?;
+        let (cert_type, rest) = read_u32(rest)?;
+        let (_key_id, rest) = keys::read_string(rest)?;
+        let (mut principals_data, rest) = keys::read_string(rest)?;
+        let (valid_after, rest) = read_u64(rest)?;
+        let (valid_before, rest) = read_u64(rest)?;
+        let (critical_options, rest) = keys::read_string(rest)?;
+        let (_extensions, rest) = keys::read_string(rest)?;
+        let (_reserved, rest) = keys::read_string(rest)?;
+        let (signature_key, rest) = keys::read_string(rest)?;
+        let signed_len = blob.len() - rest.len();
+        let (signature, _) = keys::read_string(rest)?;
+
+        let mut principals = Vec::new();
+        while !principals_data.is_empty() {
+            let (principal, next) = keys::read_string(principals_data)?;
+            principals.push(String::from_utf8_lossy(principal).into_owned());
+            principals_data = next;
+        }
+
+        Some(Self {
+            public_key,
+            cert_type,
+            principals,
+            valid_after,
+            valid_before,
+            has_critical_options: !critical_options.is_empty(),
+            signature_key: signature_key.to_vec(),
+            signature: signature.to_vec(),
+            signed_data: blob[..signed_len].to_vec(),
+        })
+    }
+
+    /// Checks that the certificate is a valid host certificate for a host, like OpenSSH does: it
+    /// must be a host certificate, list the host among its principals (if it lists any), be
+    /// within its validity period, have no critical options and carry a valid signature by its
+    /// CA key.
+    ///
+    /// Returns `Err` with the reason if the certificate is invalid.
+    ///
+    /// **NOTE**: Verifying the signature requires the `cryptography` package (the `certificates`
+    /// extra); a [`SessionException`] is raised if it is not installed.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `host` - The host name or address.
+    pub fn validate(&self, py: Python<'_>, host: &str) -> PyResult<Result<(), String>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+
+        if let Err(problem) = self.check_fields(host, now) {
+            return Ok(Err(problem));
+        }
+
+        if !self.verify_signature(py)? {
+            return Ok(Err("its signature is invalid".to_string()));
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Checks everything [`Certificate::validate`] checks but the signature.
+    ///
+    /// Returns `Err` with the reason if the certificate is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `now` - The current time (seconds since the epoch).
+    fn check_fields(&self, host: &str, now: u64) -> Result<(), String> {
+        let problem = if self.cert_type != HOST_CERT {
+            "it is not a host certificate".to_string()
+        } else if !self.principals.is_empty() && !self.principals.iter().any(|name| name == host) {
+            format!("{host} is not one of its principals")
+        } else if now < self.valid_after {
+            "it is not yet valid".to_string()
+        } else if now >= self.valid_before {
+            "it has expired".to_string()
+        } else if self.has_critical_options {
+            "it has unsupported critical options".to_string()
+        } else {
+            return Ok(());
+        };
+
+        Err(problem)
+    }
+
+    /// Verifies the CA's signature of the certificate, using the `cryptography` package.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn verify_signature(&self, py: Python<'_>) -> PyResult<bool> {
+        let strings = keys::read_strings(&self.signature, 2);
+        let (Some(ca_type), Some([algorithm, signature])) = (
+            keys::public_key_type(&self.signature_key),
+            strings.as_deref(),
+        ) else {
+            return Ok(false);
+        };
+        let algorithm = String::from_utf8_lossy(algorithm);
+
+        let serialization = match py.import("cryptography.hazmat.primitives.serialization") {
+            Ok(serialization) => serialization,
+            Err(err) if err.is_instance_of::<PyImportError>(py) => {
+                return Err(SessionException::new_err(
+                    "Verifying host certificates requires the `cryptography` package (install \
+                     `russh[certificates]`)"
+                        .to_string(),
+                ))
+            }
+            Err(err) => return Err(err),
+        };
+        let hashes = py.import("cryptography.hazmat.primitives.hashes")?;
+        let ca_key = serialization.call_method1(
+            "load_ssh_public_key",
+            (PyBytes::new(
+                py,
+                format!("{ca_type} {}", keys::base64_encode(&self.signature_key)).as_bytes(),
+            ),),
+        )?;
+        let data = PyBytes::new(py, &self.signed_data);
+
+        let result = match (ca_type, algorithm.as_ref()) {
+            ("ssh-ed25519", "ssh-ed25519") => {
+                ca_key.call_method1("verify", (PyBytes::new(py, signature), data))
+            }
+            ("ssh-rsa", algorithm @ ("rsa-sha2-256" | "rsa-sha2-512" | "ssh-rsa")) => {
+                let hash = match algorithm {
+                    "rsa-sha2-256" => "SHA256",
+                    "rsa-sha2-512" => "SHA512",
+                    _ => "SHA1",
+                };
+                let padding = py
+                    .import("cryptography.hazmat.primitives.asymmetric.padding")?
+                    .call_method0("PKCS1v15")?;
+
+                ca_key.call_method1(
+                    "verify",
+                    (
+                        PyBytes::new(py, signature),
+                        data,
+                        padding,
+                        hashes.call_method0(hash)?,
+                    ),
+                )
+            }
+            (ca_type, algorithm) if ca_type == algorithm && ca_type.starts_with("ecdsa-") => {
+                let hash = match ca_type {
+                    "ecdsa-sha2-nistp256" => "SHA256",
+                    "ecdsa-sha2-nistp384" => "SHA384",
+                    _ => "SHA512",
+                };
+                // The signature holds `r` and `s` as SSH mpints, while `cryptography` expects
+                // them DER-encoded.
+                let mpints = keys::read_strings(signature, 2);
+                let Some([r, s]) = mpints.as_deref() else {
+                    return Ok(false);
+                };
+                let int = py.get_type::<PyLong>();
+                let signature = py
+                    .import("cryptography.hazmat.primitives.asymmetric.utils")?
+                    .call_method1(
+                        "encode_dss_signature",
+                        (
+                            int.call_method1("from_bytes", (PyBytes::new(py, r), "big"))?,
+                            int.call_method1("from_bytes", (PyBytes::new(py, s), "big"))?,
+                        ),
+                    )?;
+                let ecdsa = py
+                    .import("cryptography.hazmat.primitives.asymmetric.ec")?
+                    .call_method1("ECDSA", (hashes.call_method0(hash)?,))?;
+
+                ca_key.call_method1("verify", (signature, data, ecdsa))
+            }
+            _ => return Ok(false),
+        };
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(err)
+                if err.is_instance(
+                    py,
+                    py.import("cryptography.exceptions")?
+                        .getattr("InvalidSignature")?,
+                ) =>
+            {
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The CA key that signed the certificates below, generated with `ssh-keygen`.
+    const CA_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIItlQ/tvFJI0tjG2mIe1CC815d2B7kyMUigZqmVLB4/x";
+    /// The certified host key.
+    const HOST_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIF8XiiEFu+y2FaQZ7pGfCsCH8rSmUotBVYp25MmWEAxu";
+    /// A host certificate for `example.com`, valid forever (`ssh-keygen -h -n example.com`).
+    const HOST_CERT_FOREVER: &str = concat!(
+        "AAAAIHNzaC1lZDI1NTE5LWNlcnQtdjAxQG9wZW5zc2guY29tAAAAID2gaA+r7uKOIQlKjSk6OUKZ7BwXATgVmWov67QM",
+        "8nDQAAAAIF8XiiEFu+y2FaQZ7pGfCsCH8rSmUotBVYp25MmWEAxuAAAAAAAAAAAAAAACAAAAAmlkAAAADwAAAAtleGFt",
+        "cGxlLmNvbQAAAAAAAAAA//////////8AAAAAAAAAAAAAAAAAAAAzAAAAC3NzaC1lZDI1NTE5AAAAIItlQ/tvFJI0tjG2",
+        "mIe1CC815d2B7kyMUigZqmVLB4/xAAAAUwAAAAtzc2gtZWQyNTUxOQAAAEBfjrmrwGJ1ufuNp38lFXhPmL65DxU5hZgn",
+        "mUjMkBRc7ubY+LlJRZGRQM0d9j7+/B4Bzu1HCX0YgrbU2noKWkMC",
+    );
+    /// The same host certificate, valid during 2020 only (`-V 20200101:20210101`, in UTC).
+    const HOST_CERT_2020: &str = concat!(
+        "AAAAIHNzaC1lZDI1NTE5LWNlcnQtdjAxQG9wZW5zc2guY29tAAAAICx1RnuDjo6uyituhkoOufIs2ZbO5aw21QYsipO8",
+        "rl5/AAAAIF8XiiEFu+y2FaQZ7pGfCsCH8rSmUotBVYp25MmWEAxuAAAAAAAAAAAAAAACAAAAAmlkAAAADwAAAAtleGFt",
+        "cGxlLmNvbQAAAABeC+EAAAAAAF/uZgAAAAAAAAAAAAAAAAAAAAAzAAAAC3NzaC1lZDI1NTE5AAAAIItlQ/tvFJI0tjG2",
+        "mIe1CC815d2B7kyMUigZqmVLB4/xAAAAUwAAAAtzc2gtZWQyNTUxOQAAAEA4dg4nb+v15v/kKHbu18SdoYvsfhZn1KLR",
+        "yCBBGghRQ6Mg5DxeF0Bg3X5MZtP/FvmDOcY19QGQZJFGFOeZaEQO",
+    );
+    /// A user certificate for the same key, with a `force-command` critical option.
+    const USER_CERT_FORCE_COMMAND: &str = concat!(
+        "AAAAIHNzaC1lZDI1NTE5LWNlcnQtdjAxQG9wZW5zc2guY29tAAAAIJy9KIxIvfTJnt2ULEM1dUHUOBckZozs0jWOl91k",
+        "65FyAAAAIF8XiiEFu+y2FaQZ7pGfCsCH8rSmUotBVYp25MmWEAxuAAAAAAAAAAAAAAABAAAAAmlkAAAADwAAAAtleGFt",
+        "cGxlLmNvbQAAAAAAAAAA//////////8AAAAdAAAADWZvcmNlLWNvbW1hbmQAAAAIAAAABHRydWUAAAAAAAAAAAAAADMA",
+        "AAALc3NoLWVkMjU1MTkAAAAgi2VD+28UkjS2MbaYh7UILzXl3YHuTIxSKBmqZUsHj/EAAABTAAAAC3NzaC1lZDI1NTE5",
+        "AAAAQPeoxVMv/Xvhkfbo0MNfVzuDsMe4W6a0r1OMIovVIqUT/b+tErsb74mXTfVhvpaFM+WyPBDzYBjy3SPBWl6BAAc=",
+    );
+    /// 2020-06-01T00:00:00Z.
+    const MID_2020: u64 = 1_590_969_600;
+
+    fn parse(encoded: &str) -> Certificate {
+        Certificate::parse(&keys::base64_decode(encoded).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_host_certificate() {
+        let cert = parse(HOST_CERT_FOREVER);
+
+        assert_eq!(cert.public_key, keys::base64_decode(HOST_KEY).unwrap());
+        assert_eq!(cert.signature_key, keys::base64_decode(CA_KEY).unwrap());
+        assert_eq!(cert.cert_type, HOST_CERT);
+        assert_eq!(cert.principals, ["example.com"]);
+        assert_eq!((cert.valid_after, cert.valid_before), (0, u64::MAX));
+        assert!(!cert.has_critical_options);
+        assert!(cert
+            .signed_data
+            .ends_with(&keys::base64_decode(CA_KEY).unwrap()));
+    }
+
+    #[test]
+    fn rejects_plain_keys_and_truncated_certificates() {
+        assert!(Certificate::parse(&keys::base64_decode(HOST_KEY).unwrap()).is_none());
+
+        let blob = keys::base64_decode(HOST_CERT_FOREVER).unwrap();
+        for len in [0, 40, 100, blob.len() - 1] {
+            assert!(Certificate::parse(&blob[..len]).is_none(), "length {len}");
+        }
+    }
+
+    #[test]
+    fn checks_principals() {
+        let cert = parse(HOST_CERT_FOREVER);
+
+        assert_eq!(cert.check_fields("example.com", MID_2020), Ok(()));
+        assert_eq!(
+            cert.check_fields("example.org", MID_2020),
+            Err("example.org is not one of its principals".to_string())
+        );
+    }
+
+    #[test]
+    fn checks_validity_period() {
+        let cert = parse(HOST_CERT_2020);
+
+        assert_eq!(
+            (cert.valid_after, cert.valid_before),
+            (1_577_836_800, 1_609_459_200)
+        );
+        assert_eq!(cert.check_fields("example.com", MID_2020), Ok(()));
+        assert_eq!(
+            cert.check_fields("example.com", 1_577_836_799),
+            Err("it is not yet valid".to_string())
+        );
+        assert_eq!(
+            cert.check_fields("example.com", 1_609_459_200),
+            Err("it has expired".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_user_certificates() {
+        let cert = parse(USER_CERT_FORCE_COMMAND);
+
+        assert!(cert.has_critical_options);
+        assert_eq!(
+            cert.check_fields("example.com", MID_2020),
+            Err("it is not a host certificate".to_string())
+        );
+    }
+}
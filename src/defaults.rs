@@ -0,0 +1,157 @@
+//! Per-host connection defaults, applied by [`SSHClient::connect`](crate::ssh::SSHClient::connect)
+//! when the corresponding arguments are omitted.
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use pyo3::prelude::*;
+
+use crate::ssh::AuthMethods;
+
+/// Connection defaults for a host.
+#[derive(Clone, Default)]
+pub struct HostDefaults {
+    /// The default SSH port.
+    pub port: Option<u16>,
+    /// The default authentication methods.
+    pub auth: Option<AuthMethods>,
+    /// The default TCP connection timeout (in seconds).
+    pub timeout: Option<u32>,
+    /// A `known_hosts` file used in addition to the client's own to verify the host key.
+    pub known_hosts: Option<PathBuf>,
+}
+
+/// The registered defaults and the host patterns they apply to, in registration order.
+static DEFAULTS: Mutex<Vec<(String, HostDefaults)>> = Mutex::new(Vec::new());
+
+/// Checks whether a host matches a glob pattern, where `*` matches any run of characters and `?`
+/// matches exactly one. Matching is case-insensitive.
+///
+/// # Arguments
+///
+/// * `pattern` - The glob pattern.
+/// * `host` - The host name or address.
+pub(crate) fn matches(pattern: &str, host: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let host: Vec<char> = host.to_lowercase().chars().collect();
+
+    let (mut p, mut h) = (0, 0);
+    let mut backtrack = None;
+
+    while h < host.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, h));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == host[h] => {
+                p += 1;
+                h += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    h = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Returns the name of the local user, like `ssh` uses when no username is given.
+///
+/// The name is read from the `USER`, `LOGNAME` or (on Windows) `USERNAME` environment variables.
+pub fn local_username() -> Option<String> {
+    ["USER", "LOGNAME", "USERNAME"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|username| !username.is_empty())
+}
+
+/// Returns the defaults that apply to a host.
+///
+/// When several registered patterns match, each setting is taken from the first pattern (in
+/// registration order) that provides it.
+///
+/// # Arguments
+///
+/// * `host` - The host name or address.
+pub fn lookup(host: &str) -> HostDefaults {
+    let mut found = HostDefaults::default();
+
+    for (pattern, defaults) in DEFAULTS.lock().unwrap().iter() {
+        if matches(pattern, host) {
+            found.port = found.port.or(defaults.port);
+            found.auth = found.auth.or_else(|| defaults.auth.clone());
+            found.timeout = found.timeout.or(defaults.timeout);
+            found.known_hosts = found.known_hosts.or_else(|| defaults.known_hosts.clone());
+        }
+    }
+
+    found
+}
+
+#[pyfunction]
+#[pyo3(signature = (host_pattern, port=None, auth=None, timeout=None, known_hosts=None))]
+/// Registers connection defaults for hosts matching a pattern.
+///
+/// The defaults are used by [`SSHClient::connect`](crate::ssh::SSHClient::connect) for any
+/// argument that is not passed explicitly. Registering the same pattern again replaces its
+/// defaults. When several patterns match a host, each setting is taken from the first pattern
+/// registered that provides it.
+///
+/// # Arguments
+///
+/// * `host_pattern` - The host pattern. `*` matches any run of characters and `?` matches exactly
+///   one; matching is case-insensitive.
+/// * `port` - The default SSH port.
+/// * `auth` - The default authentication methods.
+/// * `timeout` - The default TCP connection timeout (in seconds).
+/// * `known_hosts` - A `known_hosts` file used to verify the host key, in addition to
+///   `~/.ssh/known_hosts` and the files loaded with
+///   [`SSHClient::load_host_keys`](crate::ssh::SSHClient::load_host_keys). Host keys added by an
+///   [`AutoAddPolicy`](crate::ssh::AutoAddPolicy) are written to it.
+pub fn set_default(
+    host_pattern: String,
+    port: Option<u16>,
+    auth: Option<AuthMethods>,
+    timeout: Option<u32>,
+    known_hosts: Option<PathBuf>,
+) {
+    let defaults = HostDefaults {
+        port,
+        auth,
+        timeout,
+        known_hosts,
+    };
+    let mut registry = DEFAULTS.lock().unwrap();
+
+    match registry
+        .iter_mut()
+        .find(|(pattern, _)| *pattern == host_pattern)
+    {
+        Some((_, existing)) => *existing = defaults,
+        None => registry.push((host_pattern, defaults)),
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (host_pattern=None))]
+/// Removes registered connection defaults.
+///
+/// # Arguments
+///
+/// * `host_pattern` - The pattern to remove the defaults of. Defaults to removing all defaults.
+pub fn clear_defaults(host_pattern: Option<String>) {
+    let mut registry = DEFAULTS.lock().unwrap();
+
+    match host_pattern {
+        Some(host_pattern) => registry.retain(|(pattern, _)| *pattern != host_pattern),
+        None => registry.clear(),
+    }
+}
@@ -0,0 +1,348 @@
+//! Streaming gzip compression and decompression, backed by zlib.
+
+use std::ffi::c_void;
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::os::raw::c_int;
+
+use libz_sys::{
+    deflate, deflateEnd, deflateInit2_, inflate, inflateEnd, inflateInit2_, uInt, voidpf, z_stream,
+    zlibVersion, Z_BUF_ERROR, Z_DEFAULT_STRATEGY, Z_DEFLATED, Z_FINISH, Z_NO_FLUSH, Z_OK,
+    Z_STREAM_END,
+};
+
+/// zlib window bits for the largest (32 KiB) window, plus 16 to select the gzip format.
+const GZIP_WINDOW_BITS: c_int = 15 + 16;
+/// zlib's default compression level.
+const COMPRESSION_LEVEL: c_int = 6;
+/// zlib's default memory level.
+const MEM_LEVEL: c_int = 8;
+/// Size of the output buffer used for each call into zlib.
+const OUTPUT_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Allocation function handed to zlib.
+extern "C" fn zalloc(_opaque: voidpf, items: uInt, size: uInt) -> voidpf {
+    unsafe { libc::calloc(items as usize, size as usize) }
+}
+
+/// Deallocation function handed to zlib.
+extern "C" fn zfree(_opaque: voidpf, address: voidpf) {
+    unsafe { libc::free(address) }
+}
+
+/// Creates a fresh, uninitialised zlib stream.
+///
+/// The stream is boxed because zlib keeps a pointer to it, so it must not move once initialised.
+fn new_stream() -> Box<z_stream> {
+    Box::new(z_stream {
+        next_in: std::ptr::null_mut(),
+        avail_in: 0,
+        total_in: 0,
+        next_out: std::ptr::null_mut(),
+        avail_out: 0,
+        total_out: 0,
+        msg: std::ptr::null_mut(),
+        state: std::ptr::null_mut(),
+        zalloc,
+        zfree,
+        opaque: std::ptr::null_mut::<c_void>(),
+        data_type: 0,
+        adler: 0,
+        reserved: 0,
+    })
+}
+
+/// Converts a zlib error code into an I/O error.
+///
+/// # Arguments
+///
+/// * `code` - The zlib return code.
+fn zlib_error(code: c_int) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("zlib error (code {code})"))
+}
+
+/// Runs a zlib operation over `input` until it has all been consumed (and, when `flush` is
+/// `Z_FINISH`, until the stream has ended), appending everything produced to `output`.
+///
+/// Returns the last return code from zlib.
+///
+/// # Arguments
+///
+/// * `stream` - The initialised zlib stream.
+/// * `op` - The zlib operation (`deflate` or `inflate`).
+/// * `input` - The input data.
+/// * `output` - The buffer to append the output to.
+/// * `flush` - The zlib flush mode.
+fn run(
+    stream: &mut z_stream,
+    op: unsafe extern "C" fn(*mut z_stream, c_int) -> c_int,
+    input: &[u8],
+    output: &mut Vec<u8>,
+    flush: c_int,
+) -> io::Result<c_int> {
+    stream.next_in = input.as_ptr() as *mut u8;
+    stream.avail_in = input.len() as uInt;
+
+    loop {
+        let start = output.len();
+        output.resize(start + OUTPUT_CHUNK_SIZE, 0);
+        stream.next_out = output[start..].as_mut_ptr();
+        stream.avail_out = OUTPUT_CHUNK_SIZE as uInt;
+
+        let code = unsafe { op(stream, flush) };
+        output.truncate(start + OUTPUT_CHUNK_SIZE - stream.avail_out as usize);
+
+        match code {
+            Z_STREAM_END => return Ok(code),
+            Z_OK | Z_BUF_ERROR => {
+                if stream.avail_in == 0 && stream.avail_out != 0 {
+                    return Ok(code);
+                }
+            }
+            _ => return Err(zlib_error(code)),
+        }
+    }
+}
+
+/// Streaming gzip compressor.
+pub struct Encoder {
+    stream: Box<z_stream>,
+}
+
+impl Encoder {
+    /// Creates a new [`Encoder`].
+    pub fn new() -> io::Result<Self> {
+        let mut stream = new_stream();
+        let code = unsafe {
+            deflateInit2_(
+                &mut *stream,
+                COMPRESSION_LEVEL,
+                Z_DEFLATED,
+                GZIP_WINDOW_BITS,
+                MEM_LEVEL,
+                Z_DEFAULT_STRATEGY,
+                zlibVersion(),
+                mem::size_of::<z_stream>() as c_int,
+            )
+        };
+
+        if code != Z_OK {
+            return Err(zlib_error(code));
+        }
+
+        Ok(Self { stream })
+    }
+
+    /// Compresses a chunk of data, appending the compressed output produced so far to `output`.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The data to compress.
+    /// * `output` - The buffer to append the compressed data to.
+    pub fn write(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        run(&mut self.stream, deflate, input, output, Z_NO_FLUSH).map(|_| ())
+    }
+
+    /// Finishes the gzip stream, appending the remaining compressed output to `output`.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - The buffer to append the compressed data to.
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        run(&mut self.stream, deflate, &[], output, Z_FINISH).map(|_| ())
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        unsafe { deflateEnd(&mut *self.stream) };
+    }
+}
+
+/// Streaming gzip decompressor.
+pub struct Decoder {
+    stream: Box<z_stream>,
+    /// Whether the end of the gzip stream has been reached.
+    finished: bool,
+}
+
+impl Decoder {
+    /// Creates a new [`Decoder`].
+    pub fn new() -> io::Result<Self> {
+        let mut stream = new_stream();
+        let code = unsafe {
+            inflateInit2_(
+                &mut *stream,
+                GZIP_WINDOW_BITS,
+                zlibVersion(),
+                mem::size_of::<z_stream>() as c_int,
+            )
+        };
+
+        if code != Z_OK {
+            return Err(zlib_error(code));
+        }
+
+        Ok(Self {
+            stream,
+            finished: false,
+        })
+    }
+
+    /// Decompresses a chunk of gzip data, appending the decompressed output to `output`.
+    ///
+    /// Data following the end of the gzip stream is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The compressed data.
+    /// * `output` - The buffer to append the decompressed data to.
+    pub fn write(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        if run(&mut self.stream, inflate, input, output, Z_NO_FLUSH)? == Z_STREAM_END {
+            self.finished = true;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the gzip stream was complete.
+    pub fn finish(&self) -> io::Result<()> {
+        if !self.finished {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "gzip stream ended unexpectedly",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        unsafe { inflateEnd(&mut *self.stream) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `printf 'hello, world\n' | gzip -n -9`
+    const HELLO_GZ: [u8; 33] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9,
+        0xd7, 0x51, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0xe1, 0x02, 0x00, 0x53, 0x74, 0x24, 0xf4, 0x0d,
+        0x00, 0x00, 0x00,
+    ];
+
+    fn compress(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut encoder = Encoder::new().unwrap();
+        let mut output = Vec::new();
+
+        for chunk in data.chunks(chunk_size) {
+            encoder.write(chunk, &mut output).unwrap();
+        }
+        encoder.finish(&mut output).unwrap();
+
+        output
+    }
+
+    fn decompress(data: &[u8], chunk_size: usize) -> io::Result<Vec<u8>> {
+        let mut decoder = Decoder::new()?;
+        let mut output = Vec::new();
+
+        for chunk in data.chunks(chunk_size) {
+            decoder.write(chunk, &mut output)?;
+        }
+        decoder.finish()?;
+
+        Ok(output)
+    }
+
+    #[test]
+    fn decompresses_gzip_output() {
+        for chunk_size in [1, 7, HELLO_GZ.len()] {
+            assert_eq!(
+                decompress(&HELLO_GZ, chunk_size).unwrap(),
+                b"hello, world\n"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        // Large enough to span several output buffers, both compressible and not.
+        let text: Vec<u8> = b"the quick brown fox jumps over the lazy dog\n"
+            .iter()
+            .copied()
+            .cycle()
+            .take(200_000)
+            .collect();
+        let mut state = 0x2545f491u32;
+        let noise: Vec<u8> = (0..200_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        for data in [&b""[..], b"x", &text, &noise] {
+            let compressed = compress(data, 4096);
+            assert_eq!(&compressed[..2], [0x1f, 0x8b]);
+
+            for chunk_size in [1000, OUTPUT_CHUNK_SIZE + 1, compressed.len().max(1)] {
+                assert_eq!(decompress(&compressed, chunk_size).unwrap(), data);
+            }
+        }
+
+        assert!(compress(&text, 4096).len() < text.len() / 10);
+    }
+
+    #[test]
+    fn ignores_trailing_data() {
+        let mut data = HELLO_GZ.to_vec();
+        data.extend_from_slice(b"trailing garbage");
+
+        assert_eq!(decompress(&data, 5).unwrap(), b"hello, world\n");
+    }
+
+    #[test]
+    fn rejects_corrupt_input() {
+        assert_eq!(
+            decompress(b"hello, world\n", 64).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        // A damaged checksum in the trailer.
+        let mut data = HELLO_GZ.to_vec();
+        data[26] ^= 0xff;
+        assert_eq!(
+            decompress(&data, 64).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        // A damaged deflate stream.
+        let mut data = HELLO_GZ.to_vec();
+        data[10] = 0xff;
+        assert_eq!(
+            decompress(&data, 64).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        for len in [0, 5, 20, HELLO_GZ.len() - 1] {
+            assert_eq!(
+                decompress(&HELLO_GZ[..len], 64).unwrap_err().kind(),
+                ErrorKind::UnexpectedEof
+            );
+        }
+    }
+}
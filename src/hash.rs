@@ -0,0 +1,645 @@
+//! Hash functions used by the library.
+
+use std::io::{self, BufRead};
+
+/// Block size of MD5, SHA-1 and SHA-256 (in bytes).
+const BLOCK_SIZE: usize = 64;
+/// Per-round shift amounts of MD5.
+const MD5_S: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+/// Round constants of MD5.
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+/// Round constants of SHA-256.
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Splits input into blocks for the Merkle–Damgård hash functions, and pads the final block.
+#[derive(Clone)]
+struct Blocks {
+    /// Input that does not yet fill a whole block.
+    buf: [u8; BLOCK_SIZE],
+    /// Number of bytes in `buf`.
+    len: usize,
+    /// Total length of the input (in bytes).
+    total: u64,
+}
+
+impl Blocks {
+    fn new() -> Self {
+        Self {
+            buf: [0; BLOCK_SIZE],
+            len: 0,
+            total: 0,
+        }
+    }
+
+    /// Feeds data in, passing each completed block to `compress`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to feed in.
+    /// * `compress` - Processes a single block.
+    fn update(&mut self, mut data: &[u8], mut compress: impl FnMut(&[u8])) {
+        self.total = self.total.wrapping_add(data.len() as u64);
+
+        if self.len > 0 {
+            let n = data.len().min(BLOCK_SIZE - self.len);
+            self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+            self.len += n;
+            data = &data[n..];
+
+            if self.len < BLOCK_SIZE {
+                return;
+            }
+
+            compress(&self.buf);
+            self.len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            compress(block);
+        }
+
+        let rest = blocks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.len = rest.len();
+    }
+
+    /// Pads the input and passes the remaining blocks to `compress`.
+    ///
+    /// # Arguments
+    ///
+    /// * `length` - Encodes the length of the input (in bits).
+    /// * `compress` - Processes a single block.
+    fn finish(mut self, length: impl Fn(u64) -> [u8; 8], mut compress: impl FnMut(&[u8])) {
+        let bits = length(self.total.wrapping_mul(8));
+
+        self.buf[self.len] = 0x80;
+        self.buf[self.len + 1..].fill(0);
+
+        if self.len >= BLOCK_SIZE - 8 {
+            compress(&self.buf);
+            self.buf.fill(0);
+        }
+
+        self.buf[BLOCK_SIZE - 8..].copy_from_slice(&bits);
+        compress(&self.buf);
+    }
+}
+
+/// Incremental MD5 hasher.
+///
+/// **NOTE**: MD5 is broken; it is only used for legacy fingerprints.
+#[derive(Clone)]
+pub struct Md5 {
+    h: [u32; 4],
+    blocks: Blocks,
+}
+
+impl Md5 {
+    pub fn new() -> Self {
+        Self {
+            h: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            blocks: Blocks::new(),
+        }
+    }
+
+    /// Feeds data into the hasher.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to hash.
+    pub fn update(&mut self, data: &[u8]) {
+        let h = &mut self.h;
+        self.blocks.update(data, |block| md5_compress(h, block));
+    }
+
+    /// Returns the digest of all the data fed in.
+    pub fn finalize(mut self) -> [u8; 16] {
+        let h = &mut self.h;
+        self.blocks
+            .finish(u64::to_le_bytes, |block| md5_compress(h, block));
+
+        let mut digest = [0; 16];
+        for (chunk, h) in digest.chunks_mut(4).zip(self.h) {
+            chunk.copy_from_slice(&h.to_le_bytes());
+        }
+
+        digest
+    }
+}
+
+/// Processes a single block of MD5 input.
+fn md5_compress(h: &mut [u32; 4], block: &[u8]) {
+    let mut m = [0u32; 16];
+
+    for (i, word) in block.chunks(4).enumerate() {
+        m[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = *h;
+
+    for (i, k) in MD5_K.iter().enumerate() {
+        let (f, g) = match i {
+            0..=15 => ((b & c) | (!b & d), i),
+            16..=31 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            32..=47 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+
+        let temp = a
+            .wrapping_add(f)
+            .wrapping_add(*k)
+            .wrapping_add(m[g])
+            .rotate_left(MD5_S[(i / 16) * 4 + i % 4]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(temp);
+    }
+
+    for (h, v) in h.iter_mut().zip([a, b, c, d]) {
+        *h = h.wrapping_add(v);
+    }
+}
+
+/// Incremental SHA-1 hasher.
+#[derive(Clone)]
+pub struct Sha1 {
+    h: [u32; 5],
+    blocks: Blocks,
+}
+
+impl Sha1 {
+    pub fn new() -> Self {
+        Self {
+            h: [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0],
+            blocks: Blocks::new(),
+        }
+    }
+
+    /// Feeds data into the hasher.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to hash.
+    pub fn update(&mut self, data: &[u8]) {
+        let h = &mut self.h;
+        self.blocks.update(data, |block| sha1_compress(h, block));
+    }
+
+    /// Returns the digest of all the data fed in.
+    pub fn finalize(mut self) -> [u8; 20] {
+        let h = &mut self.h;
+        self.blocks
+            .finish(u64::to_be_bytes, |block| sha1_compress(h, block));
+
+        let mut digest = [0; 20];
+        for (chunk, h) in digest.chunks_mut(4).zip(self.h) {
+            chunk.copy_from_slice(&h.to_be_bytes());
+        }
+
+        digest
+    }
+}
+
+/// Processes a single block of SHA-1 input.
+fn sha1_compress(h: &mut [u32; 5], block: &[u8]) {
+    let mut w = [0u32; 80];
+
+    for (i, word) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *h;
+
+    for (i, word) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5A827999),
+            20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+            _ => (b ^ c ^ d, 0xCA62C1D6),
+        };
+
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(*word);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+    }
+
+    for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+        *h = h.wrapping_add(v);
+    }
+}
+
+/// Incremental SHA-256 hasher.
+#[derive(Clone)]
+pub struct Sha256 {
+    h: [u32; 8],
+    blocks: Blocks,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            h: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            blocks: Blocks::new(),
+        }
+    }
+
+    /// Feeds data into the hasher.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to hash.
+    pub fn update(&mut self, data: &[u8]) {
+        let h = &mut self.h;
+        self.blocks.update(data, |block| sha256_compress(h, block));
+    }
+
+    /// Returns the digest of all the data fed in.
+    pub fn finalize(mut self) -> [u8; 32] {
+        let h = &mut self.h;
+        self.blocks
+            .finish(u64::to_be_bytes, |block| sha256_compress(h, block));
+
+        let mut digest = [0; 32];
+        for (chunk, h) in digest.chunks_mut(4).zip(self.h) {
+            chunk.copy_from_slice(&h.to_be_bytes());
+        }
+
+        digest
+    }
+}
+
+/// Processes a single block of SHA-256 input.
+fn sha256_compress(h: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+
+    for (i, word) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+
+    for (word, k) in w.iter().zip(SHA256_K) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = hh
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(k)
+            .wrapping_add(*word);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+
+    for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *h = h.wrapping_add(v);
+    }
+}
+
+/// Computes the MD5 digest of the data.
+///
+/// **NOTE**: MD5 is broken; it is only used for legacy fingerprints.
+///
+/// # Arguments
+///
+/// * `data` - The data to hash.
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    let mut hasher = Md5::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Computes the SHA-1 digest of the data.
+///
+/// # Arguments
+///
+/// * `data` - The data to hash.
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Computes the SHA-1 digest of everything read from a reader, without holding it in memory.
+///
+/// # Arguments
+///
+/// * `reader` - The reader to hash the contents of.
+pub fn sha1_reader(mut reader: impl BufRead) -> io::Result<[u8; 20]> {
+    let mut hasher = Sha1::new();
+
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(hasher.finalize());
+        }
+
+        hasher.update(buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+/// Computes the SHA-256 digest of the data.
+///
+/// # Arguments
+///
+/// * `data` - The data to hash.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Computes the HMAC-SHA-1 of the message.
+///
+/// # Arguments
+///
+/// * `key` - The secret key.
+/// * `message` - The message to authenticate.
+pub fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+    let mut block = [0; BLOCK_SIZE];
+
+    if key.len() > BLOCK_SIZE {
+        block[..20].copy_from_slice(&sha1(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha1::new();
+    inner.update(&block.map(|b| b ^ 0x36));
+    inner.update(message);
+
+    let mut outer = Sha1::new();
+    outer.update(&block.map(|b| b ^ 0x5c));
+    outer.update(&inner.finalize());
+
+    outer.finalize()
+}
+
+/// Computes the HMAC-SHA-256 of the message.
+///
+/// # Arguments
+///
+/// * `key` - The secret key.
+/// * `message` - The message to authenticate.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0; BLOCK_SIZE];
+
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(&block.map(|b| b ^ 0x36));
+    inner.update(message);
+
+    let mut outer = Sha256::new();
+    outer.update(&block.map(|b| b ^ 0x5c));
+    outer.update(&inner.finalize());
+
+    outer.finalize()
+}
+
+/// Formats a digest as a lowercase hexadecimal string.
+///
+/// # Arguments
+///
+/// * `digest` - The digest to format.
+pub fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test suite from RFC 1321, appendix A.5.
+    #[test]
+    fn md5_rfc1321() {
+        let vectors: [(&[u8], &str); 7] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                b"abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+
+        for (data, digest) in vectors {
+            assert_eq!(hex(&md5(data)), digest);
+        }
+    }
+
+    /// Examples from FIPS 180-2, appendix A.
+    #[test]
+    fn sha1_fips180() {
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+
+        let mut hasher = Sha1::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+        assert_eq!(
+            hex(&hasher.finalize()),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+    }
+
+    /// Examples from FIPS 180-2, appendix B.
+    #[test]
+    fn sha256_fips180() {
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+
+        let mut hasher = Sha256::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+        assert_eq!(
+            hex(&hasher.finalize()),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    /// Test cases from RFC 2202, section 3.
+    #[test]
+    fn hmac_sha1_rfc2202() {
+        let vectors: [(&[u8], &[u8], &str); 7] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b617318655057264e28bc0b6fb378c8ef146be00",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79",
+            ),
+            (
+                &[0xaa; 20],
+                &[0xdd; 50],
+                "125d7342b9ac11cd91a39af48aa17b4f63f175d3",
+            ),
+            (
+                &[
+                    0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+                    0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19,
+                ],
+                &[0xcd; 50],
+                "4c9007f4026250c6bc8414f9bf50c86c2d7235da",
+            ),
+            (
+                &[0x0c; 20],
+                b"Test With Truncation",
+                "4c1a03424b55e07fe7f27be1d58bb9324a9a5a04",
+            ),
+            (
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "aa4ae5e15272d00e95705637ce8a3b55ed402112",
+            ),
+            (
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data",
+                "e8e99d0f45237d786d6bbaa7965c7808bbff1a91",
+            ),
+        ];
+
+        for (key, message, digest) in vectors {
+            assert_eq!(hex(&hmac_sha1(key, message)), digest);
+        }
+    }
+
+    /// Test cases from RFC 4231, section 4.
+    #[test]
+    fn hmac_sha256_rfc4231() {
+        let vectors: [(&[u8], &[u8], &str); 4] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                &[0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than block-size \
+                  data. The key needs to be hashed before being used by the HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+
+        for (key, message, digest) in vectors {
+            assert_eq!(hex(&hmac_sha256(key, message)), digest);
+        }
+    }
+
+    #[test]
+    fn incremental_matches_one_shot() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        // Exercise every split point around the block and padding boundaries.
+        for split in [0, 1, 55, 56, 63, 64, 65, 119, 120, 128, 999, 1000] {
+            let mut hasher = Sha256::new();
+            hasher.update(&data[..split]);
+            hasher.update(&data[split..]);
+            assert_eq!(hasher.finalize(), sha256(&data));
+        }
+
+        for len in 50..140 {
+            assert_eq!(
+                sha1_reader(io::BufReader::with_capacity(7, &data[..len])).unwrap(),
+                sha1(&data[..len])
+            );
+        }
+    }
+}
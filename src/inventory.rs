@@ -0,0 +1,292 @@
+//! Loading of host inventories describing the hosts to connect to.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::defaults;
+use crate::ssh::{excp_from_err, AuthMethods, ConnectArgs, SSHClient};
+
+/// Name of the group that every host of an inventory belongs to.
+const ALL_GROUP: &str = "all";
+
+#[pyclass]
+#[derive(Clone)]
+/// The options for connecting to a single host.
+pub struct ConnectOptions {
+    /// The host name or address.
+    #[pyo3(get, set)]
+    pub host: String,
+    /// The SSH port (`None` to use the registered defaults, or 22).
+    #[pyo3(get, set)]
+    pub port: Option<u16>,
+    /// The SSH username (`None` to use the local user).
+    #[pyo3(get, set)]
+    pub username: Option<String>,
+    /// Name of the credentials to authenticate with, looked up in the credentials passed to
+    /// [`ConnectOptions::connect`].
+    #[pyo3(get, set)]
+    pub auth: Option<String>,
+    /// Names of the groups the host belongs to.
+    #[pyo3(get, set)]
+    pub groups: Vec<String>,
+}
+
+#[pymethods]
+impl ConnectOptions {
+    #[new]
+    #[pyo3(signature = (host, port=None, username=None, auth=None, groups=None))]
+    /// Creates a new [`ConnectOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `port` - The SSH port. Defaults to the registered defaults, or 22.
+    /// * `username` - The SSH username. Defaults to the local user.
+    /// * `auth` - Name of the credentials to authenticate with.
+    /// * `groups` - Names of the groups the host belongs to.
+    pub fn __new__(
+        host: String,
+        port: Option<u16>,
+        username: Option<String>,
+        auth: Option<String>,
+        groups: Option<Vec<String>>,
+    ) -> Self {
+        Self {
+            host,
+            port,
+            username,
+            auth,
+            groups: groups.unwrap_or_default(),
+        }
+    }
+
+    /// Connects a new [`SSHClient`] to the host.
+    ///
+    /// If the host has no `auth` reference, the authentication methods registered with
+    /// [`set_default`](crate::defaults::set_default) are used, falling back to the default private
+    /// keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `credentials` - Optional mapping of credential names to authentication methods, used to
+    ///   resolve the host's `auth` reference.
+    /// * `timeout` - The timeout for the TCP connection (in seconds). Defaults to 30.
+    #[pyo3(signature = (credentials=None, timeout=None))]
+    pub fn connect(
+        &self,
+        py: Python<'_>,
+        credentials: Option<HashMap<String, AuthMethods>>,
+        timeout: Option<u32>,
+    ) -> PyResult<SSHClient> {
+        let auth = match &self.auth {
+            Some(name) => Some(
+                credentials
+                    .and_then(|mut credentials| credentials.remove(name))
+                    .ok_or_else(|| PyValueError::new_err(format!("Unknown credentials: {name}")))?,
+            ),
+            None => None,
+        };
+
+        let Some(username) = self.username.clone().or_else(defaults::local_username) else {
+            return Err(PyValueError::new_err(
+                "No username provided and the local user could not be determined".to_string(),
+            ));
+        };
+
+        let mut client = SSHClient::__new__();
+        client.connect_with(
+            py,
+            ConnectArgs {
+                auth,
+                port: self.port,
+                timeout,
+                ..ConnectArgs::new(self.host.clone(), username)
+            },
+        )?;
+
+        Ok(client)
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// A named group of hosts from an inventory.
+pub struct Group {
+    /// The name of the group.
+    #[pyo3(get)]
+    pub name: String,
+    /// The hosts in the group, in inventory order.
+    #[pyo3(get)]
+    pub hosts: Vec<ConnectOptions>,
+}
+
+/// Reads an optional field of a host entry.
+///
+/// # Arguments
+///
+/// * `entry` - The host entry.
+/// * `key` - The name of the field.
+fn field<'a, T: FromPyObject<'a>>(entry: &'a PyDict, key: &str) -> PyResult<Option<T>> {
+    match entry.get_item(key)? {
+        Some(value) if !value.is_none() => value.extract().map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Parses the hosts of a JSON or YAML inventory, which is either a list of host entries or a
+/// mapping with such a list under `hosts`.
+///
+/// # Arguments
+///
+/// * `document` - The parsed inventory document.
+fn parse_document(document: &PyAny) -> PyResult<Vec<ConnectOptions>> {
+    let entries: &PyList = match document.downcast::<PyDict>() {
+        Ok(document) => match document.get_item("hosts")? {
+            Some(hosts) => hosts.downcast()?,
+            None => return Ok(Vec::new()),
+        },
+        Err(_) => document.downcast()?,
+    };
+
+    entries
+        .iter()
+        .map(|entry| {
+            let entry: &PyDict = entry.downcast()?;
+            let Some(host) = field(entry, "host")? else {
+                return Err(PyValueError::new_err(format!(
+                    "Inventory entry has no host: {entry}"
+                )));
+            };
+
+            Ok(ConnectOptions {
+                host,
+                port: field(entry, "port")?,
+                username: field(entry, "user")?,
+                auth: field(entry, "auth")?,
+                groups: field(entry, "groups")?.unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Parses the hosts of an INI inventory, in which every section is a host entry named after the
+/// host (unless it sets `host`), with the groups given as a comma-separated list.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `contents` - The contents of the inventory file.
+fn parse_ini(py: Python<'_>, contents: &str) -> PyResult<Vec<ConnectOptions>> {
+    let parser = py.import("configparser")?.call_method0("ConfigParser")?;
+    parser.call_method1("read_string", (contents,))?;
+
+    let sections: Vec<String> = parser.call_method0("sections")?.extract()?;
+
+    sections
+        .into_iter()
+        .map(|section| {
+            let items = parser.call_method1("items", (&section,))?;
+            let entry = PyDict::from_sequence(py, items.into())?;
+            let port = field::<String>(entry, "port")?
+                .map(|port| {
+                    port.parse::<u16>()
+                        .map_err(|_| PyValueError::new_err(format!("Invalid port: {port}")))
+                })
+                .transpose()?;
+            let groups = field::<String>(entry, "groups")?
+                .map(|groups| {
+                    groups
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|group| !group.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Ok(ConnectOptions {
+                host: field(entry, "host")?.unwrap_or(section),
+                port,
+                username: field(entry, "user")?,
+                auth: field(entry, "auth")?,
+                groups,
+            })
+        })
+        .collect()
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, format=None))]
+/// Loads a host inventory and returns its groups, keyed by name.
+///
+/// Every host entry has a `host` and optionally a `port`, `user`, `auth` (the name of the
+/// credentials to authenticate with) and `groups`. JSON and YAML inventories are either a list of
+/// host entries or a mapping with such a list under `hosts`; in INI inventories, every section is a
+/// host entry named after the host, with `groups` given as a comma-separated list. Every host is
+/// also part of the `all` group.
+///
+/// **NOTE**: YAML inventories require the `PyYAML` package.
+///
+/// # Arguments
+///
+/// * `path` - Path to the inventory file.
+/// * `format` - The format of the inventory: `"json"`, `"yaml"` or `"ini"`. Defaults to guessing
+///   it from the file extension.
+pub fn load_inventory(
+    py: Python<'_>,
+    path: String,
+    format: Option<String>,
+) -> PyResult<HashMap<String, Group>> {
+    let format = format
+        .or_else(|| {
+            Path::new(&path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(str::to_lowercase)
+        })
+        .unwrap_or_default();
+    let contents = fs::read_to_string(&path).map_err(excp_from_err)?;
+
+    let hosts = match format.as_str() {
+        "json" => parse_document(py.import("json")?.call_method1("loads", (contents,))?)?,
+        "yaml" | "yml" => {
+            parse_document(py.import("yaml")?.call_method1("safe_load", (contents,))?)?
+        }
+        "ini" | "cfg" => parse_ini(py, &contents)?,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "Unsupported inventory format: {format}"
+            )))
+        }
+    };
+
+    let mut groups: HashMap<String, Group> = HashMap::new();
+
+    for host in &hosts {
+        for name in &host.groups {
+            groups
+                .entry(name.clone())
+                .or_insert_with(|| Group {
+                    name: name.clone(),
+                    hosts: Vec::new(),
+                })
+                .hosts
+                .push(host.clone());
+        }
+    }
+
+    groups.insert(
+        ALL_GROUP.to_string(),
+        Group {
+            name: ALL_GROUP.to_string(),
+            hosts,
+        },
+    );
+
+    Ok(groups)
+}
@@ -0,0 +1,273 @@
+//! Reaching servers through jump hosts (bastions), like OpenSSH's `ProxyJump`.
+
+#[cfg(unix)]
+use std::io::{self, ErrorKind, Read, Write};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, OwnedFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::thread;
+
+use pyo3::prelude::*;
+#[cfg(unix)]
+use ssh2::{Channel, Session};
+
+use crate::ssh::AuthMethods;
+#[cfg(unix)]
+use crate::ssh::{excp_from_err, SessionException};
+
+/// Size of the buffers used to relay data through a tunnel.
+#[cfg(unix)]
+const TUNNEL_BUFFER_SIZE: usize = 32 * 1024;
+/// Maximum time to wait for data before checking a tunnel again (in milliseconds).
+#[cfg(unix)]
+const TUNNEL_POLL_INTERVAL_MS: libc::c_int = 100;
+
+#[pyclass]
+#[derive(Clone)]
+/// A jump host (bastion) to reach a server through, like OpenSSH's `ProxyJump`.
+pub struct JumpHost {
+    /// The jump host's name or address.
+    #[pyo3(get)]
+    pub host: String,
+    /// The SSH username on the jump host.
+    #[pyo3(get)]
+    pub username: Option<String>,
+    /// The authentication methods to use on the jump host.
+    pub auth: Option<AuthMethods>,
+    /// The jump host's SSH port.
+    #[pyo3(get)]
+    pub port: Option<u16>,
+}
+
+#[pymethods]
+impl JumpHost {
+    #[new]
+    #[pyo3(signature = (host, username=None, auth=None, port=None))]
+    /// Creates a new [`JumpHost`].
+    ///
+    /// Arguments that are omitted fall back to the defaults registered for the jump host with
+    /// [`set_default`](crate::defaults::set_default), if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The jump host's name or address.
+    /// * `username` - The SSH username on the jump host. Defaults to the local user.
+    /// * `auth` - The authentication methods to use on the jump host. Defaults to the default
+    ///   private keys.
+    /// * `port` - The jump host's SSH port. Defaults to 22.
+    pub fn __new__(
+        host: String,
+        username: Option<String>,
+        auth: Option<AuthMethods>,
+        port: Option<u16>,
+    ) -> Self {
+        Self {
+            host,
+            username,
+            auth,
+            port,
+        }
+    }
+}
+
+/// One or several jump hosts, as accepted by [`SSHClient::connect`](crate::ssh::SSHClient::connect).
+#[derive(FromPyObject, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum Jump {
+    /// A single jump host.
+    #[pyo3(annotation = "JumpHost")]
+    Single(JumpHost),
+    /// Jump hosts to go through in order, each reached through the previous one.
+    #[pyo3(annotation = "list[JumpHost]")]
+    Chain(Vec<JumpHost>),
+}
+
+impl Jump {
+    /// Returns the jump hosts in the order they are to be gone through.
+    pub fn into_hosts(self) -> Vec<JumpHost> {
+        match self {
+            Self::Single(host) => vec![host],
+            Self::Chain(hosts) => hosts,
+        }
+    }
+}
+
+/// Opens a `direct-tcpip` channel to a host through an authenticated session, and returns a local
+/// socket that is connected to it, so that another SSH session can be run over the channel.
+///
+/// Data is relayed between the socket and the channel by a background thread, which takes over
+/// the session: it disconnects the session once either end of the tunnel is closed.
+///
+/// # Arguments
+///
+/// * `sess` - The authenticated session to open the channel on.
+/// * `transport` - A duplicate of the session's socket, used to wait for incoming data.
+/// * `host` - The host name or address to connect to, resolved by the server.
+/// * `port` - The port to connect to.
+#[cfg(unix)]
+pub fn open_tunnel(
+    sess: &Session,
+    transport: OwnedFd,
+    host: &str,
+    port: u16,
+) -> PyResult<UnixStream> {
+    let channel = sess.channel_direct_tcpip(host, port, None).map_err(|err| {
+        SessionException::new_err(format!("Failed to open a tunnel to {host}:{port}: {err}"))
+    })?;
+    let (local, remote) = UnixStream::pair().map_err(excp_from_err)?;
+    let sess = sess.clone();
+
+    thread::spawn(move || {
+        relay(&sess, channel, remote, transport);
+        sess.set_blocking(true);
+        let _ = sess.disconnect(None, "", None);
+    });
+
+    Ok(local)
+}
+
+/// Relays data between a channel and a local socket until either end is closed.
+///
+/// The session is switched to non-blocking mode, so that a single thread can serve both
+/// directions.
+///
+/// # Arguments
+///
+/// * `sess` - The session the channel belongs to.
+/// * `channel` - The channel.
+/// * `local` - The local socket.
+/// * `transport` - A duplicate of the session's socket, used to wait for incoming data.
+#[cfg(unix)]
+fn relay(sess: &Session, mut channel: Channel, mut local: UnixStream, transport: OwnedFd) {
+    sess.set_blocking(false);
+
+    if local.set_nonblocking(true).is_err() {
+        return;
+    }
+
+    let mut buf = vec![0; TUNNEL_BUFFER_SIZE];
+    let mut to_local = Vec::new();
+    let mut to_channel = Vec::new();
+
+    loop {
+        let mut progress = false;
+
+        if to_local.is_empty() {
+            match channel.read(&mut buf) {
+                Ok(0) if channel.eof() => break,
+                Ok(n) => {
+                    to_local.extend_from_slice(&buf[..n]);
+                    progress |= n > 0;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if !to_local.is_empty() {
+            match local.write(&to_local) {
+                Ok(n) => {
+                    to_local.drain(..n);
+                    progress = true;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if to_channel.is_empty() {
+            match local.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    to_channel.extend_from_slice(&buf[..n]);
+                    progress = true;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if !to_channel.is_empty() {
+            match channel.write(&to_channel) {
+                Ok(n) => {
+                    to_channel.drain(..n);
+                    progress |= n > 0;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if !progress
+            && wait(
+                &transport,
+                &local,
+                !to_local.is_empty(),
+                to_channel.is_empty(),
+            )
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    let _ = channel.close();
+}
+
+/// Waits until data arrives on the session's socket, or the local socket becomes ready.
+///
+/// Also returns after a short interval, as the channel may become writable again without any
+/// data arriving on the local socket.
+///
+/// # Arguments
+///
+/// * `transport` - The session's socket.
+/// * `local` - The local socket.
+/// * `writing` - Whether data is waiting to be written to the local socket.
+/// * `reading` - Whether to wait for data to read from the local socket.
+#[cfg(unix)]
+fn wait(transport: &OwnedFd, local: &UnixStream, writing: bool, reading: bool) -> io::Result<()> {
+    let mut local_events = 0;
+
+    if writing {
+        local_events |= libc::POLLOUT;
+    }
+
+    if reading {
+        local_events |= libc::POLLIN;
+    }
+
+    let mut fds = [
+        libc::pollfd {
+            fd: transport.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: local.as_raw_fd(),
+            events: local_events,
+            revents: 0,
+        },
+    ];
+    let rc = unsafe {
+        libc::poll(
+            fds.as_mut_ptr(),
+            fds.len() as libc::nfds_t,
+            TUNNEL_POLL_INTERVAL_MS,
+        )
+    };
+
+    match rc {
+        rc if rc < 0 => {
+            let err = io::Error::last_os_error();
+
+            match err.kind() {
+                ErrorKind::Interrupted => Ok(()),
+                _ => Err(err),
+            }
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,778 @@
+//! Parsing and matching of OpenSSH `known_hosts` files, used to verify the host keys presented by
+//! servers.
+
+use std::collections::hash_map::RandomState;
+use std::fs::{self, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::exceptions::{PyNotImplementedError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::certificate::Certificate;
+use crate::defaults;
+use crate::hash;
+use crate::keys;
+use crate::ssh::{excp_from_err, HostKey};
+
+/// The port whose hosts are written without a port in `known_hosts` files.
+const DEFAULT_SSH_PORT: u16 = 22;
+/// Prefix of hashed host names (`|1|<salt>|<hash>`, as written with `HashKnownHosts yes`).
+const HASHED_HOST_PREFIX: &str = "|1|";
+
+/// The registered host key change hook, if any.
+static HOST_KEY_CHANGE_HOOK: Mutex<Option<PyObject>> = Mutex::new(None);
+
+/// A marker at the start of a `known_hosts` entry.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    /// `@cert-authority`: the key is a CA trusted to sign host certificates.
+    CertAuthority,
+    /// `@revoked`: the key must never be accepted.
+    Revoked,
+}
+
+/// A single host key entry of a `known_hosts` file.
+#[derive(Clone)]
+struct Entry {
+    /// The marker of the entry, if any.
+    marker: Option<Marker>,
+    /// The host patterns the entry applies to (possibly negated with a leading `!`, or hashed).
+    patterns: Vec<String>,
+    /// The host key, in SSH wire format.
+    key: Vec<u8>,
+}
+
+impl Entry {
+    /// Parses a line of a `known_hosts` file.
+    ///
+    /// Returns `None` for comments, blank lines and lines that cannot be parsed (including
+    /// unknown markers), which are ignored like OpenSSH does.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line to parse.
+    fn parse(line: &str) -> Option<Self> {
+        let mut line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let mut marker = None;
+        if line.starts_with('@') {
+            let (name, rest) = line.split_once(char::is_whitespace)?;
+            marker = Some(match name {
+                "@cert-authority" => Marker::CertAuthority,
+                "@revoked" => Marker::Revoked,
+                _ => return None,
+            });
+            line = rest.trim_start();
+        }
+
+        let (patterns, key) = line.split_once(char::is_whitespace)?;
+        let key = keys::public_key_blob(key.trim_start())?;
+
+        Some(Self {
+            marker,
+            patterns: patterns.split(',').map(str::to_string).collect(),
+            key,
+        })
+    }
+
+    /// Checks whether the entry applies to a host name (as formatted by [`host_name`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The host name.
+    fn matches(&self, name: &str) -> bool {
+        let mut matched = false;
+
+        for pattern in &self.patterns {
+            if let Some(hashed) = pattern.strip_prefix(HASHED_HOST_PREFIX) {
+                matched |= hashed_host_matches(hashed, name);
+                continue;
+            }
+
+            match pattern.strip_prefix('!') {
+                Some(negated) if defaults::matches(negated, name) => return false,
+                Some(_) => {}
+                None => matched |= defaults::matches(pattern, name),
+            }
+        }
+
+        matched
+    }
+}
+
+/// The result of checking a host key against the known host keys.
+pub enum HostKeyCheck {
+    /// The key is known for the host.
+    Match,
+    /// The host is known with a different key of the same type (the expected key, in SSH wire
+    /// format), which may indicate a man-in-the-middle attack.
+    Mismatch(Vec<u8>),
+    /// The host is not known with a key of this type.
+    NotFound,
+    /// The key (or the CA that signed it) is marked as `@revoked`.
+    Revoked,
+    /// The key is a certificate signed by a trusted CA (`@cert-authority`), but is not valid for
+    /// the host (with the reason).
+    InvalidCertificate(String),
+}
+
+impl HostKeyCheck {
+    /// Returns the name of the result, as returned to Python.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Match => "match",
+            Self::Mismatch(_) => "mismatch",
+            Self::NotFound => "not_found",
+            Self::Revoked => "revoked",
+            Self::InvalidCertificate(_) => "invalid_certificate",
+        }
+    }
+}
+
+#[derive(FromPyObject)]
+/// A public key: a [`HostKey`], a key in OpenSSH format (`<type> <base64>`) or a key in SSH wire
+/// format.
+pub enum PublicKey {
+    /// A host key returned by [`SSHClient::get_host_key`](crate::ssh::SSHClient::get_host_key).
+    #[pyo3(annotation = "HostKey")]
+    HostKey(HostKey),
+    /// A key in OpenSSH format.
+    #[pyo3(annotation = "str")]
+    Text(String),
+    /// A key in SSH wire format.
+    #[pyo3(annotation = "bytes")]
+    Bytes(Vec<u8>),
+}
+
+impl PublicKey {
+    /// Returns the key in SSH wire format.
+    ///
+    /// Fails with a `ValueError` if the key is malformed.
+    pub fn into_blob(self) -> PyResult<Vec<u8>> {
+        let blob = match self {
+            Self::HostKey(key) => keys::base64_decode(&key.base64),
+            Self::Text(key) => keys::public_key_blob(&key),
+            Self::Bytes(key) => Some(key),
+        };
+
+        blob.filter(|blob| keys::public_key_type(blob).is_some())
+            .ok_or_else(|| PyValueError::new_err("Invalid public key".to_string()))
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+/// The host keys of one or more `known_hosts` files.
+///
+/// Hashed host names (`|1|...`, as written with OpenSSH's `HashKnownHosts yes`) are supported, as
+/// are the `@revoked` and `@cert-authority` markers.
+pub struct KnownHosts {
+    /// The lines of the loaded files and the added entries, written back by [`KnownHosts::save`].
+    lines: Vec<String>,
+    /// The host key entries, in file order.
+    entries: Vec<Entry>,
+    /// The most recently loaded file.
+    path: Option<PathBuf>,
+}
+
+impl KnownHosts {
+    /// Reads the entries of a `known_hosts` file, adding them to the known host keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the `known_hosts` file.
+    pub fn load_file(&mut self, path: &Path) -> io::Result<()> {
+        let contents = fs::read_to_string(path)?;
+
+        for line in contents.lines() {
+            self.entries.extend(Entry::parse(line));
+            self.lines.push(line.to_string());
+        }
+
+        self.path = Some(path.to_path_buf());
+
+        Ok(())
+    }
+
+    /// Adds a CA trusted to sign the host certificates of the hosts matching some patterns, like an
+    /// `@cert-authority` entry.
+    ///
+    /// Returns `None` if the key is malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `patterns` - The host patterns, as in `known_hosts` files.
+    /// * `key` - The CA key, in SSH wire format.
+    pub fn add_cert_authority(&mut self, patterns: &[String], key: &[u8]) -> Option<()> {
+        let key_type = keys::public_key_type(key)?;
+        let line = format!(
+            "@cert-authority {} {key_type} {}",
+            patterns.join(","),
+            keys::base64_encode(key)
+        );
+
+        self.entries.push(Entry::parse(&line)?);
+        self.lines.push(line);
+
+        Some(())
+    }
+
+    /// Checks the host key presented by a server.
+    ///
+    /// Keys (or certificate CAs) marked as `@revoked` are rejected. Host certificates signed by a
+    /// CA marked as `@cert-authority` for the host are validated (see [`Certificate::validate`]);
+    /// other certificates are checked as the plain key they certify, like OpenSSH does.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `host` - The host name or address.
+    /// * `port` - The SSH port.
+    /// * `key` - The host key, in SSH wire format.
+    pub fn check_key(
+        &self,
+        py: Python<'_>,
+        host: &str,
+        port: u16,
+        key: &[u8],
+    ) -> PyResult<HostKeyCheck> {
+        let name = host_name(host, port);
+        let certificate = Certificate::parse(key);
+        let key = certificate
+            .as_ref()
+            .map_or(key, |certificate| &certificate.public_key);
+        let entries: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.matches(&name))
+            .collect();
+
+        let revoked = entries.iter().any(|entry| {
+            entry.marker == Some(Marker::Revoked)
+                && (entry.key == key
+                    || certificate
+                        .as_ref()
+                        .is_some_and(|certificate| entry.key == certificate.signature_key))
+        });
+        if revoked {
+            return Ok(HostKeyCheck::Revoked);
+        }
+
+        if let Some(certificate) = &certificate {
+            let trusted = entries.iter().any(|entry| {
+                entry.marker == Some(Marker::CertAuthority)
+                    && entry.key == certificate.signature_key
+            });
+
+            if trusted {
+                return Ok(match certificate.validate(py, host)? {
+                    Ok(()) => HostKeyCheck::Match,
+                    Err(reason) => HostKeyCheck::InvalidCertificate(reason),
+                });
+            }
+        }
+
+        let key_type = keys::public_key_type(key);
+        let mut result = HostKeyCheck::NotFound;
+
+        for entry in entries.iter().filter(|entry| entry.marker.is_none()) {
+            if entry.key == key {
+                return Ok(HostKeyCheck::Match);
+            }
+
+            if keys::public_key_type(&entry.key) == key_type {
+                result = HostKeyCheck::Mismatch(entry.key.clone());
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[pymethods]
+impl KnownHosts {
+    #[new]
+    /// Creates a new [`KnownHosts`], optionally loading a `known_hosts` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the `known_hosts` file to load.
+    pub fn __new__(filename: Option<String>) -> PyResult<Self> {
+        let mut known = Self::default();
+
+        if let Some(filename) = filename {
+            known.load(filename)?;
+        }
+
+        Ok(known)
+    }
+
+    /// Loads the entries of a `known_hosts` file, in addition to the already known host keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the `known_hosts` file.
+    pub fn load(&mut self, filename: String) -> PyResult<()> {
+        self.load_file(Path::new(&filename)).map_err(excp_from_err)
+    }
+
+    /// Checks a host key against the known host keys.
+    ///
+    /// Returns `"match"` if the key is known for the host (or is a valid certificate signed by a
+    /// CA marked as `@cert-authority` for it), `"mismatch"` if the host is known with a different
+    /// key of the same type, `"revoked"` if the key or its CA is marked as `@revoked`,
+    /// `"invalid_certificate"` if the key is a certificate signed by a trusted CA but is not valid
+    /// for the host, or `"not_found"` otherwise.
+    ///
+    /// **NOTE**: Validating certificates requires the `cryptography` package, installed with the
+    /// `certificates` extra (`pip install russh[certificates]`).
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `key` - The host key: a [`HostKey`], a key in OpenSSH format or a key in SSH wire format.
+    /// * `port` - The SSH port. Defaults to 22.
+    #[pyo3(signature = (host, key, port=None))]
+    pub fn check(
+        &self,
+        py: Python<'_>,
+        host: String,
+        key: PublicKey,
+        port: Option<u16>,
+    ) -> PyResult<&'static str> {
+        let key = key.into_blob()?;
+
+        Ok(self
+            .check_key(py, &host, port.unwrap_or(DEFAULT_SSH_PORT), &key)?
+            .name())
+    }
+
+    /// Adds a host key for a host. The key is only written to a file by [`KnownHosts::save`].
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `key` - The host key: a [`HostKey`], a key in OpenSSH format or a key in SSH wire format.
+    /// * `port` - The SSH port. Defaults to 22.
+    /// * `hashed` - Whether to hash the host name, like OpenSSH's `HashKnownHosts yes`, so that
+    ///   the file does not reveal which hosts were connected to. Defaults to `false`.
+    #[pyo3(signature = (host, key, port=None, hashed=false))]
+    pub fn add(
+        &mut self,
+        host: String,
+        key: PublicKey,
+        port: Option<u16>,
+        hashed: bool,
+    ) -> PyResult<()> {
+        let key = key.into_blob()?;
+        let line = format_entry(&host, port.unwrap_or(DEFAULT_SSH_PORT), &key, hashed)
+            .ok_or_else(|| PyValueError::new_err("Invalid public key".to_string()))?;
+
+        self.entries.extend(Entry::parse(&line));
+        self.lines.push(line);
+
+        Ok(())
+    }
+
+    /// Writes the loaded and added entries to a `known_hosts` file, replacing its contents.
+    ///
+    /// Comments and entries that are not understood are written back unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the `known_hosts` file. Defaults to the most recently loaded
+    ///   file.
+    pub fn save(&self, filename: Option<String>) -> PyResult<()> {
+        let Some(path) = filename.map(PathBuf::from).or_else(|| self.path.clone()) else {
+            return Err(PyValueError::new_err(
+                "No file name given and no known_hosts file was loaded".to_string(),
+            ));
+        };
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(excp_from_err)?;
+        }
+
+        let mut contents = self.lines.join("\n");
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+
+        fs::write(path, contents).map_err(excp_from_err)
+    }
+}
+
+#[pyclass(subclass)]
+/// Base class for application-managed host key stores (e.g. backed by a database), which clients
+/// consult instead of `known_hosts` files (see
+/// [`SSHClient::set_host_key_store`](crate::ssh::SSHClient::set_host_key_store)).
+///
+/// Subclasses implement [`HostKeyStore::get`] and [`HostKeyStore::set`]; any other object with
+/// these methods can be used as well.
+pub struct HostKeyStore;
+
+#[pymethods]
+impl HostKeyStore {
+    #[new]
+    /// Creates a new [`HostKeyStore`].
+    pub fn __new__() -> Self {
+        Self
+    }
+
+    /// Returns the known host key of a host (a [`HostKey`], a key in OpenSSH format or a key in
+    /// SSH wire format), or `None` if the host is not known.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `port` - The SSH port.
+    pub fn get(&self, host: String, port: u16) -> PyResult<Option<PyObject>> {
+        let _ = (host, port);
+
+        Err(PyNotImplementedError::new_err(
+            "HostKeyStore.get must be implemented by subclasses".to_string(),
+        ))
+    }
+
+    /// Stores the host key of a host, when a client accepts an unknown host with an
+    /// [`AutoAddPolicy`](crate::ssh::AutoAddPolicy).
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `port` - The SSH port.
+    /// * `key` - The host key.
+    pub fn set(&self, host: String, port: u16, key: HostKey) -> PyResult<()> {
+        let _ = (host, port, key);
+
+        Err(PyNotImplementedError::new_err(
+            "HostKeyStore.set must be implemented by subclasses".to_string(),
+        ))
+    }
+}
+
+/// Checks the host key presented by a server against an application-managed host key store (see
+/// [`HostKeyStore`]), which holds one key per host.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `store` - The host key store.
+/// * `host` - The host name or address.
+/// * `port` - The SSH port.
+/// * `key` - The host key, in SSH wire format.
+pub fn check_store(
+    py: Python<'_>,
+    store: &PyObject,
+    host: &str,
+    port: u16,
+    key: &[u8],
+) -> PyResult<HostKeyCheck> {
+    let known = store.call_method1(py, "get", (host, port))?;
+
+    if known.is_none(py) {
+        return Ok(HostKeyCheck::NotFound);
+    }
+
+    let known = known.extract::<PublicKey>(py)?.into_blob()?;
+
+    Ok(if known == key {
+        HostKeyCheck::Match
+    } else {
+        HostKeyCheck::Mismatch(known)
+    })
+}
+
+/// Formats a host as it appears in `known_hosts` files: the plain host for the default port, or
+/// `[host]:port` otherwise.
+///
+/// # Arguments
+///
+/// * `host` - The host name or address.
+/// * `port` - The SSH port.
+pub fn host_name(host: &str, port: u16) -> String {
+    if port == DEFAULT_SSH_PORT {
+        host.to_string()
+    } else {
+        format!("[{host}]:{port}")
+    }
+}
+
+/// Appends a host key to a `known_hosts` file, creating the file (and its directory) if needed.
+///
+/// # Arguments
+///
+/// * `path` - The path to the `known_hosts` file.
+/// * `host` - The host name or address.
+/// * `port` - The SSH port.
+/// * `key` - The host key, in SSH wire format.
+/// * `hashed` - Whether to hash the host name.
+pub fn append(path: &Path, host: &str, port: u16, key: &[u8], hashed: bool) -> io::Result<()> {
+    let line = format_entry(host, port, key, hashed)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid host key"))?;
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Don't join the new entry to the last line if the file does not end with a newline.
+    let separator = match fs::read(path) {
+        Ok(contents) if !contents.is_empty() && !contents.ends_with(b"\n") => "\n",
+        _ => "",
+    };
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{separator}{line}")
+}
+
+/// Formats a `known_hosts` entry for a host key.
+///
+/// Returns `None` if the key is malformed.
+///
+/// # Arguments
+///
+/// * `host` - The host name or address.
+/// * `port` - The SSH port.
+/// * `key` - The host key, in SSH wire format.
+/// * `hashed` - Whether to hash the host name.
+fn format_entry(host: &str, port: u16, key: &[u8], hashed: bool) -> Option<String> {
+    let key_type = keys::public_key_type(key)?;
+    let name = host_name(host, port);
+    let name = if hashed { hash_host(&name) } else { name };
+
+    Some(format!("{name} {key_type} {}", keys::base64_encode(key)))
+}
+
+/// Checks whether a hashed host name (without its `|1|` prefix) is the hash of a host name.
+///
+/// # Arguments
+///
+/// * `hashed` - The hashed host name, as `<salt>|<hash>` in base64.
+/// * `name` - The host name (as formatted by [`host_name`]).
+fn hashed_host_matches(hashed: &str, name: &str) -> bool {
+    let Some((salt, digest)) = hashed.split_once('|') else {
+        return false;
+    };
+
+    match (keys::base64_decode(salt), keys::base64_decode(digest)) {
+        (Some(salt), Some(digest)) => hash::hmac_sha1(&salt, name.as_bytes())[..] == digest[..],
+        _ => false,
+    }
+}
+
+/// Hashes a host name like OpenSSH does (`|1|<salt>|<hash>`), with a new random salt.
+///
+/// # Arguments
+///
+/// * `name` - The host name (as formatted by [`host_name`]).
+fn hash_host(name: &str) -> String {
+    // The salt only has to be unique; mix the OS-seeded hasher keys with the time.
+    let mut seed = Vec::new();
+    for _ in 0..2 {
+        seed.extend_from_slice(&RandomState::new().build_hasher().finish().to_le_bytes());
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or(0);
+    seed.extend_from_slice(&nanos.to_le_bytes());
+
+    let salt = hash::sha1(&seed);
+
+    format!(
+        "{HASHED_HOST_PREFIX}{}|{}",
+        keys::base64_encode(&salt),
+        keys::base64_encode(&hash::hmac_sha1(&salt, name.as_bytes()))
+    )
+}
+
+/// Reports a host that presented a different key than the known one to the host key change hook,
+/// if one is registered.
+///
+/// Exceptions raised by the hook are reported as unraisable, so that reporting never interferes
+/// with the verification itself.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `name` - The host name (as formatted by [`host_name`]).
+/// * `expected` - The known host key, in SSH wire format.
+/// * `actual` - The host key the server presented, in SSH wire format.
+pub fn report_host_key_change(py: Python<'_>, name: &str, expected: &[u8], actual: &[u8]) {
+    let Some(hook) = HOST_KEY_CHANGE_HOOK
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|hook| hook.clone_ref(py))
+    else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64())
+        .unwrap_or(0.0);
+    let args = (
+        name,
+        HostKey::from_blob(expected),
+        HostKey::from_blob(actual),
+        timestamp,
+    );
+
+    if let Err(err) = hook.call1(py, args) {
+        err.write_unraisable(py, None);
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (hook=None))]
+/// Registers a callable that is invoked whenever a client connects to a known host that presents a
+/// different host key than the known one (as opposed to a host that is not known at all), e.g. to
+/// alert on a possible man-in-the-middle attack.
+///
+/// The hook is called with `(host, expected_key, actual_key, timestamp)`, where `host` is the host
+/// name (`[host]:port` for ports other than 22), the keys are [`HostKey`]s and `timestamp` is when
+/// the change was detected (as a UNIX timestamp). It only observes the change: the connection
+/// still fails with a [`BadHostKeyException`](crate::ssh::BadHostKeyException). Exceptions raised
+/// by the hook are reported but otherwise ignored.
+///
+/// # Arguments
+///
+/// * `hook` - The callable to register. Defaults to `None`, which removes the current hook.
+pub fn set_host_key_change_hook(hook: Option<PyObject>) {
+    *HOST_KEY_CHANGE_HOOK.lock().unwrap() = hook;
+}
+
+/// Returns the path of the user's `known_hosts` file (`~/.ssh/known_hosts`).
+pub fn default_path() -> Option<PathBuf> {
+    keys::ssh_dir().map(|dir| dir.join("known_hosts"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Ed25519 host key, generated with `ssh-keygen`.
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIF8XiiEFu+y2FaQZ7pGfCsCH8rSmUotBVYp25MmWEAxu";
+    /// `example.com`, hashed by `ssh-keygen -H`.
+    const HASHED_HOST: &str = "|1|44mnM+IQXeKMoPtYiEeVqAN57HA=|b8VVwLsZmcXRX0gUYOecSVGXbhs=";
+    /// `[example.com]:2222`, hashed by `ssh-keygen -H`.
+    const HASHED_HOST_PORT: &str = "|1|jxOpj6lNMiySf3oXY/GfurNe30o=|kEPxPdogQ+epl9VgkRc2BI01XHc=";
+
+    fn entry(patterns: &str) -> Entry {
+        Entry::parse(&format!("{patterns} {KEY}")).unwrap()
+    }
+
+    #[test]
+    fn matches_hashed_hosts() {
+        let entry = entry(HASHED_HOST);
+        assert!(entry.matches("example.com"));
+        assert!(!entry.matches("example.org"));
+        assert!(!entry.matches(&host_name("example.com", 2222)));
+
+        let entry = self::entry(HASHED_HOST_PORT);
+        assert!(entry.matches(&host_name("example.com", 2222)));
+        assert!(!entry.matches("example.com"));
+        assert!(!entry.matches(&host_name("example.com", 2022)));
+    }
+
+    #[test]
+    fn rejects_malformed_hashed_hosts() {
+        assert!(!entry("|1|44mnM+IQXeKMoPtYiEeVqAN57HA=").matches("example.com"));
+        assert!(!entry("|1|!!!|b8VVwLsZmcXRX0gUYOecSVGXbhs=").matches("example.com"));
+        assert!(
+            !entry("|2|44mnM+IQXeKMoPtYiEeVqAN57HA=|b8VVwLsZmcXRX0gUYOecSVGXbhs=")
+                .matches("example.com")
+        );
+    }
+
+    #[test]
+    fn hashes_hosts_with_fresh_salts() {
+        let name = host_name("example.com", 2222);
+        let (first, second) = (hash_host(&name), hash_host(&name));
+
+        assert_ne!(first, second);
+        for hashed in [&first, &second] {
+            let hashed = hashed.strip_prefix(HASHED_HOST_PREFIX).unwrap();
+            assert!(hashed_host_matches(hashed, &name));
+            assert!(!hashed_host_matches(hashed, "example.com"));
+        }
+    }
+
+    #[test]
+    fn negated_patterns_take_precedence() {
+        for patterns in [
+            "*.example.com,!bad.example.com",
+            "!bad.example.com,*.example.com",
+        ] {
+            let entry = entry(patterns);
+            assert!(entry.matches("good.example.com"), "{patterns}");
+            assert!(!entry.matches("bad.example.com"), "{patterns}");
+            assert!(!entry.matches("example.org"), "{patterns}");
+        }
+
+        // A negation alone never matches.
+        assert!(!entry("!bad.example.com").matches("good.example.com"));
+        // Negations also apply to hosts matched by hashed patterns.
+        assert!(!entry(&format!("{HASHED_HOST},!example.com")).matches("example.com"));
+    }
+
+    #[test]
+    fn matches_ports() {
+        let entry = entry("[example.com]:2222");
+        assert!(entry.matches(&host_name("example.com", 2222)));
+        assert!(!entry.matches("example.com"));
+
+        let entry = self::entry("example.com");
+        assert!(entry.matches(&host_name("example.com", DEFAULT_SSH_PORT)));
+        assert!(!entry.matches(&host_name("example.com", 2222)));
+    }
+
+    #[test]
+    fn parses_markers_and_skips_other_lines() {
+        let entry = Entry::parse(&format!("@revoked * {KEY}")).unwrap();
+        assert!(entry.marker == Some(Marker::Revoked));
+        assert_eq!(entry.patterns, ["*"]);
+        assert_eq!(entry.key, keys::public_key_blob(KEY).unwrap());
+
+        let entry = Entry::parse(&format!("@cert-authority a,b {KEY}")).unwrap();
+        assert!(entry.marker == Some(Marker::CertAuthority));
+        assert_eq!(entry.patterns, ["a", "b"]);
+
+        for line in [
+            "",
+            "   ",
+            "# comment",
+            &format!("@unknown * {KEY}"),
+            "host",
+            "host ssh-ed25519 !!!",
+        ] {
+            assert!(Entry::parse(line).is_none(), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn formats_entries() {
+        let key = keys::public_key_blob(KEY).unwrap();
+
+        assert_eq!(
+            format_entry("example.com", 2222, &key, false).unwrap(),
+            format!("[example.com]:2222 {KEY}")
+        );
+
+        let hashed = format_entry("example.com", DEFAULT_SSH_PORT, &key, true).unwrap();
+        let entry = Entry::parse(&hashed).unwrap();
+        assert!(entry.patterns[0].starts_with(HASHED_HOST_PREFIX));
+        assert!(entry.matches("example.com"));
+        assert_eq!(entry.key, key);
+    }
+}
@@ -2,21 +2,99 @@
 
 use pyo3::prelude::*;
 
+use audit::*;
+use defaults::*;
+use inventory::*;
+use jump::*;
+use known_hosts::*;
+use pool::*;
 use ssh::*;
+use ssh_config::*;
 
+mod aes;
+mod audit;
+mod certificate;
+mod defaults;
+mod gzip;
+mod hash;
+mod inventory;
+mod jump;
+mod keys;
+mod known_hosts;
+mod lock;
+mod net;
+mod pool;
+mod proxy;
 mod ssh;
+mod ssh_config;
+mod totp;
 
 #[pymodule]
 fn russh(py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add("SessionException", py.get_type::<SessionException>())?;
     m.add("SFTPException", py.get_type::<SFTPException>())?;
+    m.add("ChannelException", py.get_type::<ChannelException>())?;
+    m.add(
+        "PassphraseRequiredException",
+        py.get_type::<PassphraseRequiredException>(),
+    )?;
+    m.add(
+        "PasswordExpiredException",
+        py.get_type::<PasswordExpiredException>(),
+    )?;
+    m.add(
+        "AuthenticationException",
+        py.get_type::<AuthenticationException>(),
+    )?;
+    m.add("BadHostKeyException", py.get_type::<BadHostKeyException>())?;
+    m.add(
+        "NoValidConnectionsError",
+        py.get_type::<NoValidConnectionsError>(),
+    )?;
 
     m.add_class::<PasswordAuth>()?;
     m.add_class::<PrivateKeyAuth>()?;
+    m.add_class::<PrivateKeyMemoryAuth>()?;
+    m.add_class::<AgentAuth>()?;
+    m.add_class::<HostBasedAuth>()?;
+    m.add_class::<KeyboardInteractiveAuth>()?;
     m.add_class::<AuthMethods>()?;
+    m.add_class::<PtyOptions>()?;
+    m.add_class::<TcpOptions>()?;
     m.add_class::<File>()?;
+    m.add_class::<FileStat>()?;
+    m.add_class::<TreeComparison>()?;
+    m.add_class::<WatchEvent>()?;
+    m.add_class::<FileWatcher>()?;
     m.add_class::<SFTPClient>()?;
+    m.add_class::<ForwardedChannel>()?;
+    m.add_class::<RemoteForward>()?;
+    m.add_class::<TransportMetrics>()?;
+    m.add_class::<NegotiatedMethods>()?;
+    m.add_class::<HostKey>()?;
+    m.add_class::<KnownHosts>()?;
+    m.add_class::<HostKeyStore>()?;
+    m.add_class::<RejectPolicy>()?;
+    m.add_class::<AutoAddPolicy>()?;
+    m.add_class::<WarningPolicy>()?;
+    m.add_class::<JumpHost>()?;
+    m.add_class::<SSHConfig>()?;
     m.add_class::<SSHClient>()?;
+    m.add_class::<ConnectionPool>()?;
+    m.add_class::<ConnectOptions>()?;
+    m.add_class::<Group>()?;
+
+    m.add_function(wrap_pyfunction!(wait, m)?)?;
+    m.add_function(wrap_pyfunction!(copy_between, m)?)?;
+    m.add_function(wrap_pyfunction!(quote, m)?)?;
+    m.add_function(wrap_pyfunction!(quote_command, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_ppk, m)?)?;
+    m.add_function(wrap_pyfunction!(supported_key_types, m)?)?;
+    m.add_function(wrap_pyfunction!(set_default, m)?)?;
+    m.add_function(wrap_pyfunction!(clear_defaults, m)?)?;
+    m.add_function(wrap_pyfunction!(set_audit_hook, m)?)?;
+    m.add_function(wrap_pyfunction!(set_host_key_change_hook, m)?)?;
+    m.add_function(wrap_pyfunction!(load_inventory, m)?)?;
 
     Ok(())
 }
@@ -0,0 +1,175 @@
+//! Serialization of the use of an SSH session by several Python threads.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::thread::{self, ThreadId};
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+/// The thread holding a [`SessionLock`].
+#[derive(Default)]
+struct Holder {
+    /// The thread, if the lock is held.
+    thread: Option<ThreadId>,
+    /// The number of times the thread acquired the lock without releasing it yet.
+    depth: usize,
+}
+
+/// A lock serializing the operations made on an SSH session by several threads.
+///
+/// `ssh2` only synchronizes the individual calls made on a session, whereas most operations are
+/// made of several calls, some of which change the state of the whole session (e.g. its timeout or
+/// its blocking mode). The GIL does not make these operations atomic either, as it is released
+/// when calling back into Python (e.g. audit hooks or file-like objects) and while waiting on the
+/// server (e.g. in [`SSHClient::is_active`](crate::ssh::SSHClient::is_active)). The lock is held
+/// for the whole duration of such operations instead.
+///
+/// The lock is reentrant, so that operations can be made of other operations, or call back into
+/// Python code that uses the session on the same thread. The GIL is released while waiting for the
+/// lock, so that the thread holding it can still run Python code.
+#[derive(Default)]
+pub struct SessionLock {
+    /// The thread holding the lock.
+    holder: Mutex<Holder>,
+    /// Notified when the lock is released.
+    released: Condvar,
+}
+
+impl SessionLock {
+    /// Creates a new, shareable [`SessionLock`].
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Returns the thread holding the lock.
+    fn holder(&self) -> MutexGuard<'_, Holder> {
+        self.holder.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquires the lock for a thread if it is free or already held by the thread.
+    ///
+    /// Returns whether the lock was acquired.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread` - The thread.
+    fn try_enter(&self, thread: ThreadId) -> bool {
+        let mut holder = self.holder();
+
+        if holder.thread.is_some_and(|other| other != thread) {
+            return false;
+        }
+
+        holder.thread = Some(thread);
+        holder.depth += 1;
+
+        true
+    }
+
+    /// Acquires the lock for the current thread, waiting for other threads to release it.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting.
+    pub fn acquire(self: &Arc<Self>, py: Python<'_>) -> SessionGuard {
+        let thread = thread::current().id();
+
+        if !self.try_enter(thread) {
+            py.allow_threads(|| {
+                let mut holder = self.holder();
+
+                while holder.thread.is_some() {
+                    holder = self
+                        .released
+                        .wait(holder)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+
+                holder.thread = Some(thread);
+                holder.depth = 1;
+            });
+        }
+
+        SessionGuard {
+            lock: Arc::clone(self),
+        }
+    }
+}
+
+/// Acquires two locks, which may be the same one, always in the same order so that threads
+/// acquiring both cannot deadlock.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token, released while waiting.
+/// * `first` - The first lock.
+/// * `second` - The second lock.
+pub fn acquire_pair(
+    py: Python<'_>,
+    first: &Arc<SessionLock>,
+    second: &Arc<SessionLock>,
+) -> (SessionGuard, SessionGuard) {
+    if Arc::as_ptr(first) <= Arc::as_ptr(second) {
+        let first = first.acquire(py);
+        (first, second.acquire(py))
+    } else {
+        let second = second.acquire(py);
+        (first.acquire(py), second)
+    }
+}
+
+/// Keeps a [`SessionLock`] acquired until it is dropped.
+pub struct SessionGuard {
+    /// The lock.
+    lock: Arc<SessionLock>,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        let mut holder = self.lock.holder();
+        holder.depth -= 1;
+
+        if holder.depth == 0 {
+            holder.thread = None;
+            self.lock.released.notify_one();
+        }
+    }
+}
+
+/// The mutable state of a Python object whose operations are serialized by a [`SessionLock`].
+///
+/// Python objects are borrowed before their methods run, so an object whose methods borrow it
+/// mutably cannot be used by a thread while another thread is running one of them (e.g. waiting
+/// on the server with the GIL released). Such objects keep their state here instead, and only
+/// borrow it once they hold the session lock.
+pub struct LockedState<T> {
+    /// The state.
+    state: Mutex<T>,
+}
+
+impl<T> LockedState<T> {
+    /// Creates a new [`LockedState`].
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The initial state.
+    pub fn new(state: T) -> Self {
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Borrows the state, which must only be done while holding the session lock.
+    ///
+    /// Fails if the state is already borrowed, i.e. if Python code called back by an operation on
+    /// the object uses the object again.
+    pub fn borrow_mut(&self) -> PyResult<MutexGuard<'_, T>> {
+        match self.state.try_lock() {
+            Ok(state) => Ok(state),
+            Err(TryLockError::Poisoned(err)) => Ok(err.into_inner()),
+            Err(TryLockError::WouldBlock) => Err(PyRuntimeError::new_err(
+                "Object is already in use by the current operation".to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! Helpers for establishing the TCP connection underlying an SSH session.
+
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Delay before starting the next connection attempt while earlier ones are still pending, as
+/// recommended by RFC 8305 ("Happy Eyeballs").
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// The address families to connect over.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Both IPv4 and IPv6.
+    #[default]
+    Any,
+    /// IPv4 only.
+    Ipv4,
+    /// IPv6 only.
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Parses an address family from its name (`"any"`, `"ipv4"` or `"ipv6"`).
+    ///
+    /// Returns `None` if the name is not recognised.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the address family.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "any" => Some(Self::Any),
+            "ipv4" => Some(Self::Ipv4),
+            "ipv6" => Some(Self::Ipv6),
+            _ => None,
+        }
+    }
+
+    /// Returns the family of an address.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address.
+    pub fn of(addr: &SocketAddr) -> Self {
+        if addr.is_ipv4() {
+            Self::Ipv4
+        } else {
+            Self::Ipv6
+        }
+    }
+
+    /// Checks whether an address belongs to the family.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to check.
+    fn allows(self, addr: &SocketAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Ipv4 => addr.is_ipv4(),
+            Self::Ipv6 => addr.is_ipv6(),
+        }
+    }
+}
+
+/// Strips the brackets around an IPv6 address written as in URLs (e.g. `[::1]`), leaving other
+/// host names and addresses untouched.
+///
+/// # Arguments
+///
+/// * `host` - The host name or address.
+pub fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// Returns the path of the Unix socket a host refers to, if it is written as a `unix://` URL
+/// (e.g. `unix:///run/sshd.sock`).
+///
+/// # Arguments
+///
+/// * `host` - The host name or address.
+pub fn unix_socket_path(host: &str) -> Option<&str> {
+    host.strip_prefix("unix://")
+}
+
+/// Resolves a host name or address into the socket addresses to connect to, using the system
+/// resolver.
+///
+/// # Arguments
+///
+/// * `host` - The host name or address.
+/// * `port` - The port to connect to.
+/// * `family` - The address families to keep.
+pub fn resolve(host: &str, port: u16, family: AddressFamily) -> io::Result<Vec<SocketAddr>> {
+    select(host, (host, port).to_socket_addrs()?.collect(), family)
+}
+
+/// Keeps only the addresses of the given families, failing if none are left.
+///
+/// # Arguments
+///
+/// * `host` - The host the addresses belong to (used in the error message).
+/// * `addrs` - The resolved addresses.
+/// * `family` - The address families to keep.
+pub fn select(
+    host: &str,
+    addrs: Vec<SocketAddr>,
+    family: AddressFamily,
+) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = addrs
+        .into_iter()
+        .filter(|addr| family.allows(addr))
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!("No addresses found for host: {host}"),
+        ));
+    }
+
+    Ok(addrs)
+}
+
+/// Reorders addresses so that the two families alternate, starting with the family of the first
+/// address (RFC 8305, section 4).
+///
+/// # Arguments
+///
+/// * `addrs` - The addresses in resolver order.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first().copied() else {
+        return addrs;
+    };
+
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first.is_ipv6());
+    preferred.reverse();
+    other.reverse();
+
+    let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+
+    while !preferred.is_empty() || !other.is_empty() {
+        ordered.extend(preferred.pop());
+        ordered.extend(other.pop());
+    }
+
+    ordered
+}
+
+/// Connects to the first reachable address, racing staggered attempts across address families
+/// ("Happy Eyeballs", RFC 8305) so that a broken path does not delay the connection by a full
+/// timeout.
+///
+/// A new attempt is started every 250 milliseconds, or as soon as the previous one fails. The first
+/// attempt to succeed wins; the others are abandoned.
+///
+/// If every attempt fails, returns each address along with the error it failed with, in the order
+/// the attempts were started.
+///
+/// # Arguments
+///
+/// * `addrs` - The addresses to connect to.
+/// * `timeout` - The timeout for each connection attempt.
+/// * `source` - Optional local address to connect from (of the same family as `addrs`).
+pub fn connect(
+    addrs: Vec<SocketAddr>,
+    timeout: Duration,
+    source: Option<SocketAddr>,
+) -> Result<TcpStream, Vec<(SocketAddr, io::Error)>> {
+    let (sender, receiver) = mpsc::channel();
+    let mut pending = interleave(addrs).into_iter().enumerate();
+    let mut in_flight = 0;
+    let mut failures = Vec::new();
+
+    loop {
+        if let Some((index, addr)) = pending.next() {
+            let sender = sender.clone();
+
+            thread::spawn(move || {
+                let result = match source {
+                    Some(source) => connect_from(&addr, &source, timeout),
+                    None => TcpStream::connect_timeout(&addr, timeout),
+                };
+                let _ = sender.send((index, addr, result));
+            });
+            in_flight += 1;
+        } else if in_flight == 0 {
+            break;
+        }
+
+        let (index, addr, result) = if pending.len() > 0 {
+            match receiver.recv_timeout(CONNECTION_ATTEMPT_DELAY) {
+                Ok(attempt) => attempt,
+                Err(_) => continue,
+            }
+        } else {
+            match receiver.recv() {
+                Ok(attempt) => attempt,
+                Err(_) => break,
+            }
+        };
+        in_flight -= 1;
+
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(err) => failures.push((index, addr, err)),
+        }
+    }
+
+    failures.sort_by_key(|(index, _, _)| *index);
+
+    Err(failures
+        .into_iter()
+        .map(|(_, addr, err)| (addr, err))
+        .collect())
+}
+
+/// Converts a socket address into its C representation, returning it along with its length.
+///
+/// # Arguments
+///
+/// * `addr` - The socket address.
+#[cfg(unix)]
+fn raw_sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let raw = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            raw.sin_family = libc::AF_INET as libc::sa_family_t;
+            raw.sin_port = addr.port().to_be();
+            raw.sin_addr = libc::in_addr {
+                s_addr: u32::from_ne_bytes(addr.ip().octets()),
+            };
+            std::mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let raw = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            raw.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            raw.sin6_port = addr.port().to_be();
+            raw.sin6_flowinfo = addr.flowinfo();
+            raw.sin6_addr = libc::in6_addr {
+                s6_addr: addr.ip().octets(),
+            };
+            raw.sin6_scope_id = addr.scope_id();
+            std::mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    (storage, len as libc::socklen_t)
+}
+
+/// Connects to an address from a specific local address, which the standard library does not
+/// support.
+///
+/// # Arguments
+///
+/// * `addr` - The address to connect to.
+/// * `source` - The local address to bind to (port 0 lets the system pick a port).
+/// * `timeout` - The timeout for the connection attempt.
+#[cfg(unix)]
+fn connect_from(
+    addr: &SocketAddr,
+    source: &SocketAddr,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    let domain = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    let fd = unsafe { libc::socket(domain, libc::SOCK_STREAM, 0) };
+
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // The stream owns the socket from here on, and closes it on failure.
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+
+    unsafe {
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+
+    let (local, local_len) = raw_sockaddr(source);
+    let rc = unsafe { libc::bind(fd, &local as *const _ as *const libc::sockaddr, local_len) };
+
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    stream.set_nonblocking(true)?;
+
+    let (remote, remote_len) = raw_sockaddr(addr);
+    let rc = unsafe { libc::connect(fd, &remote as *const _ as *const libc::sockaddr, remote_len) };
+
+    if rc != 0 {
+        let err = io::Error::last_os_error();
+
+        if err.raw_os_error() != Some(libc::EINPROGRESS) {
+            return Err(err);
+        }
+
+        let mut pollfd = libc::pollfd {
+            fd: stream.as_raw_fd(),
+            events: libc::POLLOUT,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+        match unsafe { libc::poll(&mut pollfd, 1, millis) } {
+            0 => return Err(io::Error::new(ErrorKind::TimedOut, "Connection timed out")),
+            rc if rc < 0 => return Err(io::Error::last_os_error()),
+            _ => {}
+        }
+
+        if let Some(err) = stream.take_error()? {
+            return Err(err);
+        }
+    }
+
+    stream.set_nonblocking(false)?;
+
+    Ok(stream)
+}
+
+/// Connects to an address from a specific local address.
+///
+/// Binding the source address is only supported on Unix platforms.
+///
+/// # Arguments
+///
+/// * `addr` - The address to connect to.
+/// * `source` - The local address to bind to.
+/// * `timeout` - The timeout for the connection attempt.
+#[cfg(not(unix))]
+fn connect_from(
+    _addr: &SocketAddr,
+    _source: &SocketAddr,
+    _timeout: Duration,
+) -> io::Result<TcpStream> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "Binding a source address is not supported on this platform",
+    ))
+}
+
+/// Returns the number of bytes sent (and acknowledged by the peer) and received on a TCP
+/// connection, as tracked by the operating system.
+///
+/// Returns `None` if the platform does not report these counters (currently, only Linux does).
+///
+/// # Arguments
+///
+/// * `stream` - The TCP connection.
+#[cfg(target_os = "linux")]
+pub fn transfer_counts(stream: &TcpStream) -> Option<(u64, u64)> {
+    use std::os::fd::AsRawFd;
+
+    let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut libc::tcp_info as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    (rc == 0).then_some((info.tcpi_bytes_acked, info.tcpi_bytes_received))
+}
+
+/// Returns the number of bytes sent (and acknowledged by the peer) and received on a TCP
+/// connection, as tracked by the operating system.
+///
+/// Returns `None` if the platform does not report these counters (currently, only Linux does).
+///
+/// # Arguments
+///
+/// * `stream` - The TCP connection.
+#[cfg(not(target_os = "linux"))]
+pub fn transfer_counts(_stream: &TcpStream) -> Option<(u64, u64)> {
+    None
+}
+
+/// Sets an integer socket option on a TCP connection.
+///
+/// # Arguments
+///
+/// * `stream` - The TCP connection.
+/// * `level` - The protocol level of the option (e.g. `SOL_SOCKET`).
+/// * `name` - The option.
+/// * `value` - The value to set.
+#[cfg(unix)]
+fn set_option(
+    stream: &TcpStream,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    let rc = unsafe {
+        libc::setsockopt(
+            stream.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    match rc {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Enables TCP keepalive probes on a connection, sent once it has been idle for the interval, and
+/// then every interval until the peer answers.
+///
+/// The interval can only be configured on Linux and macOS; other platforms use the system's.
+///
+/// # Arguments
+///
+/// * `stream` - The TCP connection.
+/// * `interval` - The interval between probes.
+#[cfg(unix)]
+pub fn set_keepalive(stream: &TcpStream, interval: Duration) -> io::Result<()> {
+    set_option(stream, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    {
+        let secs = interval.as_secs().clamp(1, libc::c_int::MAX as u64) as libc::c_int;
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        set_option(stream, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, secs)?;
+        #[cfg(target_os = "macos")]
+        set_option(stream, libc::IPPROTO_TCP, libc::TCP_KEEPALIVE, secs)?;
+
+        set_option(stream, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, secs)?;
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+    let _ = interval;
+
+    Ok(())
+}
+
+/// Enables TCP keepalive probes on a connection.
+///
+/// Not supported on this platform.
+///
+/// # Arguments
+///
+/// * `_stream` - The TCP connection.
+/// * `_interval` - The interval between probes.
+#[cfg(not(unix))]
+pub fn set_keepalive(_stream: &TcpStream, _interval: Duration) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "TCP keepalive settings are not supported on this platform",
+    ))
+}
+
+/// Sets the sizes of a TCP connection's send and receive buffers, as hints to the operating
+/// system (which may round or cap them).
+///
+/// # Arguments
+///
+/// * `stream` - The TCP connection.
+/// * `send` - The size of the send buffer (in bytes), if it is to be set.
+/// * `recv` - The size of the receive buffer (in bytes), if it is to be set.
+#[cfg(unix)]
+pub fn set_buffer_sizes(
+    stream: &TcpStream,
+    send: Option<u32>,
+    recv: Option<u32>,
+) -> io::Result<()> {
+    let size = |size: u32| size.min(libc::c_int::MAX as u32) as libc::c_int;
+
+    if let Some(send) = send {
+        set_option(stream, libc::SOL_SOCKET, libc::SO_SNDBUF, size(send))?;
+    }
+
+    if let Some(recv) = recv {
+        set_option(stream, libc::SOL_SOCKET, libc::SO_RCVBUF, size(recv))?;
+    }
+
+    Ok(())
+}
+
+/// Sets the sizes of a TCP connection's send and receive buffers.
+///
+/// Not supported on this platform.
+///
+/// # Arguments
+///
+/// * `_stream` - The TCP connection.
+/// * `_send` - The size of the send buffer (in bytes), if it is to be set.
+/// * `_recv` - The size of the receive buffer (in bytes), if it is to be set.
+#[cfg(not(unix))]
+pub fn set_buffer_sizes(
+    _stream: &TcpStream,
+    _send: Option<u32>,
+    _recv: Option<u32>,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "TCP buffer sizes are not supported on this platform",
+    ))
+}
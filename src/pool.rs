@@ -0,0 +1,331 @@
+//! Reuse of authenticated SSH sessions across many short operations, like OpenSSH's
+//! `ControlMaster`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::defaults;
+use crate::ssh::{
+    duration_from_secs, is_transport_lost, AuthMethods, ConnectArgs, ExecOutput, PtyOptions,
+    SFTPClient, SSHClient, SessionException, DEFAULT_PORT,
+};
+
+/// Default maximum number of channels open at once on a pooled session (OpenSSH's default
+/// `MaxSessions`).
+const DEFAULT_MAX_CHANNELS: usize = 10;
+/// Default time after which an unused pooled session is closed.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The key pooled sessions are looked up by: the host, port and username.
+type PoolKey = (String, u16, String);
+
+/// A channel handed out by a [`ConnectionPool`].
+enum Lease {
+    /// A command started with [`ConnectionPool::exec_command`].
+    Exec(Py<ExecOutput>),
+    /// An SFTP session opened with [`ConnectionPool::open_sftp`].
+    Sftp(Py<SFTPClient>),
+}
+
+impl Lease {
+    /// Checks whether the channel is still in use: it must not be closed, and still be referenced
+    /// outside of the pool. Channels that are being used by another thread are in use.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn in_use(&self, py: Python<'_>) -> bool {
+        match self {
+            Self::Exec(output) => {
+                output.get_refcnt(py) > 1 && output.borrow(py).try_closed() != Some(true)
+            }
+            Self::Sftp(sftp) => {
+                sftp.get_refcnt(py) > 1 && sftp.borrow(py).try_is_closed() != Some(true)
+            }
+        }
+    }
+}
+
+/// An authenticated session kept by a [`ConnectionPool`].
+struct PooledSession {
+    /// The client holding the session.
+    client: Py<SSHClient>,
+    /// The channels handed out on the session that may still be in use.
+    leases: Vec<Lease>,
+    /// When a channel was last handed out on the session.
+    last_used: Instant,
+}
+
+impl PooledSession {
+    /// Forgets the channels that are no longer in use, and returns the number of remaining ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn channels_in_use(&mut self, py: Python<'_>) -> usize {
+        self.leases.retain(|lease| lease.in_use(py));
+        self.leases.len()
+    }
+
+    /// Checks whether the session's connection to the server is still usable, going by the
+    /// session's last error.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn is_alive(&self, py: Python<'_>) -> bool {
+        self.client.try_borrow(py).map_or(true, |client| {
+            client
+                .session()
+                .is_some_and(|sess| !is_transport_lost(sess))
+        })
+    }
+
+    /// Closes the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn close(&self, py: Python<'_>) {
+        let _ = SSHClient::close(self.client.as_ref(py), py);
+    }
+}
+
+#[pyclass]
+/// A pool of authenticated SSH sessions, reused across operations on the same host, port and
+/// username, like OpenSSH's `ControlMaster`. This saves a TCP connection, a handshake and an
+/// authentication for each of the many short commands an application may run.
+///
+/// A new session is opened when every session to the destination already has the maximum number
+/// of channels open. Sessions that have not been used for a while are closed.
+pub struct ConnectionPool {
+    /// The pooled sessions, by destination.
+    sessions: HashMap<PoolKey, Vec<PooledSession>>,
+    /// Maximum number of channels open at once on a session.
+    max_channels: usize,
+    /// Time after which an unused session is closed.
+    idle_timeout: Duration,
+    /// The timeout for TCP connections (in seconds).
+    timeout: Option<u32>,
+}
+
+impl ConnectionPool {
+    /// Returns a session to the destination that can take another channel, opening a new one if
+    /// needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `host` - The host name or address.
+    /// * `username` - The SSH username. Defaults to the local user.
+    /// * `auth` - The authentication methods to use when a new session is opened.
+    /// * `port` - The SSH port. Defaults to the registered defaults, or 22.
+    fn checkout(
+        &mut self,
+        py: Python<'_>,
+        host: String,
+        username: Option<String>,
+        auth: Option<AuthMethods>,
+        port: Option<u16>,
+    ) -> PyResult<&mut PooledSession> {
+        self.reap(py);
+
+        let Some(username) = username.or_else(defaults::local_username) else {
+            return Err(PyValueError::new_err(
+                "No username provided and the local user could not be determined".to_string(),
+            ));
+        };
+        let port = port
+            .or(defaults::lookup(&host).port)
+            .unwrap_or(DEFAULT_PORT);
+        let max_channels = self.max_channels;
+        let sessions = self
+            .sessions
+            .entry((host.clone(), port, username.clone()))
+            .or_default();
+
+        let index = match sessions
+            .iter_mut()
+            .position(|session| session.channels_in_use(py) < max_channels)
+        {
+            Some(index) => index,
+            None => {
+                let mut client = SSHClient::__new__();
+                client.connect_with(
+                    py,
+                    ConnectArgs {
+                        auth,
+                        port: Some(port),
+                        timeout: self.timeout,
+                        ..ConnectArgs::new(host, username)
+                    },
+                )?;
+
+                if client.session().is_none() {
+                    return Err(SessionException::new_err(
+                        "Failed to authenticate".to_string(),
+                    ));
+                }
+
+                sessions.push(PooledSession {
+                    client: Py::new(py, client)?,
+                    leases: Vec::new(),
+                    last_used: Instant::now(),
+                });
+
+                sessions.len() - 1
+            }
+        };
+
+        let session = &mut sessions[index];
+        session.last_used = Instant::now();
+
+        Ok(session)
+    }
+}
+
+#[pymethods]
+impl ConnectionPool {
+    #[new]
+    #[pyo3(signature = (max_channels=None, idle_timeout=None, timeout=None))]
+    /// Creates a new, empty [`ConnectionPool`].
+    ///
+    /// # Arguments
+    ///
+    /// * `max_channels` - Maximum number of channels open at once on a session, which should not
+    ///   exceed the server's `MaxSessions`. Defaults to 10.
+    /// * `idle_timeout` - Time after which a session that has no channel in use is closed (in
+    ///   seconds). Defaults to 300.
+    /// * `timeout` - The timeout for TCP connections (in seconds). Defaults to 30.
+    pub fn __new__(
+        max_channels: Option<usize>,
+        idle_timeout: Option<f64>,
+        timeout: Option<u32>,
+    ) -> PyResult<Self> {
+        if max_channels == Some(0) {
+            return Err(PyValueError::new_err(
+                "At least one channel per session must be allowed".to_string(),
+            ));
+        }
+
+        let idle_timeout = match idle_timeout {
+            Some(idle_timeout) => duration_from_secs("idle_timeout", idle_timeout)?,
+            None => DEFAULT_IDLE_TIMEOUT,
+        };
+
+        Ok(Self {
+            sessions: HashMap::new(),
+            max_channels: max_channels.unwrap_or(DEFAULT_MAX_CHANNELS),
+            idle_timeout,
+            timeout,
+        })
+    }
+
+    /// The number of sessions in the pool.
+    #[getter]
+    pub fn size(&self) -> usize {
+        self.sessions.values().map(Vec::len).sum()
+    }
+
+    /// Executes a command on a pooled session to the host, and returns the output (see
+    /// [`SSHClient::exec_command`]).
+    ///
+    /// The command's channel counts towards the session's maximum until the output is closed (or
+    /// the command has finished and its exit status was read), or is no longer referenced.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `command` - The command to run.
+    /// * `username` - The SSH username. Defaults to the local user.
+    /// * `auth` - The authentication methods to use if a new session has to be opened. Required
+    ///   unless defaults provide them, or the default private keys can be used.
+    /// * `port` - The SSH port. Defaults to the registered defaults, or 22.
+    /// * `pty` - Optional options for allocating a pseudo-terminal (PTY) for the command.
+    /// * `cwd` - Optional remote directory to run the command in.
+    #[pyo3(signature = (host, command, username=None, auth=None, port=None, pty=None, cwd=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn exec_command(
+        &mut self,
+        py: Python<'_>,
+        host: String,
+        command: String,
+        username: Option<String>,
+        auth: Option<AuthMethods>,
+        port: Option<u16>,
+        pty: Option<PtyOptions>,
+        cwd: Option<String>,
+    ) -> PyResult<Py<ExecOutput>> {
+        let session = self.checkout(py, host, username, auth, port)?;
+        let output =
+            SSHClient::exec_command(session.client.as_ref(py), py, command, pty, None, cwd)?;
+        let output = Py::new(py, output)?;
+        session.leases.push(Lease::Exec(output.clone_ref(py)));
+
+        Ok(output)
+    }
+
+    /// Opens an SFTP session on a pooled session to the host (see [`SSHClient::open_sftp`]).
+    ///
+    /// The SFTP session's channel counts towards the session's maximum until it is closed, or is
+    /// no longer referenced.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `username` - The SSH username. Defaults to the local user.
+    /// * `auth` - The authentication methods to use if a new session has to be opened.
+    /// * `port` - The SSH port. Defaults to the registered defaults, or 22.
+    #[pyo3(signature = (host, username=None, auth=None, port=None))]
+    pub fn open_sftp(
+        &mut self,
+        py: Python<'_>,
+        host: String,
+        username: Option<String>,
+        auth: Option<AuthMethods>,
+        port: Option<u16>,
+    ) -> PyResult<Py<SFTPClient>> {
+        let session = self.checkout(py, host, username, auth, port)?;
+        let sftp = SSHClient::open_sftp(session.client.as_ref(py), py, false)?;
+        let sftp = Py::new(py, sftp)?;
+        session.leases.push(Lease::Sftp(sftp.clone_ref(py)));
+
+        Ok(sftp)
+    }
+
+    /// Closes the sessions that have no channel in use and have not been used for longer than the
+    /// idle timeout, as well as those whose connection to the server was lost.
+    ///
+    /// This is done automatically whenever the pool hands out a channel.
+    pub fn reap(&mut self, py: Python<'_>) {
+        let idle_timeout = self.idle_timeout;
+
+        for sessions in self.sessions.values_mut() {
+            sessions.retain_mut(|session| {
+                let keep = session.is_alive(py)
+                    && (session.channels_in_use(py) > 0
+                        || session.last_used.elapsed() < idle_timeout);
+
+                if !keep {
+                    session.close(py);
+                }
+
+                keep
+            });
+        }
+
+        self.sessions.retain(|_, sessions| !sessions.is_empty());
+    }
+
+    /// Closes every session in the pool.
+    ///
+    /// Channels that are still in use are closed along with their session.
+    pub fn close(&mut self, py: Python<'_>) {
+        for session in self.sessions.drain().flat_map(|(_, sessions)| sessions) {
+            session.close(py);
+        }
+    }
+}
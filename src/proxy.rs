@@ -0,0 +1,352 @@
+//! Tunneling the TCP connection underlying an SSH session through a proxy server.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::Duration;
+
+use crate::{keys, net};
+
+/// Default port of SOCKS proxies.
+const DEFAULT_SOCKS_PORT: u16 = 1080;
+/// Default port of HTTP proxies.
+const DEFAULT_HTTP_PORT: u16 = 8080;
+/// Maximum size of the response headers accepted from an HTTP proxy.
+const MAX_HTTP_RESPONSE_SIZE: usize = 16 * 1024;
+/// SOCKS protocol version 5.
+const SOCKS5_VERSION: u8 = 5;
+/// SOCKS5 authentication method: no authentication.
+const SOCKS5_NO_AUTH: u8 = 0x00;
+/// SOCKS5 authentication method: username and password (RFC 1929).
+const SOCKS5_PASSWORD_AUTH: u8 = 0x02;
+/// SOCKS5 reply when none of the offered authentication methods is acceptable.
+const SOCKS5_NO_ACCEPTABLE_METHOD: u8 = 0xff;
+/// Version of the SOCKS5 username and password authentication subnegotiation.
+const SOCKS5_PASSWORD_AUTH_VERSION: u8 = 1;
+/// SOCKS5 command: establish a TCP connection.
+const SOCKS5_CONNECT: u8 = 1;
+/// SOCKS5 address type: IPv4 address.
+const SOCKS5_IPV4: u8 = 1;
+/// SOCKS5 address type: domain name.
+const SOCKS5_DOMAIN: u8 = 3;
+/// SOCKS5 address type: IPv6 address.
+const SOCKS5_IPV6: u8 = 4;
+
+/// The protocols spoken by proxy servers.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// SOCKS5 (RFC 1928).
+    Socks5,
+    /// HTTP, with the `CONNECT` method (RFC 9110).
+    Http,
+}
+
+/// A proxy server to tunnel connections through.
+#[derive(Clone)]
+pub struct Proxy {
+    /// The protocol spoken by the proxy.
+    kind: ProxyKind,
+    /// The proxy's host name or address.
+    pub host: String,
+    /// The proxy's port.
+    pub port: u16,
+    /// Optional username and password to authenticate to the proxy with.
+    credentials: Option<(String, String)>,
+}
+
+/// Decodes the percent-encoded characters of a URL component (e.g. `%40` for `@`).
+///
+/// Returns `None` if the component is malformed.
+///
+/// # Arguments
+///
+/// * `component` - The URL component.
+fn percent_decode(component: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(component.len());
+    let mut bytes = component.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+impl Proxy {
+    /// Parses a proxy URL of the form `socks5://[user[:password]@]host[:port]` or
+    /// `http://[user[:password]@]host[:port]`.
+    ///
+    /// Returns `None` if the URL is malformed or its scheme is not supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The proxy URL.
+    pub fn parse(url: &str) -> Option<Self> {
+        let (scheme, rest) = url.split_once("://")?;
+        let (kind, default_port) = match scheme.to_ascii_lowercase().as_str() {
+            "socks5" | "socks5h" => (ProxyKind::Socks5, DEFAULT_SOCKS_PORT),
+            "http" => (ProxyKind::Http, DEFAULT_HTTP_PORT),
+            _ => return None,
+        };
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((userinfo, address)) => {
+                let (username, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+                (
+                    Some((percent_decode(username)?, percent_decode(password)?)),
+                    address,
+                )
+            }
+            None => (None, rest),
+        };
+
+        // IPv6 addresses are enclosed in brackets, and contain colons themselves.
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+            _ => (address, default_port),
+        };
+        let host = net::strip_brackets(host);
+
+        if host.is_empty() || host.contains(['/', '[', ']']) {
+            return None;
+        }
+
+        Some(Self {
+            kind,
+            host: host.to_string(),
+            port,
+            credentials,
+        })
+    }
+
+    /// Asks the proxy to connect to a host, over an established connection to the proxy. Once
+    /// this returns, the connection is tunneled to the host.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection to the proxy.
+    /// * `host` - The host name or address to connect to, resolved by the proxy.
+    /// * `port` - The port to connect to.
+    /// * `timeout` - The timeout for the exchange with the proxy (`None` to wait indefinitely).
+    pub fn open_tunnel(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        match self.kind {
+            ProxyKind::Socks5 => self.socks5_connect(stream, host, port),
+            ProxyKind::Http => self.http_connect(stream, host, port),
+        }?;
+
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(None)
+    }
+
+    /// Performs the SOCKS5 handshake (RFC 1928), authenticating with a username and password
+    /// (RFC 1929) if the proxy requires it.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection to the proxy.
+    /// * `host` - The host name or address to connect to.
+    /// * `port` - The port to connect to.
+    fn socks5_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let methods: &[u8] = match self.credentials {
+            Some(_) => &[SOCKS5_NO_AUTH, SOCKS5_PASSWORD_AUTH],
+            None => &[SOCKS5_NO_AUTH],
+        };
+        let mut greeting = vec![SOCKS5_VERSION, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting)?;
+
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply)?;
+
+        if reply[0] != SOCKS5_VERSION {
+            return Err(proxy_error("Proxy server is not a SOCKS5 server"));
+        }
+
+        match (reply[1], &self.credentials) {
+            (SOCKS5_NO_AUTH, _) => {}
+            (SOCKS5_PASSWORD_AUTH, Some((username, password))) => {
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "SOCKS5 username and password must be at most 255 bytes long",
+                    ));
+                }
+
+                let mut request = vec![SOCKS5_PASSWORD_AUTH_VERSION, username.len() as u8];
+                request.extend_from_slice(username.as_bytes());
+                request.push(password.len() as u8);
+                request.extend_from_slice(password.as_bytes());
+                stream.write_all(&request)?;
+
+                let mut reply = [0; 2];
+                stream.read_exact(&mut reply)?;
+
+                if reply[1] != 0 {
+                    return Err(io::Error::new(
+                        ErrorKind::PermissionDenied,
+                        "SOCKS5 proxy authentication failed",
+                    ));
+                }
+            }
+            (SOCKS5_NO_ACCEPTABLE_METHOD | SOCKS5_PASSWORD_AUTH, _) => {
+                return Err(io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    "SOCKS5 proxy requires authentication",
+                ));
+            }
+            (method, _) => {
+                return Err(proxy_error(&format!(
+                    "SOCKS5 proxy selected an unsupported authentication method: {method}"
+                )));
+            }
+        }
+
+        let mut request = vec![SOCKS5_VERSION, SOCKS5_CONNECT, 0];
+
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(SOCKS5_IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(SOCKS5_IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) if host.len() <= 255 => {
+                request.extend_from_slice(&[SOCKS5_DOMAIN, host.len() as u8]);
+                request.extend_from_slice(host.as_bytes());
+            }
+            Err(_) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Host name is too long for SOCKS5: {host}"),
+                ));
+            }
+        }
+
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request)?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply)?;
+
+        if reply[1] != 0 {
+            let (kind, reason) = match reply[1] {
+                2 => (
+                    ErrorKind::PermissionDenied,
+                    "connection not allowed by ruleset",
+                ),
+                3 => (ErrorKind::Other, "network unreachable"),
+                4 => (ErrorKind::Other, "host unreachable"),
+                5 => (ErrorKind::ConnectionRefused, "connection refused"),
+                6 => (ErrorKind::TimedOut, "TTL expired"),
+                7 => (ErrorKind::Other, "command not supported"),
+                8 => (ErrorKind::Other, "address type not supported"),
+                _ => (ErrorKind::Other, "general failure"),
+            };
+
+            return Err(io::Error::new(
+                kind,
+                format!("SOCKS5 proxy could not connect to {host}:{port}: {reason}"),
+            ));
+        }
+
+        // Skip the address the proxy bound for the connection, which is of no use here.
+        let address_len = match reply[3] {
+            SOCKS5_IPV4 => 4,
+            SOCKS5_IPV6 => 16,
+            SOCKS5_DOMAIN => {
+                let mut len = [0; 1];
+                stream.read_exact(&mut len)?;
+                len[0] as usize
+            }
+            _ => return Err(proxy_error("SOCKS5 proxy sent a malformed reply")),
+        };
+        let mut bound = vec![0; address_len + 2];
+        stream.read_exact(&mut bound)
+    }
+
+    /// Sends an HTTP `CONNECT` request, authenticating with the `Basic` scheme if credentials
+    /// were given.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection to the proxy.
+    /// * `host` - The host name or address to connect to.
+    /// * `port` - The port to connect to.
+    fn http_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let authority = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => format!("[{host}]:{port}"),
+            _ => format!("{host}:{port}"),
+        };
+        let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+
+        if let Some((username, password)) = &self.credentials {
+            request += &format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                keys::base64_encode(format!("{username}:{password}").as_bytes())
+            );
+        }
+
+        request += "\r\n";
+        stream.write_all(request.as_bytes())?;
+
+        // The response is read byte by byte, so that none of the SSH data that follows it is
+        // consumed.
+        let mut response = Vec::new();
+        let mut byte = [0; 1];
+
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_HTTP_RESPONSE_SIZE {
+                return Err(proxy_error("HTTP proxy sent oversized response headers"));
+            }
+
+            stream.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let status = match status_line.split_whitespace().collect::<Vec<_>>()[..] {
+            [version, status, ..] if version.starts_with("HTTP/") => status,
+            _ => return Err(proxy_error("Proxy server is not an HTTP proxy")),
+        };
+
+        match status {
+            _ if status.starts_with('2') => Ok(()),
+            "407" => Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                match self.credentials {
+                    Some(_) => "HTTP proxy authentication failed",
+                    None => "HTTP proxy requires authentication",
+                },
+            )),
+            _ => Err(io::Error::other(format!(
+                "HTTP proxy could not connect to {authority}: {status_line}"
+            ))),
+        }
+    }
+}
+
+/// Creates an error for a proxy server that does not follow its protocol.
+///
+/// # Arguments
+///
+/// * `message` - The error message.
+fn proxy_error(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
@@ -1,27 +1,183 @@
 //! SSH types and methods.
 
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::error::Error;
+use std::ffi::{c_char, c_int, c_uint, c_void, CString};
 use std::fs;
-use std::io::{self, ErrorKind, Read, Write};
-use std::net::{SocketAddr, TcpStream};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpStream};
+#[cfg(unix)]
+use std::os::fd::{BorrowedFd, OwnedFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use pyo3::exceptions::{
-    PyConnectionRefusedError, PyException, PyFileExistsError, PyFileNotFoundError, PyIOError,
-    PyPermissionError, PyValueError,
+    PyConnectionError, PyConnectionRefusedError, PyException, PyFileExistsError,
+    PyFileNotFoundError, PyIOError, PyPermissionError, PyTimeoutError, PyUserWarning, PyValueError,
 };
 use pyo3::prelude::*;
-use ssh2::{Channel, ErrorCode, OpenFlags, OpenType, Session, Sftp, Stream};
+use pyo3::types::{PyBytes, PyDict};
+use ssh2::{
+    Channel, DisconnectCode, ErrorCode, KeyboardInteractivePrompt, Listener, MethodType, OpenFlags,
+    OpenType, Prompt, PtyModeOpcode, PtyModes, Session, Sftp, Stream,
+};
+
+use crate::audit::AuditRecord;
+use crate::certificate::Certificate;
+use crate::jump::{self, Jump, JumpHost};
+use crate::known_hosts::{self, HostKeyCheck, KnownHosts, PublicKey};
+use crate::lock::{self, LockedState, SessionGuard, SessionLock};
+use crate::net::{self, AddressFamily};
+use crate::proxy::Proxy;
+use crate::ssh_config::SSHConfig;
+use crate::{defaults, gzip, hash, keys, totp};
+
+/// `libssh2` error code returned when a non-blocking operation would block.
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+/// `libssh2` error code returned when a blocking operation times out.
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+/// `libssh2` error code for invalid arguments or states.
+const LIBSSH2_ERROR_INVAL: i32 = -34;
+/// `libssh2` error code returned when sending data fails.
+const LIBSSH2_ERROR_SOCKET_SEND: i32 = -7;
+/// `libssh2` error code returned when the connection is closed unexpectedly.
+const LIBSSH2_ERROR_SOCKET_DISCONNECT: i32 = -13;
+/// `libssh2` error code returned when receiving data fails.
+const LIBSSH2_ERROR_SOCKET_RECV: i32 = -43;
+/// `libssh2` error code returned when a channel request fails.
+const LIBSSH2_ERROR_CHANNEL_FAILURE: i32 = -21;
+/// `libssh2` error code returned when using a closed channel.
+const LIBSSH2_ERROR_CHANNEL_CLOSED: i32 = -26;
+/// `libssh2` error code returned when writing to a channel after EOF.
+const LIBSSH2_ERROR_CHANNEL_EOF_SENT: i32 = -27;
+/// `libssh2` error code returned when the server requires the password to be changed.
+const LIBSSH2_ERROR_PASSWORD_EXPIRED: i32 = -15;
+/// `libssh2` error code returned when the server rejects an authentication attempt.
+const LIBSSH2_ERROR_AUTHENTICATION_FAILED: i32 = -18;
+/// `libssh2` error code returned when a public key cannot be verified (or signing with it fails).
+const LIBSSH2_ERROR_PUBLICKEY_UNVERIFIED: i32 = -19;
 
 /// Default SSH port.
-const DEFAULT_PORT: u16 = 22;
+pub(crate) const DEFAULT_PORT: u16 = 22;
 /// Default connection timeout.
 const DEFAULT_TIMEOUT: u32 = 30;
+/// Default delay between attempts to open a channel.
+const DEFAULT_CHANNEL_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Default delay before the first retry of a failed connection.
+const DEFAULT_CONNECT_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Default time to wait for the server to answer a liveness check (in seconds).
+const DEFAULT_PROBE_TIMEOUT: f64 = 5.0;
+/// Default delay before the first attempt to re-establish a lost session.
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Maximum length of the identification string sent to servers, without the final CRLF.
+const MAX_BANNER_LENGTH: usize = 253;
+/// Default mode for newly-created remote directories.
+const DEFAULT_DIR_MODE: i32 = 0o777;
+/// Mask of the permission bits (including the setuid, setgid and sticky bits) of a POSIX mode.
+const PERMISSION_BITS: u32 = 0o7777;
+/// Default terminal type for PTY requests.
+const DEFAULT_TERM: &str = "vt100";
+/// Default PTY width (in characters).
+const DEFAULT_PTY_WIDTH: u32 = 80;
+/// Default PTY height (in characters).
+const DEFAULT_PTY_HEIGHT: u32 = 24;
+/// Size of the chunks used when reading from channel streams.
+const CHUNK_SIZE: usize = 8192;
+/// Maximum number of paths passed to a single remote checksum command.
+const CHECKSUM_BATCH_SIZE: usize = 100;
+/// Maximum number of SFTP channels used to keep requests in flight during batch operations.
+const BATCH_CHANNELS: usize = 8;
+/// Interval between progress checks while batch operations wait on the server.
+const BATCH_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// SFTP status code for a file that does not exist.
+const LIBSSH2_FX_NO_SUCH_FILE: i32 = 2;
+/// SFTP status code for a path that does not exist.
+const LIBSSH2_FX_NO_SUCH_PATH: i32 = 10;
+/// SFTP status code returned when there is no connection to the server.
+const LIBSSH2_FX_NO_CONNECTION: i32 = 6;
+/// SFTP status code returned when the connection to the server was lost.
+const LIBSSH2_FX_CONNECTION_LOST: i32 = 7;
+/// Interval between readiness checks in [`wait`].
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Default pattern used to recognise one-time password prompts during keyboard-interactive
+/// authentication.
+const DEFAULT_TOTP_PROMPT: &str = r"(?i)(code|token|otp|one[- ]time|verification)";
+
+/// Terminal modes that can be set on PTY requests, keyed by their RFC 4254 names.
+const PTY_MODES: &[(&str, PtyModeOpcode)] = &[
+    ("VINTR", PtyModeOpcode::VINTR),
+    ("VQUIT", PtyModeOpcode::VQUIT),
+    ("VERASE", PtyModeOpcode::VERASE),
+    ("VKILL", PtyModeOpcode::VKILL),
+    ("VEOF", PtyModeOpcode::VEOF),
+    ("VEOL", PtyModeOpcode::VEOL),
+    ("VEOL2", PtyModeOpcode::VEOL2),
+    ("VSTART", PtyModeOpcode::VSTART),
+    ("VSTOP", PtyModeOpcode::VSTOP),
+    ("VSUSP", PtyModeOpcode::VSUSP),
+    ("VDSUSP", PtyModeOpcode::VDSUSP),
+    ("VREPRINT", PtyModeOpcode::VREPRINT),
+    ("VWERASE", PtyModeOpcode::VWERASE),
+    ("VLNEXT", PtyModeOpcode::VLNEXT),
+    ("VFLUSH", PtyModeOpcode::VFLUSH),
+    ("VSWTCH", PtyModeOpcode::VSWTCH),
+    ("VSTATUS", PtyModeOpcode::VSTATUS),
+    ("VDISCARD", PtyModeOpcode::VDISCARD),
+    ("IGNPAR", PtyModeOpcode::IGNPAR),
+    ("PARMRK", PtyModeOpcode::PARMRK),
+    ("INPCK", PtyModeOpcode::INPCK),
+    ("ISTRIP", PtyModeOpcode::ISTRIP),
+    ("INLCR", PtyModeOpcode::INLCR),
+    ("IGNCR", PtyModeOpcode::IGNCR),
+    ("ICRNL", PtyModeOpcode::ICRNL),
+    ("IUCLC", PtyModeOpcode::IUCLC),
+    ("IXON", PtyModeOpcode::IXON),
+    ("IXANY", PtyModeOpcode::IXANY),
+    ("IXOFF", PtyModeOpcode::IXOFF),
+    ("IMAXBEL", PtyModeOpcode::IMAXBEL),
+    ("ISIG", PtyModeOpcode::ISIG),
+    ("ICANON", PtyModeOpcode::ICANON),
+    ("XCASE", PtyModeOpcode::XCASE),
+    ("ECHO", PtyModeOpcode::ECHO),
+    ("ECHOE", PtyModeOpcode::ECHOE),
+    ("ECHOK", PtyModeOpcode::ECHOK),
+    ("ECHONL", PtyModeOpcode::ECHONL),
+    ("NOFLSH", PtyModeOpcode::NOFLSH),
+    ("TOSTOP", PtyModeOpcode::TOSTOP),
+    ("IEXTEN", PtyModeOpcode::IEXTEN),
+    ("ECHOCTL", PtyModeOpcode::ECHOCTL),
+    ("ECHOKE", PtyModeOpcode::ECHOKE),
+    ("PENDIN", PtyModeOpcode::PENDIN),
+    ("OPOST", PtyModeOpcode::OPOST),
+    ("OLCUC", PtyModeOpcode::OLCUC),
+    ("ONLCR", PtyModeOpcode::ONLCR),
+    ("OCRNL", PtyModeOpcode::OCRNL),
+    ("ONOCR", PtyModeOpcode::ONOCR),
+    ("ONLRET", PtyModeOpcode::ONLRET),
+    ("CS7", PtyModeOpcode::CS7),
+    ("CS8", PtyModeOpcode::CS8),
+    ("PARENB", PtyModeOpcode::PARENB),
+    ("PARODD", PtyModeOpcode::PARODD),
+    ("TTY_OP_ISPEED", PtyModeOpcode::TTY_OP_ISPEED),
+    ("TTY_OP_OSPEED", PtyModeOpcode::TTY_OP_OSPEED),
+];
 
 // Custom Python exception types.
 pyo3::create_exception!(russh, SessionException, PyException);
 pyo3::create_exception!(russh, SFTPException, PyException);
+pyo3::create_exception!(russh, ChannelException, SessionException);
+pyo3::create_exception!(russh, PassphraseRequiredException, SessionException);
+pyo3::create_exception!(russh, PasswordExpiredException, SessionException);
+pyo3::create_exception!(russh, AuthenticationException, SessionException);
+pyo3::create_exception!(russh, BadHostKeyException, SessionException);
+pyo3::create_exception!(russh, NoValidConnectionsError, PyIOError);
 
 /// Convenience function to map Rust errors to appropriate Python exceptions.
 ///
@@ -30,7 +186,7 @@ pyo3::create_exception!(russh, SFTPException, PyException);
 /// # Arguments
 ///
 /// * `err` - The error to convert.
-fn excp_from_err<E>(err: E) -> PyErr
+pub(crate) fn excp_from_err<E>(err: E) -> PyErr
 where
     E: Error + Send + Sync + 'static,
 {
@@ -38,6 +194,9 @@ where
 
     if let Some(ssh_err) = err.downcast_ref::<ssh2::Error>() {
         return match ssh_err.code() {
+            ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT) => {
+                PyErr::new::<PyTimeoutError, _>(ssh_err.to_string())
+            }
             ErrorCode::Session(_) => SessionException::new_err(ssh_err.to_string()),
             ErrorCode::SFTP(_) => SFTPException::new_err(ssh_err.to_string()),
         };
@@ -51,6 +210,7 @@ where
             ErrorKind::ConnectionRefused => {
                 PyErr::new::<PyConnectionRefusedError, _>(io_err.to_string())
             }
+            ErrorKind::TimedOut => PyErr::new::<PyTimeoutError, _>(io_err.to_string()),
             _ => PyErr::new::<PyIOError, _>(io_err.to_string()),
         };
     }
@@ -58,21 +218,274 @@ where
     PyErr::new::<PyException, _>(err.to_string())
 }
 
+/// Converts a duration given from Python (in seconds) to a [`Duration`].
+///
+/// Fails with a `ValueError` if the duration is negative, NaN or too large.
+///
+/// # Arguments
+///
+/// * `name` - What the duration is, for the error message (e.g. `"timeout"`).
+/// * `seconds` - The duration (in seconds).
+pub(crate) fn duration_from_secs(name: &str, seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| PyValueError::new_err(format!("Invalid {name}: {seconds}")))
+}
+
 #[pyclass]
 #[derive(Clone)]
 /// Represents password-based authentication.
-pub struct PasswordAuth(pub String);
+pub struct PasswordAuth {
+    /// The password.
+    pub password: Option<String>,
+    /// Callback that provides the password on demand, each time it is needed.
+    pub provider: Option<PyObject>,
+    /// The new password to set if the server reports that the password has expired.
+    pub new_password: Option<String>,
+    /// Callback that provides the new password on demand when the password has expired.
+    pub new_password_callback: Option<PyObject>,
+}
+
+impl PasswordAuth {
+    /// Attempts to authenticate with the password, changing it if the server reports that it has
+    /// expired.
+    ///
+    /// If the password has expired and no new password is available (from `new_password`, or
+    /// else from the callback), a [`PasswordExpiredException`] is raised.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `sess` - The session to authenticate.
+    /// * `username` - The SSH username.
+    fn authenticate(&self, py: Python<'_>, sess: &Session, username: &str) -> PyResult<()> {
+        let password = match (&self.password, &self.provider) {
+            (Some(password), _) => password.clone(),
+            (None, Some(provider)) => provider.call0(py)?.extract::<String>(py)?,
+            (None, None) => String::new(),
+        };
+        let result = userauth_password(sess, username, &password, self.new_password.as_deref());
+
+        match result {
+            Err(err) if err.code() == ErrorCode::Session(LIBSSH2_ERROR_PASSWORD_EXPIRED) => {
+                let new_password = match (&self.new_password, &self.new_password_callback) {
+                    (None, Some(callback)) => callback
+                        .call1(py, (username,))?
+                        .extract::<Option<String>>(py)?,
+                    _ => None,
+                };
+
+                let Some(new_password) = new_password else {
+                    return Err(PasswordExpiredException::new_err(format!(
+                        "Password of user {username} has expired and no new password was provided"
+                    )));
+                };
+
+                userauth_password(sess, username, &password, Some(&new_password))
+                    .map_err(excp_from_err)
+            }
+            result => result.map_err(excp_from_err),
+        }
+    }
+}
 
 #[pymethods]
 impl PasswordAuth {
     #[new]
+    #[pyo3(signature = (password=None, new_password=None, new_password_callback=None, provider=None))]
     /// Creates a new [`PasswordAuth`].
     ///
+    /// Fails if neither a password nor a provider is given.
+    ///
     /// # Arguments
     ///
     /// * `password` - The password.
-    pub fn __new__(password: String) -> Self {
-        Self(password)
+    /// * `new_password` - The new password to set if the server reports that the password has
+    ///   expired.
+    /// * `new_password_callback` - Callable invoked with the username when the password has expired
+    ///   and no new password was provided. It should return the new password, or `None`.
+    /// * `provider` - Callable invoked without arguments each time the password is needed (e.g.
+    ///   to fetch it from a secret manager), so that it is not kept in memory. Only used if no
+    ///   password is given.
+    pub fn __new__(
+        password: Option<String>,
+        new_password: Option<String>,
+        new_password_callback: Option<PyObject>,
+        provider: Option<PyObject>,
+    ) -> PyResult<Self> {
+        if password.is_none() && provider.is_none() {
+            return Err(PyValueError::new_err(
+                "Either a password or a provider is required".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            password,
+            provider,
+            new_password,
+            new_password_callback,
+        })
+    }
+}
+
+thread_local! {
+    /// The new password handed to `libssh2` by [`supply_new_password`] during a password change.
+    static NEW_PASSWORD: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Password change callback for `libssh2`, which supplies the password stored in
+/// [`NEW_PASSWORD`].
+///
+/// The password is copied into a buffer allocated with `malloc`, which `libssh2` frees.
+extern "C" fn supply_new_password(
+    _sess: *mut libssh2_sys::LIBSSH2_SESSION,
+    newpw: *mut *mut c_char,
+    newpw_len: *mut c_int,
+    _abstract: *mut *mut c_void,
+) {
+    NEW_PASSWORD.with(|password| {
+        let password = password.borrow();
+        let Some(password) = password.as_ref() else {
+            return;
+        };
+
+        unsafe {
+            let buf = libc::malloc(password.len().max(1)) as *mut c_char;
+
+            if buf.is_null() {
+                return;
+            }
+
+            std::ptr::copy_nonoverlapping(password.as_ptr() as *const c_char, buf, password.len());
+            *newpw = buf;
+            *newpw_len = password.len() as c_int;
+        }
+    });
+}
+
+/// Attempts password authentication, changing the password if the server requests it and a new
+/// password is given.
+///
+/// Without a new password, an expired password fails with `LIBSSH2_ERROR_PASSWORD_EXPIRED`.
+///
+/// # Arguments
+///
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `password` - The password.
+/// * `new_password` - Optional new password to set if the password has expired.
+fn userauth_password(
+    sess: &Session,
+    username: &str,
+    password: &str,
+    new_password: Option<&str>,
+) -> Result<(), ssh2::Error> {
+    let Some(new_password) = new_password else {
+        return sess.userauth_password(username, password);
+    };
+
+    NEW_PASSWORD.with(|cell| *cell.borrow_mut() = Some(new_password.to_string()));
+
+    let mut raw = sess.raw();
+    let raw: *mut libssh2_sys::LIBSSH2_SESSION = &mut *raw;
+    let rc = unsafe {
+        libssh2_sys::libssh2_userauth_password_ex(
+            raw,
+            username.as_ptr() as *const c_char,
+            username.len() as c_uint,
+            password.as_ptr() as *const c_char,
+            password.len() as c_uint,
+            Some(supply_new_password),
+        )
+    };
+    let result = match rc {
+        0 => Ok(()),
+        rc => Err(ssh2::Error::from_session_error_raw(raw, rc)),
+    };
+
+    NEW_PASSWORD.with(|cell| cell.borrow_mut().take());
+
+    result
+}
+
+extern "C" {
+    /// Authenticates with a public key, delegating the signature to a callback (not bound by
+    /// `libssh2-sys`).
+    fn libssh2_userauth_publickey(
+        session: *mut libssh2_sys::LIBSSH2_SESSION,
+        username: *const c_char,
+        pubkeydata: *const u8,
+        pubkeydata_len: libc::size_t,
+        sign_callback: Option<
+            extern "C" fn(
+                *mut libssh2_sys::LIBSSH2_SESSION,
+                *mut *mut u8,
+                *mut libc::size_t,
+                *const u8,
+                libc::size_t,
+                *mut *mut c_void,
+            ) -> c_int,
+        >,
+        abstract_: *mut *mut c_void,
+    ) -> c_int;
+}
+
+thread_local! {
+    /// Whether the server accepted the public key queried by [`query_public_key`].
+    static KEY_ACCEPTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Signing callback for `libssh2`, which is only invoked once the server has accepted the public
+/// key. It records that in [`KEY_ACCEPTED`] and refuses to sign, aborting the authentication.
+extern "C" fn refuse_to_sign(
+    _sess: *mut libssh2_sys::LIBSSH2_SESSION,
+    _sig: *mut *mut u8,
+    _sig_len: *mut libc::size_t,
+    _data: *const u8,
+    _data_len: libc::size_t,
+    _abstract: *mut *mut c_void,
+) -> c_int {
+    KEY_ACCEPTED.with(|accepted| accepted.set(true));
+
+    LIBSSH2_ERROR_INVAL
+}
+
+/// Asks the server whether it would accept a public key for a user, without signing anything
+/// (a `publickey` request without a signature, as described in RFC 4252).
+///
+/// # Arguments
+///
+/// * `sess` - The unauthenticated session.
+/// * `username` - The SSH username.
+/// * `blob` - The public key, in SSH wire format.
+fn query_public_key(sess: &Session, username: &str, blob: &[u8]) -> Result<bool, ssh2::Error> {
+    let username = CString::new(username).map_err(|_| {
+        ssh2::Error::new(
+            ErrorCode::Session(LIBSSH2_ERROR_INVAL),
+            "username contains a NUL byte",
+        )
+    })?;
+
+    KEY_ACCEPTED.with(|accepted| accepted.set(false));
+
+    let mut raw = sess.raw();
+    let raw: *mut libssh2_sys::LIBSSH2_SESSION = &mut *raw;
+    let rc = unsafe {
+        libssh2_userauth_publickey(
+            raw,
+            username.as_ptr(),
+            blob.as_ptr(),
+            blob.len(),
+            Some(refuse_to_sign),
+            std::ptr::null_mut(),
+        )
+    };
+
+    match rc {
+        // Some servers accept the key without even asking for a signature.
+        0 => Ok(true),
+        _ if KEY_ACCEPTED.with(Cell::get) => Ok(true),
+        LIBSSH2_ERROR_AUTHENTICATION_FAILED => Ok(false),
+        rc => Err(ssh2::Error::from_session_error_raw(raw, rc)),
     }
 }
 
@@ -84,6 +497,53 @@ pub struct PrivateKeyAuth {
     pub private_key: String,
     /// The passphrase for the private-key file.
     pub passphrase: Option<String>,
+    /// Callback that provides the passphrase for an encrypted private-key file on demand.
+    pub passphrase_callback: Option<PyObject>,
+    /// The path to the OpenSSH certificate signed for the key.
+    pub cert_file: Option<String>,
+}
+
+impl PrivateKeyAuth {
+    /// Determines the passphrase to use for the private-key file.
+    ///
+    /// If no passphrase was provided and the key is encrypted, the passphrase callback is invoked
+    /// with the path to the key. If there is no callback, or it returns `None`, a
+    /// [`PassphraseRequiredException`] is raised.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn resolve_passphrase(&self, py: Python<'_>) -> PyResult<Option<String>> {
+        if self.passphrase.is_some() {
+            return Ok(self.passphrase.clone());
+        }
+
+        let encrypted = fs::read_to_string(&self.private_key)
+            .map(|key| keys::is_encrypted(&key))
+            .unwrap_or(false);
+
+        if !encrypted {
+            return Ok(None);
+        }
+
+        if let Some(callback) = &self.passphrase_callback {
+            let passphrase: Option<String> = callback
+                .call1(py, (self.private_key.clone(),))?
+                .extract(py)?;
+
+            if passphrase.is_some() {
+                return Ok(passphrase);
+            }
+        }
+
+        Err(PassphraseRequiredException::new_err((
+            format!(
+                "Private key {} is encrypted and no passphrase was provided",
+                self.private_key
+            ),
+            self.private_key.clone(),
+        )))
+    }
 }
 
 #[pymethods]
@@ -93,507 +553,7282 @@ impl PrivateKeyAuth {
     ///
     /// # Arguments
     ///
-    /// * `private_key` - The path to the private-key file.
+    /// * `private_key` - The path to the private-key file, in PEM, OpenSSH or PuTTY (PPK) format.
     /// * `passphrase` - The password for the private-key file.
-    pub fn __new__(private_key: String, passphrase: Option<String>) -> Self {
+    /// * `passphrase_callback` - Callable invoked with the path to the private-key file when the key
+    ///   is encrypted and no passphrase was provided. It should return the passphrase, or `None`.
+    /// * `cert_file` - The path to the OpenSSH certificate signed for the key. Defaults to the
+    ///   key's path with `-cert.pub` appended, if that file exists.
+    pub fn __new__(
+        private_key: String,
+        passphrase: Option<String>,
+        passphrase_callback: Option<PyObject>,
+        cert_file: Option<String>,
+    ) -> Self {
         Self {
             private_key,
             passphrase,
+            passphrase_callback,
+            cert_file,
         }
     }
 }
 
-#[pyclass]
-#[derive(Clone)]
-/// Represents supported authentication methods.
-pub struct AuthMethods {
-    /// Password-based authentication method.
-    pub password: Option<PasswordAuth>,
-    /// Private-key-based authentication method.
-    pub private_key: Option<PrivateKeyAuth>,
-}
+#[pyfunction]
+#[pyo3(signature = (ppk_path, openssh_path=None, passphrase=None))]
+/// Converts a PuTTY private key (PPK format version 2 or 3) into the OpenSSH format and returns
+/// it, unencrypted.
+///
+/// Keys encrypted with a passphrase are supported in version 2 of the format. If the key is
+/// encrypted and no passphrase is given, a [`PassphraseRequiredException`] is raised.
+///
+/// **NOTE**: [`PrivateKeyAuth`] converts PPK files automatically; this is only needed to keep a
+/// converted copy of the key.
+///
+/// # Arguments
+///
+/// * `ppk_path` - The path to the PPK file.
+/// * `openssh_path` - Optional path to write the converted key to (with `0600` permissions on
+///   UNIX).
+/// * `passphrase` - The passphrase the PPK file is encrypted with.
+pub fn convert_ppk(
+    ppk_path: String,
+    openssh_path: Option<String>,
+    passphrase: Option<String>,
+) -> PyResult<String> {
+    let contents = fs::read_to_string(&ppk_path).map_err(excp_from_err)?;
 
-#[pymethods]
-impl AuthMethods {
-    #[new]
-    /// Creates a new [`AuthMethods`].
-    ///
-    /// * `password` - Password-based authentication method.
-    /// * `private_key` - Private-key-based authentication method.
-    pub fn __new__(password: Option<PasswordAuth>, private_key: Option<PrivateKeyAuth>) -> Self {
-        Self {
-            password,
-            private_key,
-        }
+    if passphrase.is_none() && keys::is_encrypted(&contents) {
+        return Err(PassphraseRequiredException::new_err((
+            format!("Private key {ppk_path} is encrypted and no passphrase was provided"),
+            ppk_path,
+        )));
     }
-}
 
-#[pyclass]
-/// Represents the output produced when running [`SSHClient::exec_command`].
-pub struct ExecOutput {
-    channel: Option<Channel>,
-    /// The `stdin` stream.
-    stdin: Option<Stream>,
-    /// The `stdout` stream's contents.
-    stdout: Option<Stream>,
-    /// The `stderr` stream's contents.
-    stderr: Option<Stream>,
-}
+    let converted =
+        keys::ppk_to_openssh(&contents, passphrase.as_deref()).map_err(PyValueError::new_err)?;
 
-#[pymethods]
-impl ExecOutput {
-    /// Writes the provided data to the `stdin` stream and closes it.
-    ///
-    /// **NOTE**: Future calls will discard the provided data without doing anything.
-    ///
-    /// # Arguments
-    ///
-    /// * `data` - The data to write to the stream.
-    pub fn write_stdin(&mut self, data: String) -> PyResult<()> {
-        if let Some(mut stdin) = self.stdin.take() {
-            if let Some(channel) = self.channel.as_mut() {
-                stdin.write_all(data.as_bytes()).map_err(excp_from_err)?;
-                stdin.flush().map_err(excp_from_err)?;
+    if let Some(openssh_path) = openssh_path {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
 
-                channel.send_eof().map_err(excp_from_err)?;
-            }
-        }
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
 
-        Ok(())
+        options
+            .open(&openssh_path)
+            .and_then(|mut file| file.write_all(converted.as_bytes()))
+            .map_err(excp_from_err)?;
     }
 
-    /// Reads the contents of the `stdout` stream and consumes it.
-    ///
-    /// **NOTE**: Future calls will return an empty string.
-    fn read_stdout(&mut self) -> PyResult<String> {
-        let mut buf = String::new();
+    Ok(converted)
+}
+
+#[pyfunction]
+/// Returns the types of keys (e.g. `ssh-ed25519`, `ecdsa-sha2-nistp256` or `ssh-rsa`) that the
+/// `libssh2` build in use supports, as the supported algorithms depend on how it was compiled.
+pub fn supported_key_types() -> PyResult<Vec<String>> {
+    let sess = Session::new().map_err(excp_from_err)?;
+    let mut key_types: Vec<String> = Vec::new();
+
+    for alg in sess
+        .supported_algs(MethodType::HostKey)
+        .map_err(excp_from_err)?
+    {
+        // Certificates use the same keys, and RSA keys can sign with several hash algorithms.
+        let key_type = match alg {
+            alg if alg.contains("-cert-") => continue,
+            "rsa-sha2-256" | "rsa-sha2-512" => "ssh-rsa",
+            alg => alg,
+        };
 
-        if let Some(mut stdout) = self.stdout.take() {
-            stdout.read_to_string(&mut buf).map_err(excp_from_err)?;
+        if !key_types.iter().any(|known| known == key_type) {
+            key_types.push(key_type.to_string());
         }
+    }
 
-        Ok(buf)
+    Ok(key_types)
+}
+
+/// Checks that a private key can be used for authentication, failing with an actionable error if
+/// it cannot: e.g. if it is actually a public key, or if `libssh2` was built without support for
+/// its type.
+///
+/// Keys whose type cannot be detected are let through, for `libssh2` to decide.
+///
+/// # Arguments
+///
+/// * `name` - The name of the key (e.g. its path), for error messages.
+/// * `key` - The contents of the private key.
+fn check_key_type(name: &str, key: &str) -> PyResult<()> {
+    if keys::public_key_blob(key).is_some() {
+        return Err(SessionException::new_err(format!(
+            "{name} is a public key; use the corresponding private key instead"
+        )));
     }
 
-    /// Reads the contents of the `stderr` stream and consumes it.
-    ///
-    /// **NOTE**: Future calls will return an empty string.
-    fn read_stderr(&mut self) -> PyResult<String> {
-        let mut buf = String::new();
+    let Some(key_type) = keys::key_type(key) else {
+        return Ok(());
+    };
 
-        if let Some(mut stderr) = self.stderr.take() {
-            stderr.read_to_string(&mut buf).map_err(excp_from_err)?;
-        }
+    if key_type.starts_with("sk-") {
+        return Err(SessionException::new_err(format!(
+            "{name} is a security-key ({key_type}) key, which can only be used through the SSH agent"
+        )));
+    }
 
-        Ok(buf)
+    let supported = supported_key_types()?;
+    let is_supported = match key_type.as_str() {
+        "ecdsa" => supported.iter().any(|known| known.starts_with("ecdsa-")),
+        key_type => supported.iter().any(|known| known == key_type),
+    };
+
+    if is_supported {
+        return Ok(());
     }
 
-    /// Retrieves the exit status of the command and closes the channel and all streams.
-    ///
-    /// **NOTE**: Future calls will return 0.
-    ///
-    /// **NOTE**: Future reads of the `stdout` or `stderr` streams will return empty strings.
-    fn exit_status(&mut self) -> PyResult<i32> {
-        let mut exit_status = 0;
+    Err(SessionException::new_err(format!(
+        "libssh2 was built without support for {key_type} keys (used by {name}); supported key types: {}",
+        supported.join(", ")
+    )))
+}
 
-        if let Some(mut chan) = self.channel.take() {
-            let mut stdout = String::new();
-            let mut stderr = String::new();
+#[derive(FromPyObject)]
+/// Private-key material: either text or raw bytes.
+pub enum KeyData {
+    /// The key as text.
+    #[pyo3(annotation = "str")]
+    Text(String),
+    /// The key as UTF-8 encoded bytes.
+    #[pyo3(annotation = "bytes")]
+    Bytes(Vec<u8>),
+}
 
-            chan.read_to_string(&mut stdout).map_err(excp_from_err)?;
-            chan.stderr()
-                .read_to_string(&mut stderr)
-                .map_err(excp_from_err)?;
+#[pyclass]
+#[derive(Clone)]
+/// Represents private-key-based authentication with key material held in memory, e.g. when it
+/// comes from a secrets vault or an environment variable.
+pub struct PrivateKeyMemoryAuth {
+    /// The private key, in PEM or OpenSSH format.
+    pub private_key: String,
+    /// The passphrase for the private key.
+    pub passphrase: Option<String>,
+}
 
-            chan.wait_close().map_err(excp_from_err)?;
-            exit_status = chan.exit_status().map_err(excp_from_err)?;
-        }
+#[pymethods]
+impl PrivateKeyMemoryAuth {
+    #[new]
+    /// Creates a new [`PrivateKeyMemoryAuth`].
+    ///
+    /// # Arguments
+    ///
+    /// * `private_key` - The private key, in PEM or OpenSSH format.
+    /// * `passphrase` - The passphrase for the private key.
+    pub fn __new__(private_key: KeyData, passphrase: Option<String>) -> PyResult<Self> {
+        let private_key = match private_key {
+            KeyData::Text(key) => key,
+            KeyData::Bytes(key) => String::from_utf8(key)
+                .map_err(|_| PyValueError::new_err("Private key is not valid UTF-8".to_string()))?,
+        };
 
-        Ok(exit_status)
+        Ok(Self {
+            private_key,
+            passphrase,
+        })
     }
+}
 
-    /// Consumes all streams and closes the underlying channel if it exists and is active.
-    ///
-    /// If there is no active channel, then this function does nothing.
-    fn close(&mut self) -> PyResult<()> {
-        self.stdin.take();
-        self.stdout.take();
-        self.stderr.take();
+/// Attempts to authenticate with a private key held in memory.
+///
+/// # Arguments
+///
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `auth` - The in-memory private key.
+#[cfg(unix)]
+fn userauth_pubkey_memory(
+    sess: &Session,
+    username: &str,
+    auth: &PrivateKeyMemoryAuth,
+) -> Result<(), ssh2::Error> {
+    sess.userauth_pubkey_memory(
+        username,
+        None,
+        &auth.private_key,
+        auth.passphrase.as_deref(),
+    )
+}
 
-        if let Some(mut channel) = self.channel.take() {
-            channel.close().map_err(excp_from_err)?;
-        }
+/// Attempts to authenticate with a private key held in memory.
+///
+/// # Arguments
+///
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `auth` - The in-memory private key.
+#[cfg(not(unix))]
+fn userauth_pubkey_memory(
+    _sess: &Session,
+    _username: &str,
+    _auth: &PrivateKeyMemoryAuth,
+) -> Result<(), ssh2::Error> {
+    Err(ssh2::Error::new(
+        ErrorCode::Session(LIBSSH2_ERROR_INVAL),
+        "in-memory private keys are not supported on this platform",
+    ))
+}
 
-        Ok(())
-    }
+/// Attempts to authenticate with a private key converted from another format (e.g. PPK), held in
+/// memory as an unencrypted OpenSSH-format key.
+///
+/// # Arguments
+///
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `private_key` - The converted private key.
+/// * `cert` - The path to the OpenSSH certificate signed for the key.
+#[cfg(unix)]
+fn userauth_pubkey_converted(
+    sess: &Session,
+    username: &str,
+    private_key: &str,
+    cert: Option<&Path>,
+) -> Result<(), ssh2::Error> {
+    let cert = cert.and_then(|cert| fs::read_to_string(cert).ok());
+
+    sess.userauth_pubkey_memory(username, cert.as_deref(), private_key, None)
 }
 
-/// Convenience function that concatenates a base and a child path into a [`PathBuf`].
+/// Attempts to authenticate with a private key converted from another format (e.g. PPK), held in
+/// memory as an unencrypted OpenSSH-format key.
 ///
-/// If the base is `None`, the child path is returned as a [`PathBuf`].
+/// libssh2 only supports in-memory keys on UNIX, so the key is written to a temporary file that is
+/// removed once authentication completes.
 ///
 /// # Arguments
 ///
-/// * `base` - Optional base path.
-/// * `path` - The child path.
-fn path_from_string(base: Option<String>, path: String) -> PathBuf {
-    if let Some(base) = base {
-        let mut base_path = PathBuf::new();
-        base_path.push(base);
-        base_path.push(path);
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `private_key` - The converted private key.
+/// * `cert` - The path to the OpenSSH certificate signed for the key.
+#[cfg(not(unix))]
+fn userauth_pubkey_converted(
+    sess: &Session,
+    username: &str,
+    private_key: &str,
+    cert: Option<&Path>,
+) -> Result<(), ssh2::Error> {
+    let path = std::env::temp_dir().join(format!("russh-key-{}", std::process::id()));
+    fs::write(&path, private_key).map_err(|_| {
+        ssh2::Error::new(
+            ErrorCode::Session(LIBSSH2_ERROR_INVAL),
+            "failed to write the converted private key",
+        )
+    })?;
 
-        return base_path;
-    }
+    let result = sess.userauth_pubkey_file(username, cert, &path, None);
+    let _ = fs::remove_file(&path);
 
-    Path::new(&path).to_path_buf()
+    result
 }
 
 #[pyclass]
-/// A file on a remote server.
-pub struct File(pub ssh2::File);
+#[derive(Clone)]
+/// Represents SSH-agent-based authentication.
+///
+/// Security-key (FIDO2) identities, such as `sk-ssh-ed25519@openssh.com` keys, are supported: the
+/// agent performs the signature, which may require touching the key.
+///
+/// Every identity held by the agent is tried in turn, unless a specific identity is selected. On
+/// Windows, both Pageant and the built-in OpenSSH agent (`\\.\pipe\openssh-ssh-agent`) are
+/// supported; elsewhere, the agent is located through the `SSH_AUTH_SOCK` environment variable,
+/// unless an explicit socket path is given.
+pub struct AgentAuth {
+    /// The comment or fingerprint of the only identity to try.
+    pub identity: Option<String>,
+    /// The path to the agent's UNIX socket or Windows named pipe.
+    pub socket_path: Option<String>,
+}
 
 #[pymethods]
-impl File {
-    /// Reads and returns the contents of the file.
-    pub fn read(&mut self) -> PyResult<String> {
-        let mut buf = String::new();
-        self.0.read_to_string(&mut buf).map_err(excp_from_err)?;
-
-        Ok(buf)
-    }
-
-    /// Writes the specified data to the file.
+impl AgentAuth {
+    #[new]
+    /// Creates a new [`AgentAuth`].
     ///
     /// # Arguments
     ///
-    /// * `data` - The data to write to the file.
-    pub fn write(&mut self, data: String) -> PyResult<()> {
-        self.0.write_all(data.as_bytes()).map_err(excp_from_err)?;
-        self.0.flush().map_err(excp_from_err)
+    /// * `identity` - The comment (e.g. `deploy@ci`) or SHA-256 fingerprint (e.g. `SHA256:...`, as
+    ///   shown by `ssh-add -l`) of the only identity to try. Defaults to trying every identity,
+    ///   which may exceed the server's `MaxAuthTries` if the agent holds many keys.
+    /// * `socket_path` - The path to the agent's UNIX socket, or its named pipe on Windows (e.g.
+    ///   `\\.\pipe\openssh-ssh-agent`). Defaults to the `SSH_AUTH_SOCK` environment variable, or
+    ///   the default agents on Windows.
+    pub fn __new__(identity: Option<String>, socket_path: Option<String>) -> Self {
+        Self {
+            identity,
+            socket_path,
+        }
     }
 }
-#[pyclass]
-/// The SFTP client.
-pub struct SFTPClient {
-    /// Underlying SFTP client.
-    client: Option<Sftp>,
-    /// Current working directory.
-    cwd: Option<String>,
-}
 
-#[pymethods]
-impl SFTPClient {
-    /// Changes the current working directory to the specified directory.
+/// Attempts to authenticate with each identity held by the SSH agent in turn.
+///
+/// # Arguments
+///
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `auth` - The agent to use and the identity to select.
+fn userauth_agent(sess: &Session, username: &str, auth: &AgentAuth) -> PyResult<()> {
+    let selected = auth.identity.as_deref();
+    let mut agent = sess.agent().map_err(excp_from_err)?;
+
+    if let Some(socket_path) = &auth.socket_path {
+        agent
+            .set_identity_path(Path::new(socket_path))
+            .map_err(excp_from_err)?;
+    }
+
+    agent.connect().map_err(excp_from_err)?;
+    agent.list_identities().map_err(excp_from_err)?;
+
+    let mut last_error = None;
+
+    for identity in agent.identities().map_err(excp_from_err)? {
+        if let Some(selected) = selected {
+            let fingerprint = keys::fingerprint(identity.blob());
+
+            if identity.comment() != selected
+                && fingerprint != selected
+                && fingerprint.strip_prefix("SHA256:") != Some(selected)
+            {
+                continue;
+            }
+        }
+
+        match agent.userauth(username, &identity) {
+            Ok(()) => {
+                let _ = agent.disconnect();
+                return Ok(());
+            }
+            // The agent refused to sign. For security keys (FIDO2), this usually means that the
+            // user did not confirm the signature by touching the key in time.
+            Err(err)
+                if err.code() == ErrorCode::Session(LIBSSH2_ERROR_PUBLICKEY_UNVERIFIED)
+                    && err.message() == "Callback returned error"
+                    && keys::public_key_type(identity.blob())
+                        .is_some_and(|key_type| key_type.starts_with("sk-")) =>
+            {
+                last_error = Some(SessionException::new_err(format!(
+                    "Security key {} did not sign the authentication request; the touch \
+                     confirmation may have timed out or been denied",
+                    identity.comment()
+                )));
+            }
+            Err(err) => last_error = Some(excp_from_err(err)),
+        }
+    }
+
+    let _ = agent.disconnect();
+
+    Err(last_error.unwrap_or_else(|| {
+        SessionException::new_err(match selected {
+            Some(_) => "no matching identity found in the SSH agent",
+            None => "no identities found in the SSH agent",
+        })
+    }))
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// Represents host-based authentication, where the client host's key vouches for the user.
+pub struct HostBasedAuth {
+    /// The path to the client host's public-key file.
+    pub public_key: String,
+    /// The path to the client host's private-key file.
+    pub private_key: String,
+    /// The fully-qualified name of the client host, as known to the server.
+    pub hostname: String,
+    /// The passphrase for the private-key file.
+    pub passphrase: Option<String>,
+    /// The name of the user on the client host.
+    pub local_username: Option<String>,
+}
+
+#[pymethods]
+impl HostBasedAuth {
+    #[new]
+    /// Creates a new [`HostBasedAuth`].
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - The path to the client host's public-key file.
+    /// * `private_key` - The path to the client host's private-key file.
+    /// * `hostname` - The fully-qualified name of the client host, as known to the server.
+    /// * `passphrase` - The passphrase for the private-key file.
+    /// * `local_username` - The name of the user on the client host. Defaults to the SSH username.
+    pub fn __new__(
+        public_key: String,
+        private_key: String,
+        hostname: String,
+        passphrase: Option<String>,
+        local_username: Option<String>,
+    ) -> Self {
+        Self {
+            public_key,
+            private_key,
+            hostname,
+            passphrase,
+            local_username,
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// Represents keyboard-interactive authentication with automatically generated responses.
+///
+/// Each prompt sent by the server is answered with the response of the first pattern that matches
+/// it. Prompts that match no pattern, but match the one-time password pattern, are answered with
+/// a TOTP code if a TOTP secret is configured. All other prompts are answered with an empty string.
+///
+/// If a handler is given, it answers all the prompts instead.
+pub struct KeyboardInteractiveAuth {
+    /// Responses to prompts, keyed by regular expressions that are searched for in the prompt.
+    pub responses: Vec<(String, String)>,
+    /// The base32-encoded TOTP secret.
+    pub totp_secret: Option<String>,
+    /// Regular expression used to recognise one-time password prompts.
+    pub totp_prompt: String,
+    /// Callable that answers the prompts sent by the server.
+    pub handler: Option<PyObject>,
+    /// Callable that generates one-time passwords, e.g. from a hardware token or another library.
+    pub totp_generator: Option<PyObject>,
+}
+
+impl KeyboardInteractiveAuth {
+    /// Determines the response to a single prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, used to evaluate the regular expressions.
+    /// * `prompt` - The prompt text.
+    fn respond(&self, py: Python<'_>, prompt: &str) -> PyResult<String> {
+        let re = py.import("re")?;
+        let matches = |pattern: &str| -> PyResult<bool> {
+            Ok(!re.call_method1("search", (pattern, prompt))?.is_none())
+        };
+
+        for (pattern, response) in &self.responses {
+            if matches(pattern)? {
+                return Ok(response.clone());
+            }
+        }
+
+        if let Some(secret) = &self.totp_secret {
+            if matches(&self.totp_prompt)? {
+                return totp::totp(secret, totp::DEFAULT_STEP, totp::DEFAULT_DIGITS)
+                    .ok_or_else(|| PyValueError::new_err("invalid TOTP secret".to_string()));
+            }
+        }
+
+        if let Some(generator) = &self.totp_generator {
+            if matches(&self.totp_prompt)? {
+                return generator.call0(py)?.extract(py);
+            }
+        }
+
+        Ok(String::new())
+    }
+
+    /// Asks the handler to answer a round of prompts.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `handler` - The handler.
+    /// * `instructions` - The instructions sent by the server along with the prompts.
+    /// * `prompts` - The prompts.
+    fn ask_handler(
+        py: Python<'_>,
+        handler: &PyObject,
+        instructions: &str,
+        prompts: &[Prompt<'_>],
+    ) -> PyResult<Vec<String>> {
+        let prompts: Vec<(&str, bool)> = prompts
+            .iter()
+            .map(|prompt| (prompt.text.as_ref(), prompt.echo))
+            .collect();
+        let expected = prompts.len();
+        let responses: Vec<String> = handler.call1(py, (instructions, prompts))?.extract(py)?;
+
+        if responses.len() != expected {
+            return Err(PyValueError::new_err(format!(
+                "Expected {} responses from keyboard-interactive handler, got {}",
+                expected,
+                responses.len()
+            )));
+        }
+
+        Ok(responses)
+    }
+}
+
+#[pymethods]
+impl KeyboardInteractiveAuth {
+    #[new]
+    /// Creates a new [`KeyboardInteractiveAuth`].
+    ///
+    /// # Arguments
+    ///
+    /// * `responses` - Responses to prompts, keyed by regular expressions that are searched for in
+    ///   the prompt. Patterns are tried in order.
+    /// * `totp_secret` - The base32-encoded TOTP secret used to answer one-time password prompts.
+    /// * `totp_prompt` - Regular expression used to recognise one-time password prompts.
+    /// * `handler` - Callable that answers the prompts instead. It is called with the instructions
+    ///   sent by the server and a list of `(prompt, echo)` tuples (where `echo` indicates whether
+    ///   the response may be displayed), and must return a list with one response per prompt.
+    /// * `totp_generator` - Callable invoked without arguments to generate the one-time password
+    ///   when no TOTP secret is given (e.g. to read it from a hardware token).
+    pub fn __new__(
+        py: Python<'_>,
+        responses: Option<&PyDict>,
+        totp_secret: Option<String>,
+        totp_prompt: Option<String>,
+        handler: Option<PyObject>,
+        totp_generator: Option<PyObject>,
+    ) -> PyResult<Self> {
+        let responses = match responses {
+            Some(responses) => responses
+                .items()
+                .iter()
+                .map(|item| item.extract())
+                .collect::<PyResult<_>>()?,
+            None => Vec::new(),
+        };
+        let totp_prompt = totp_prompt.unwrap_or_else(|| DEFAULT_TOTP_PROMPT.to_string());
+
+        let re = py.import("re")?;
+        for pattern in responses
+            .iter()
+            .map(|(pattern, _)| pattern)
+            .chain([&totp_prompt])
+        {
+            re.call_method1("compile", (pattern,))?;
+        }
+
+        if let Some(secret) = &totp_secret {
+            if totp::totp(secret, totp::DEFAULT_STEP, totp::DEFAULT_DIGITS).is_none() {
+                return Err(PyValueError::new_err("invalid TOTP secret".to_string()));
+            }
+        }
+
+        Ok(Self {
+            responses,
+            totp_secret,
+            totp_prompt,
+            handler,
+            totp_generator,
+        })
+    }
+}
+
+/// Answers keyboard-interactive prompts on behalf of a [`KeyboardInteractiveAuth`].
+struct KeyboardInteractiveResponder<'a, 'py> {
+    /// The authentication method to answer for.
+    auth: &'a KeyboardInteractiveAuth,
+    /// Python GIL token.
+    py: Python<'py>,
+    /// The first error raised while answering, if any.
+    error: Option<PyErr>,
+}
+
+impl KeyboardInteractivePrompt for KeyboardInteractiveResponder<'_, '_> {
+    fn prompt<'b>(
+        &mut self,
+        _username: &str,
+        instructions: &str,
+        prompts: &[Prompt<'b>],
+    ) -> Vec<String> {
+        if let Some(handler) = &self.auth.handler {
+            return KeyboardInteractiveAuth::ask_handler(self.py, handler, instructions, prompts)
+                .unwrap_or_else(|err| {
+                    self.error.get_or_insert(err);
+                    vec![String::new(); prompts.len()]
+                });
+        }
+
+        prompts
+            .iter()
+            .map(|prompt| match self.auth.respond(self.py, &prompt.text) {
+                Ok(response) => response,
+                Err(err) => {
+                    self.error.get_or_insert(err);
+                    String::new()
+                }
+            })
+            .collect()
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// Represents supported authentication methods.
+pub struct AuthMethods {
+    /// Password-based authentication method.
+    pub password: Option<PasswordAuth>,
+    /// Private-key-based authentication method.
+    pub private_key: Option<PrivateKeyAuth>,
+    /// In-memory private-key-based authentication method.
+    pub private_key_memory: Option<PrivateKeyMemoryAuth>,
+    /// SSH-agent-based authentication method.
+    pub agent: Option<AgentAuth>,
+    /// Host-based authentication method.
+    pub host_based: Option<HostBasedAuth>,
+    /// Keyboard-interactive authentication method.
+    pub keyboard_interactive: Option<KeyboardInteractiveAuth>,
+    /// The methods to attempt first, in order.
+    order: Vec<AuthStep>,
+}
+
+#[pymethods]
+impl AuthMethods {
+    #[new]
+    /// Creates a new [`AuthMethods`].
+    ///
+    /// * `password` - Password-based authentication method.
+    /// * `private_key` - Private-key-based authentication method.
+    /// * `keyboard_interactive` - Keyboard-interactive authentication method.
+    /// * `host_based` - Host-based authentication method.
+    /// * `agent` - SSH-agent-based authentication method.
+    /// * `private_key_memory` - In-memory private-key-based authentication method.
+    /// * `order` - Names of the methods to attempt first, in order (`"password"`, `"private_key"`,
+    ///   `"private_key_memory"`, `"agent"`, `"host_based"` or `"keyboard_interactive"`). Methods
+    ///   that are not listed are attempted afterwards, in the default order.
+    pub fn __new__(
+        password: Option<PasswordAuth>,
+        private_key: Option<PrivateKeyAuth>,
+        keyboard_interactive: Option<KeyboardInteractiveAuth>,
+        host_based: Option<HostBasedAuth>,
+        agent: Option<AgentAuth>,
+        private_key_memory: Option<PrivateKeyMemoryAuth>,
+        order: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        let order = order
+            .unwrap_or_default()
+            .iter()
+            .map(|name| AuthStep::from_name(name))
+            .collect::<PyResult<_>>()?;
+
+        Ok(Self {
+            password,
+            private_key,
+            private_key_memory,
+            agent,
+            host_based,
+            keyboard_interactive,
+            order,
+        })
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// Represents the options used when requesting a pseudo-terminal (PTY).
+pub struct PtyOptions {
+    /// The terminal type (the `TERM` environment variable).
+    pub term: String,
+    /// The width of the terminal (in characters).
+    pub width: u32,
+    /// The height of the terminal (in characters).
+    pub height: u32,
+    /// The width of the terminal (in pixels).
+    pub width_px: u32,
+    /// The height of the terminal (in pixels).
+    pub height_px: u32,
+    /// Terminal modes, keyed by their RFC 4254 names (e.g. `ECHO`).
+    pub modes: HashMap<String, u32>,
+    /// Whether the command's `stderr` is merged into `stdout` (the default behaviour of a PTY).
+    pub merge_stderr: bool,
+}
+
+impl PtyOptions {
+    /// Encodes the terminal modes for a PTY request.
+    ///
+    /// Fails if any of the mode names is unknown.
+    fn pty_modes(&self) -> PyResult<PtyModes> {
+        let mut modes = PtyModes::new();
+
+        for (name, value) in &self.modes {
+            let Some((_, opcode)) = PTY_MODES.iter().find(|(known, _)| known == name) else {
+                return Err(PyValueError::new_err(format!(
+                    "invalid terminal mode: '{}'",
+                    name
+                )));
+            };
+
+            modes.set_u32(*opcode, *value);
+        }
+
+        Ok(modes)
+    }
+
+    /// Requests a PTY with these options on the channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to request the PTY on.
+    fn request(&self, channel: &mut Channel) -> PyResult<()> {
+        let dim = (self.width, self.height, self.width_px, self.height_px);
+
+        channel
+            .request_pty(&self.term, Some(self.pty_modes()?), Some(dim))
+            .map_err(excp_from_err)
+    }
+}
+
+#[pymethods]
+impl PtyOptions {
+    #[new]
+    /// Creates a new [`PtyOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `term` - The terminal type. Defaults to `vt100`.
+    /// * `width` - The width of the terminal (in characters). Defaults to 80.
+    /// * `height` - The height of the terminal (in characters). Defaults to 24.
+    /// * `width_px` - The width of the terminal (in pixels). Defaults to 0.
+    /// * `height_px` - The height of the terminal (in pixels). Defaults to 0.
+    /// * `modes` - Terminal modes, keyed by their RFC 4254 names (e.g. `{"ECHO": 0}`).
+    /// * `merge_stderr` - Whether the command's `stderr` is merged into `stdout`. Defaults to `true`.
+    ///   See [`SSHClient::exec_command`] for what happens otherwise.
+    pub fn __new__(
+        term: Option<String>,
+        width: Option<u32>,
+        height: Option<u32>,
+        width_px: Option<u32>,
+        height_px: Option<u32>,
+        modes: Option<HashMap<String, u32>>,
+        merge_stderr: Option<bool>,
+    ) -> Self {
+        Self {
+            term: term.unwrap_or_else(|| DEFAULT_TERM.to_string()),
+            width: width.unwrap_or(DEFAULT_PTY_WIDTH),
+            height: height.unwrap_or(DEFAULT_PTY_HEIGHT),
+            width_px: width_px.unwrap_or(0),
+            height_px: height_px.unwrap_or(0),
+            modes: modes.unwrap_or_default(),
+            merge_stderr: merge_stderr.unwrap_or(true),
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// Represents the socket options set on the TCP connection underlying an SSH session.
+pub struct TcpOptions {
+    /// Whether Nagle's algorithm is disabled (`TCP_NODELAY`), so that small writes are sent
+    /// immediately.
+    pub nodelay: bool,
+    /// The interval between TCP keepalive probes, if they are enabled.
+    pub keepalive: Option<Duration>,
+    /// The size of the socket's send buffer (in bytes), if it is to be set.
+    pub send_buffer_size: Option<u32>,
+    /// The size of the socket's receive buffer (in bytes), if it is to be set.
+    pub recv_buffer_size: Option<u32>,
+}
+
+impl TcpOptions {
+    /// Sets these options on a TCP connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The TCP connection.
+    fn apply(&self, stream: &TcpStream) -> PyResult<()> {
+        stream.set_nodelay(self.nodelay).map_err(excp_from_err)?;
+
+        if let Some(interval) = self.keepalive {
+            net::set_keepalive(stream, interval).map_err(excp_from_err)?;
+        }
+
+        if self.send_buffer_size.is_some() || self.recv_buffer_size.is_some() {
+            net::set_buffer_sizes(stream, self.send_buffer_size, self.recv_buffer_size)
+                .map_err(excp_from_err)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl TcpOptions {
+    #[new]
+    #[pyo3(signature = (nodelay=false, keepalive=None, send_buffer_size=None, recv_buffer_size=None))]
+    /// Creates a new [`TcpOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `nodelay` - Whether to disable Nagle's algorithm (`TCP_NODELAY`), which lowers the latency
+    ///   of interactive sessions. Defaults to `false`.
+    /// * `keepalive` - Optional interval between TCP keepalive probes (in seconds). Enables
+    ///   `SO_KEEPALIVE`, with probes sent once the connection has been idle for the interval, so
+    ///   that dead peers are detected and idle connections survive NAT timeouts.
+    /// * `send_buffer_size` - Optional size of the socket's send buffer (in bytes), which can be
+    ///   raised for bulk transfers over links with a high bandwidth-delay product.
+    /// * `recv_buffer_size` - Optional size of the socket's receive buffer (in bytes).
+    ///
+    /// **NOTE**: Buffer sizes are hints; the operating system may round or cap them.
+    pub fn __new__(
+        nodelay: bool,
+        keepalive: Option<f64>,
+        send_buffer_size: Option<u32>,
+        recv_buffer_size: Option<u32>,
+    ) -> PyResult<Self> {
+        let keepalive = keepalive
+            .map(|interval| duration_from_secs("keepalive interval", interval))
+            .transpose()?;
+
+        if let Some(interval) = keepalive {
+            if interval < Duration::from_secs(1) {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid keepalive interval: {} (expected at least 1 second)",
+                    interval.as_secs_f64()
+                )));
+            }
+        }
+
+        if send_buffer_size == Some(0) || recv_buffer_size == Some(0) {
+            return Err(PyValueError::new_err(
+                "Socket buffer sizes must be positive".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            nodelay,
+            keepalive,
+            send_buffer_size,
+            recv_buffer_size,
+        })
+    }
+}
+
+/// Reads from a channel stream until EOF, appending everything read to `buf`.
+///
+/// If a timeout is given and it expires before EOF is reached, an error of kind
+/// [`ErrorKind::TimedOut`] is returned. Any data read up to that point is kept in `buf`.
+///
+/// # Arguments
+///
+/// * `sess` - The session the stream belongs to.
+/// * `stream` - The stream to read from.
+/// * `buf` - The buffer to append the data to.
+/// * `timeout` - Optional timeout.
+fn read_stream_to_end(
+    sess: Option<&Session>,
+    stream: &mut Stream,
+    buf: &mut Vec<u8>,
+    timeout: Option<Duration>,
+) -> io::Result<()> {
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let mut chunk = [0; CHUNK_SIZE];
+
+    loop {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        if remaining == Some(Duration::ZERO) {
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "Timed out waiting for data on stream",
+            ));
+        }
+
+        match with_timeout(sess, remaining, || stream.read(&mut chunk)) {
+            Ok(0) => return Ok(()),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Runs the provided closure with the session's blocking timeout temporarily set to `timeout`.
+///
+/// If either the session or the timeout is `None`, the closure is run as is.
+///
+/// # Arguments
+///
+/// * `sess` - The session whose timeout should be set.
+/// * `timeout` - The timeout to apply while the closure runs.
+/// * `f` - The closure to run.
+fn with_timeout<T>(sess: Option<&Session>, timeout: Option<Duration>, f: impl FnOnce() -> T) -> T {
+    match (sess, timeout) {
+        (Some(sess), Some(timeout)) => {
+            let previous = sess.timeout();
+            // A timeout of 0 disables the timeout altogether, so round up to at least 1ms.
+            sess.set_timeout(timeout.as_millis().clamp(1, u32::MAX.into()) as u32);
+
+            let result = f();
+            sess.set_timeout(previous);
+
+            result
+        }
+        _ => f(),
+    }
+}
+
+/// Receives at most `n` bytes from a channel stream, draining any previously buffered data first.
+///
+/// # Arguments
+///
+/// * `sess` - The session the stream belongs to.
+/// * `stream` - The stream to read from, if it has not been consumed yet.
+/// * `buf` - Data that was already read from the stream but not yet returned.
+/// * `n` - The maximum number of bytes to receive.
+/// * `timeout` - Optional timeout.
+fn recv_stream(
+    sess: Option<&Session>,
+    stream: Option<&mut Stream>,
+    buf: &mut Vec<u8>,
+    n: usize,
+    timeout: Option<Duration>,
+) -> io::Result<Vec<u8>> {
+    if !buf.is_empty() {
+        let n = n.min(buf.len());
+        return Ok(buf.drain(..n).collect());
+    }
+
+    let mut data = vec![0; n];
+    let read = match stream {
+        Some(stream) => with_timeout(sess, timeout, || stream.read(&mut data))?,
+        None => 0,
+    };
+    data.truncate(read);
+
+    Ok(data)
+}
+
+/// Runs the provided closure with the session temporarily switched to non-blocking mode.
+///
+/// # Arguments
+///
+/// * `sess` - The session to switch.
+/// * `f` - The closure to run.
+fn non_blocking<T>(sess: &Session, f: impl FnOnce() -> T) -> T {
+    sess.set_blocking(false);
+    let result = f();
+    sess.set_blocking(true);
+
+    result
+}
+
+/// Attempts a single non-blocking read from a channel stream, appending any data read to `buf`.
+///
+/// Returns `true` if data was read or the stream has reached EOF, and `false` if no data is
+/// available yet.
+///
+/// # Arguments
+///
+/// * `sess` - The session the stream belongs to.
+/// * `stream` - The stream to read from.
+/// * `buf` - The buffer to append the data to.
+fn poll_stream(sess: &Session, stream: &mut Stream, buf: &mut Vec<u8>) -> io::Result<bool> {
+    let mut chunk = [0; CHUNK_SIZE];
+
+    match non_blocking(sess, || stream.read(&mut chunk)) {
+        Ok(n) => {
+            buf.extend_from_slice(&chunk[..n]);
+            Ok(true)
+        }
+        Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads a command's `stdout` and `stderr` streams until EOF, alternating between them so that the
+/// command never stalls because the channel window is full of output on the stream that is not
+/// being read.
+///
+/// Returns the contents of `stderr`. The session's timeout applies while no data arrives.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token, released while waiting for data.
+/// * `sess` - The session the command runs on.
+/// * `chan` - The command's channel.
+/// * `on_stdout` - Called with each chunk of `stdout` data as it arrives.
+fn drain_outputs(
+    py: Python<'_>,
+    sess: &Session,
+    chan: &mut Channel,
+    mut on_stdout: impl FnMut(&[u8]) -> PyResult<()>,
+) -> PyResult<Vec<u8>> {
+    let timeout = Duration::from_millis(sess.timeout().into());
+    let mut streams = [Some(chan.stream(0)), Some(chan.stderr())];
+    let mut stderr = Vec::new();
+    let mut chunk = [0; CHUNK_SIZE];
+    let mut last_read = Instant::now();
+
+    while streams.iter().any(Option::is_some) {
+        let mut progressed = false;
+
+        for (index, slot) in streams.iter_mut().enumerate() {
+            let Some(stream) = slot.as_mut() else {
+                continue;
+            };
+
+            match non_blocking(sess, || stream.read(&mut chunk)) {
+                Ok(0) => *slot = None,
+                Ok(n) if index == 0 => on_stdout(&chunk[..n])?,
+                Ok(n) => stderr.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => continue,
+                Err(err) => return Err(excp_from_err(err)),
+            }
+
+            progressed = true;
+        }
+
+        if progressed {
+            last_read = Instant::now();
+            continue;
+        }
+
+        if !timeout.is_zero() && last_read.elapsed() >= timeout {
+            return Err(excp_from_err(io::Error::new(
+                ErrorKind::TimedOut,
+                "Timed out waiting for command output",
+            )));
+        }
+
+        py.allow_threads(|| std::thread::sleep(POLL_INTERVAL));
+        py.check_signals()?;
+    }
+
+    Ok(stderr)
+}
+
+#[derive(FromPyObject)]
+/// A local file to read from or write to: either a file path or a file-like object.
+pub enum LocalFile {
+    /// Path to a local file.
+    #[pyo3(annotation = "str")]
+    Path(String),
+    /// A file-like object, with a `read(size)` method returning bytes or text when read from, or a
+    /// `write(data)` method accepting bytes when written to.
+    #[pyo3(annotation = "BinaryIO")]
+    File(PyObject),
+}
+
+/// Writes a chunk of data to a local file.
+type ChunkWriter<'a> = Box<dyn FnMut(&[u8]) -> PyResult<()> + 'a>;
+
+/// Streams the rest of a channel stream (preceded by any buffered data) into a local file.
+///
+/// Returns the number of bytes written.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `stream` - The stream to read from, if it has not been consumed yet.
+/// * `buf` - Data already read from the stream that has not been returned yet.
+/// * `dest` - The local file to write to.
+fn save_stream(
+    py: Python<'_>,
+    stream: Option<&mut Stream>,
+    buf: &mut Vec<u8>,
+    dest: LocalFile,
+) -> PyResult<u64> {
+    let mut write_chunk: ChunkWriter = match dest {
+        LocalFile::Path(path) => {
+            let mut local = fs::File::create(path).map_err(excp_from_err)?;
+
+            Box::new(move |chunk| local.write_all(chunk).map_err(excp_from_err))
+        }
+        LocalFile::File(fileobj) => Box::new(move |chunk| {
+            fileobj.call_method1(py, "write", (PyBytes::new(py, chunk),))?;
+
+            Ok(())
+        }),
+    };
+
+    let mut written = buf.len() as u64;
+    write_chunk(&std::mem::take(buf))?;
+
+    if let Some(stream) = stream {
+        let mut chunk = [0; CHUNK_SIZE];
+
+        loop {
+            py.check_signals()?;
+
+            let n = stream.read(&mut chunk).map_err(excp_from_err)?;
+
+            if n == 0 {
+                break;
+            }
+
+            write_chunk(&chunk[..n])?;
+            written += n as u64;
+        }
+    }
+
+    Ok(written)
+}
+
+#[derive(FromPyObject)]
+/// Data that can be written to a remote stream: either text or raw bytes.
+pub enum StreamData<'a> {
+    /// Text, written as UTF-8.
+    #[pyo3(annotation = "str")]
+    Text(String),
+    /// Raw bytes (`bytes` or `bytearray`).
+    #[pyo3(annotation = "bytes")]
+    Bytes(Cow<'a, [u8]>),
+}
+
+impl StreamData<'_> {
+    /// Returns the data as a byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            StreamData::Text(text) => text.as_bytes(),
+            StreamData::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+#[pyclass]
+/// Represents the output produced when running [`SSHClient::exec_command`].
+pub struct ExecOutput {
+    /// Serializes the use of the session with other threads.
+    lock: Arc<SessionLock>,
+    /// The state of the output, borrowed while holding the lock.
+    state: LockedState<ExecOutputState>,
+}
+
+/// The state of an [`ExecOutput`].
+struct ExecOutputState {
+    /// The session the command runs on.
+    sess: Option<Session>,
+    channel: Option<Channel>,
+    /// The `stdin` stream.
+    stdin: Option<Stream>,
+    /// The `stdout` stream's contents.
+    stdout: Option<Stream>,
+    /// The `stderr` stream's contents.
+    stderr: Option<Stream>,
+    /// Data read from `stdout` that has not been returned yet.
+    stdout_buf: Vec<u8>,
+    /// Data read from `stderr` that has not been returned yet.
+    stderr_buf: Vec<u8>,
+    /// The exit status of the command, once it is known.
+    returncode: Option<i32>,
+    /// Remote file that the command's `stderr` is redirected to, if it has not been fetched yet.
+    stderr_file: Option<String>,
+    /// The command's audit record, until it has been reported.
+    audit: Option<AuditRecord>,
+}
+
+impl Drop for ExecOutputState {
+    fn drop(&mut self) {
+        if let Some(audit) = self.audit.take() {
+            Python::with_gil(|py| audit.finish(py, self.returncode));
+        }
+    }
+}
+
+impl ExecOutputState {
+    /// Reports the command to the audit hook, if it has not been reported yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn report_audit(&mut self, py: Python<'_>) {
+        if let Some(audit) = self.audit.take() {
+            audit.finish(py, self.returncode);
+        }
+    }
+
+    /// Writes data to the `stdin` stream without blocking on the server's window, draining the
+    /// `stdout` and `stderr` streams into their buffers while waiting for room to write.
+    ///
+    /// This avoids a deadlock when the command produces lots of output while still being fed.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting on the server.
+    /// * `stdin` - The `stdin` stream.
+    /// * `data` - The data to write.
+    fn write_draining(
+        &mut self,
+        py: Python<'_>,
+        stdin: &mut Stream,
+        mut data: &[u8],
+    ) -> PyResult<()> {
+        let Some(sess) = self.sess.as_ref() else {
+            return Ok(());
+        };
+
+        while !data.is_empty() {
+            match non_blocking(sess, || stdin.write(data)) {
+                Ok(n) => data = &data[n..],
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    let buffered = self.stdout_buf.len() + self.stderr_buf.len();
+
+                    if let Some(stdout) = self.stdout.as_mut() {
+                        poll_stream(sess, stdout, &mut self.stdout_buf).map_err(excp_from_err)?;
+                    }
+
+                    if let Some(stderr) = self.stderr.as_mut() {
+                        poll_stream(sess, stderr, &mut self.stderr_buf).map_err(excp_from_err)?;
+                    }
+
+                    if self.stdout_buf.len() + self.stderr_buf.len() == buffered {
+                        py.allow_threads(|| std::thread::sleep(POLL_INTERVAL));
+                        py.check_signals()?;
+                    }
+                }
+                Err(err) => return Err(excp_from_err(err)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Streams the contents of a local file (or file-like object) into the `stdin` stream in
+    /// chunks, then closes it.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while reading the file and waiting on the server.
+    /// * `source` - The local source to read from.
+    fn feed_stdin(&mut self, py: Python<'_>, source: LocalFile) -> PyResult<()> {
+        let Some(mut stdin) = self.stdin.take() else {
+            return Ok(());
+        };
+
+        let mut read_chunk: Box<dyn FnMut() -> PyResult<Vec<u8>>> = match source {
+            LocalFile::Path(path) => {
+                let mut local = fs::File::open(path).map_err(excp_from_err)?;
+
+                Box::new(move || {
+                    let mut chunk = vec![0; CHUNK_SIZE];
+                    let n = py
+                        .allow_threads(|| local.read(&mut chunk))
+                        .map_err(excp_from_err)?;
+                    chunk.truncate(n);
+
+                    Ok(chunk)
+                })
+            }
+            LocalFile::File(fileobj) => Box::new(move || {
+                let data = fileobj.call_method1(py, "read", (CHUNK_SIZE,))?;
+
+                Ok(data.extract::<StreamData>(py)?.as_bytes().to_vec())
+            }),
+        };
+
+        loop {
+            py.check_signals()?;
+
+            let chunk = read_chunk()?;
+
+            if chunk.is_empty() {
+                break;
+            }
+
+            self.write_draining(py, &mut stdin, &chunk)?;
+        }
+
+        if let Some(channel) = self.channel.as_mut() {
+            channel.send_eof().map_err(excp_from_err)?;
+        }
+
+        Ok(())
+    }
+
+    /// Switches the `stderr` stream over to the contents of the remote file that `stderr` was
+    /// redirected to, once the command has finished.
+    ///
+    /// This blocks until the command's `stdout` reaches EOF; its contents are buffered and returned
+    /// by later reads. The remote file is removed once it has been read.
+    fn fetch_stderr_file(&mut self) -> io::Result<()> {
+        let (Some(sess), Some(path)) = (self.sess.as_ref(), self.stderr_file.take()) else {
+            return Ok(());
+        };
+
+        if let Some(stdout) = self.stdout.as_mut() {
+            read_stream_to_end(Some(sess), stdout, &mut self.stdout_buf, None)?;
+        }
+
+        let mut chan = sess.channel_session()?;
+        chan.exec(&format!("cat {path}; rm -f {path}"))?;
+        self.stderr = Some(chan.stream(0));
+
+        Ok(())
+    }
+
+    /// Removes the remote file that `stderr` was redirected to, if it has not been fetched.
+    ///
+    /// Failures are ignored, since the file lives in a temporary directory anyway.
+    fn discard_stderr_file(&mut self) {
+        if let (Some(sess), Some(path)) = (self.sess.as_ref(), self.stderr_file.take()) {
+            if let Ok(mut chan) = sess.channel_session() {
+                let _ = chan.exec(&format!("rm -f {path}"));
+                let _ = chan.wait_eof();
+                let _ = chan.wait_close();
+            }
+        }
+    }
+
+    /// Whether the server has signalled EOF on the channel (see [`ExecOutput::eof_received`]).
+    fn eof_received(&self) -> bool {
+        self.returncode.is_some() || self.channel.as_ref().is_some_and(|channel| channel.eof())
+    }
+
+    /// Whether the channel has been torn down (see [`ExecOutput::closed`]).
+    fn closed(&self) -> bool {
+        self.channel.is_none() || self.returncode.is_some()
+    }
+
+    /// Checks whether the command has output available to read or has finished.
+    ///
+    /// This never blocks. Any data that is pulled off the channel while checking is buffered and
+    /// returned by later reads.
+    fn is_ready(&mut self) -> io::Result<bool> {
+        if !self.stdout_buf.is_empty() || !self.stderr_buf.is_empty() {
+            return Ok(true);
+        }
+
+        let (Some(sess), Some(channel)) = (self.sess.as_ref(), self.channel.as_ref()) else {
+            return Ok(true);
+        };
+
+        if let Some(stdout) = self.stdout.as_mut() {
+            if poll_stream(sess, stdout, &mut self.stdout_buf)? {
+                return Ok(true);
+            }
+        }
+
+        if let Some(stderr) = self.stderr.as_mut() {
+            if poll_stream(sess, stderr, &mut self.stderr_buf)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(channel.eof())
+    }
+}
+
+impl ExecOutput {
+    /// Whether the channel has been torn down (see [`ExecOutput::closed`]), or `None` if another
+    /// operation is using the output.
+    pub(crate) fn try_closed(&self) -> Option<bool> {
+        self.state.borrow_mut().ok().map(|this| this.closed())
+    }
+}
+
+#[pymethods]
+impl ExecOutput {
+    /// Writes the provided data to the `stdin` stream and closes it.
+    ///
+    /// **NOTE**: Future calls will discard the provided data without doing anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to write to the stream. Text is written as UTF-8.
+    pub fn write_stdin(&self, py: Python<'_>, data: StreamData) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        if let Some(mut stdin) = this.stdin.take() {
+            if let Some(channel) = this.channel.as_mut() {
+                stdin.write_all(data.as_bytes()).map_err(excp_from_err)?;
+                stdin.flush().map_err(excp_from_err)?;
+
+                channel.send_eof().map_err(excp_from_err)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the contents of the `stdout` stream and consumes it.
+    ///
+    /// If the timeout expires before the stream is exhausted, a `TimeoutError` is raised. The data
+    /// read so far is retained and returned by the next call.
+    ///
+    /// **NOTE**: Future calls will return an empty string.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Optional timeout (in seconds). Defaults to blocking until EOF.
+    #[pyo3(signature = (timeout=None))]
+    fn read_stdout(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<String> {
+        let timeout = timeout
+            .map(|timeout| duration_from_secs("timeout", timeout))
+            .transpose()?;
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let this = &mut *this;
+
+        if let Some(stdout) = this.stdout.as_mut() {
+            read_stream_to_end(this.sess.as_ref(), stdout, &mut this.stdout_buf, timeout)
+                .map_err(excp_from_err)?;
+            this.stdout.take();
+        }
+
+        String::from_utf8(std::mem::take(&mut this.stdout_buf)).map_err(excp_from_err)
+    }
+
+    /// Reads the contents of the `stderr` stream and consumes it.
+    ///
+    /// If the timeout expires before the stream is exhausted, a `TimeoutError` is raised. The data
+    /// read so far is retained and returned by the next call.
+    ///
+    /// **NOTE**: Future calls will return an empty string.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Optional timeout (in seconds). Defaults to blocking until EOF.
+    #[pyo3(signature = (timeout=None))]
+    fn read_stderr(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<String> {
+        let timeout = timeout
+            .map(|timeout| duration_from_secs("timeout", timeout))
+            .transpose()?;
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let this = &mut *this;
+        this.fetch_stderr_file().map_err(excp_from_err)?;
+
+        if let Some(stderr) = this.stderr.as_mut() {
+            read_stream_to_end(this.sess.as_ref(), stderr, &mut this.stderr_buf, timeout)
+                .map_err(excp_from_err)?;
+            this.stderr.take();
+        }
+
+        String::from_utf8(std::mem::take(&mut this.stderr_buf)).map_err(excp_from_err)
+    }
+
+    /// Streams the contents of the `stdout` stream into a local file and consumes it.
+    ///
+    /// The data is written in chunks as it arrives, so it is never held in memory as a whole.
+    /// Returns the number of bytes written.
+    ///
+    /// **NOTE**: Future calls will write nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - Path to the local file (which is created or truncated), or a binary file-like
+    ///   object to write to.
+    fn save_stdout(&self, py: Python<'_>, dest: LocalFile) -> PyResult<u64> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let this = &mut *this;
+        let written = save_stream(py, this.stdout.as_mut(), &mut this.stdout_buf, dest)?;
+        this.stdout.take();
+
+        Ok(written)
+    }
+
+    /// Streams the contents of the `stderr` stream into a local file and consumes it.
+    ///
+    /// The data is written in chunks as it arrives, so it is never held in memory as a whole.
+    /// Returns the number of bytes written.
+    ///
+    /// **NOTE**: Future calls will write nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - Path to the local file (which is created or truncated), or a binary file-like
+    ///   object to write to.
+    fn save_stderr(&self, py: Python<'_>, dest: LocalFile) -> PyResult<u64> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let this = &mut *this;
+        this.fetch_stderr_file().map_err(excp_from_err)?;
+
+        let written = save_stream(py, this.stderr.as_mut(), &mut this.stderr_buf, dest)?;
+        this.stderr.take();
+
+        Ok(written)
+    }
+
+    /// Receives at most `n` bytes from the `stdout` stream.
+    ///
+    /// Returns an empty `bytes` object once the stream is exhausted. If no data arrives before the
+    /// timeout expires, a `TimeoutError` is raised.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The maximum number of bytes to receive.
+    /// * `timeout` - Optional timeout (in seconds). Defaults to blocking until data is available.
+    #[pyo3(signature = (n, timeout=None))]
+    fn recv<'py>(&self, py: Python<'py>, n: usize, timeout: Option<f64>) -> PyResult<&'py PyBytes> {
+        let timeout = timeout
+            .map(|timeout| duration_from_secs("timeout", timeout))
+            .transpose()?;
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let this = &mut *this;
+        let data = recv_stream(
+            this.sess.as_ref(),
+            this.stdout.as_mut(),
+            &mut this.stdout_buf,
+            n,
+            timeout,
+        )
+        .map_err(excp_from_err)?;
+
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Receives at most `n` bytes from the `stderr` stream.
+    ///
+    /// Returns an empty `bytes` object once the stream is exhausted. If no data arrives before the
+    /// timeout expires, a `TimeoutError` is raised.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The maximum number of bytes to receive.
+    /// * `timeout` - Optional timeout (in seconds). Defaults to blocking until data is available.
+    #[pyo3(signature = (n, timeout=None))]
+    fn recv_stderr<'py>(
+        &self,
+        py: Python<'py>,
+        n: usize,
+        timeout: Option<f64>,
+    ) -> PyResult<&'py PyBytes> {
+        let timeout = timeout
+            .map(|timeout| duration_from_secs("timeout", timeout))
+            .transpose()?;
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let this = &mut *this;
+        this.fetch_stderr_file().map_err(excp_from_err)?;
+
+        let data = recv_stream(
+            this.sess.as_ref(),
+            this.stderr.as_mut(),
+            &mut this.stderr_buf,
+            n,
+            timeout,
+        )
+        .map_err(excp_from_err)?;
+
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Retrieves the exit status of the command and closes the channel and all streams.
+    ///
+    /// **NOTE**: Future calls will return 0.
+    ///
+    /// **NOTE**: Future reads of the `stdout` or `stderr` streams will return empty strings.
+    fn exit_status(&self, py: Python<'_>) -> PyResult<i32> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let mut exit_status = 0;
+
+        if let Some(mut chan) = this.channel.take() {
+            let mut stdout = String::new();
+            let mut stderr = String::new();
+
+            chan.read_to_string(&mut stdout).map_err(excp_from_err)?;
+            chan.stderr()
+                .read_to_string(&mut stderr)
+                .map_err(excp_from_err)?;
+
+            chan.wait_close().map_err(excp_from_err)?;
+            exit_status = chan.exit_status().map_err(excp_from_err)?;
+            this.returncode = Some(exit_status);
+        }
+
+        this.discard_stderr_file();
+        this.report_audit(py);
+
+        Ok(exit_status)
+    }
+
+    /// Whether the server has signalled EOF on the channel, i.e. the command's output is finished.
+    ///
+    /// This only reflects data already processed by the session; it never reads from the network.
+    #[getter]
+    fn eof_received(&self, py: Python<'_>) -> PyResult<bool> {
+        let _guard = self.lock.acquire(py);
+
+        Ok(self.state.borrow_mut()?.eof_received())
+    }
+
+    /// Whether the channel has been torn down, either because the command finished and the channel
+    /// was closed, or because [`ExecOutput::close`] was called.
+    #[getter]
+    fn closed(&self, py: Python<'_>) -> PyResult<bool> {
+        let _guard = self.lock.acquire(py);
+
+        Ok(self.state.borrow_mut()?.closed())
+    }
+
+    /// Whether the command is still running, i.e. the channel is open and its output is not
+    /// finished yet.
+    #[getter]
+    fn active(&self, py: Python<'_>) -> PyResult<bool> {
+        let _guard = self.lock.acquire(py);
+        let this = self.state.borrow_mut()?;
+
+        Ok(!this.closed() && !this.eof_received())
+    }
+
+    /// Checks whether the command has finished, without blocking.
+    ///
+    /// Returns the exit status if the command has finished, or `None` if it is still running.
+    /// Output that arrives in the meantime is buffered and returned by later reads; nothing is
+    /// consumed or closed.
+    fn poll(&self, py: Python<'_>) -> PyResult<Option<i32>> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let this = &mut *this;
+
+        if this.returncode.is_some() {
+            return Ok(this.returncode);
+        }
+
+        let (Some(sess), Some(channel)) = (this.sess.as_ref(), this.channel.as_mut()) else {
+            return Ok(None);
+        };
+
+        if let Some(stdout) = this.stdout.as_mut() {
+            poll_stream(sess, stdout, &mut this.stdout_buf).map_err(excp_from_err)?;
+        }
+
+        if let Some(stderr) = this.stderr.as_mut() {
+            poll_stream(sess, stderr, &mut this.stderr_buf).map_err(excp_from_err)?;
+        }
+
+        if !channel.eof() {
+            return Ok(None);
+        }
+
+        match non_blocking(sess, || channel.wait_close()) {
+            Ok(()) => {
+                this.returncode = Some(channel.exit_status().map_err(excp_from_err)?);
+                this.report_audit(py);
+
+                Ok(this.returncode)
+            }
+            Err(err) if err.code() == ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => Ok(None),
+            Err(err) => Err(excp_from_err(err)),
+        }
+    }
+
+    /// Consumes all streams and closes the underlying channel if it exists and is active.
+    ///
+    /// If there is no active channel, then this function does nothing.
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        this.stdin.take();
+        this.stdout.take();
+        this.stderr.take();
+        this.stdout_buf.clear();
+        this.stderr_buf.clear();
+        this.report_audit(py);
+
+        if let Some(mut channel) = this.channel.take() {
+            channel.close().map_err(excp_from_err)?;
+        }
+
+        this.discard_stderr_file();
+
+        Ok(())
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (channels, timeout=None))]
+/// Blocks until any of the given commands has output available to read or has finished.
+///
+/// Returns the subset of `channels` that are ready, which is empty if the timeout expired first.
+///
+/// # Arguments
+///
+/// * `channels` - The command outputs to wait on.
+/// * `timeout` - Optional timeout (in seconds). Defaults to waiting indefinitely.
+pub fn wait(
+    py: Python<'_>,
+    channels: Vec<Py<ExecOutput>>,
+    timeout: Option<f64>,
+) -> PyResult<Vec<Py<ExecOutput>>> {
+    let deadline = match timeout {
+        Some(timeout) => Instant::now().checked_add(duration_from_secs("timeout", timeout)?),
+        None => None,
+    };
+
+    loop {
+        let mut ready = Vec::new();
+
+        for channel in &channels {
+            let channel_output = channel.borrow(py);
+            let _guard = channel_output.lock.acquire(py);
+
+            if channel_output
+                .state
+                .borrow_mut()?
+                .is_ready()
+                .map_err(excp_from_err)?
+            {
+                ready.push(channel.clone_ref(py));
+            }
+        }
+
+        if !ready.is_empty() || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Ok(ready);
+        }
+
+        py.allow_threads(|| std::thread::sleep(POLL_INTERVAL));
+        py.check_signals()?;
+    }
+}
+
+#[pyclass]
+/// A bidirectional channel opened by the server, e.g. for a connection to a forwarded port.
+pub struct ForwardedChannel {
+    /// The session the channel belongs to.
+    sess: Session,
+    /// Serializes the use of the session with other threads.
+    lock: Arc<SessionLock>,
+    /// The underlying channel, borrowed while holding the lock.
+    channel: LockedState<Option<Channel>>,
+}
+
+#[pymethods]
+impl ForwardedChannel {
+    /// Receives at most `n` bytes from the channel.
+    ///
+    /// Returns an empty `bytes` object once the channel has reached EOF. If no data arrives before
+    /// the timeout expires, a `TimeoutError` is raised.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The maximum number of bytes to receive.
+    /// * `timeout` - Optional timeout (in seconds). Defaults to blocking until data is available.
+    #[pyo3(signature = (n, timeout=None))]
+    pub fn recv<'py>(
+        &self,
+        py: Python<'py>,
+        n: usize,
+        timeout: Option<f64>,
+    ) -> PyResult<&'py PyBytes> {
+        let timeout = timeout
+            .map(|timeout| duration_from_secs("timeout", timeout))
+            .transpose()?;
+        let _guard = self.lock.acquire(py);
+        let mut data = vec![0; n];
+        let read = match self.channel.borrow_mut()?.as_mut() {
+            Some(channel) => with_timeout(Some(&self.sess), timeout, || channel.read(&mut data))
+                .map_err(excp_from_err)?,
+            None => 0,
+        };
+        data.truncate(read);
+
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Sends the data over the channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to send. Text is sent as UTF-8.
+    pub fn send(&self, py: Python<'_>, data: StreamData) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut channel = self.channel.borrow_mut()?;
+        let Some(channel) = channel.as_mut() else {
+            return Err(ChannelException::new_err("Channel is closed".to_string()));
+        };
+
+        channel.write_all(data.as_bytes()).map_err(excp_from_err)?;
+        channel.flush().map_err(excp_from_err)
+    }
+
+    /// Checks if the remote end has finished sending data.
+    pub fn eof(&self, py: Python<'_>) -> PyResult<bool> {
+        let _guard = self.lock.acquire(py);
+
+        Ok(self
+            .channel
+            .borrow_mut()?
+            .as_ref()
+            .is_none_or(|channel| channel.eof()))
+    }
+
+    /// Closes the channel.
+    ///
+    /// If the channel is already closed, then this function does nothing.
+    pub fn close(&self, py: Python<'_>) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+
+        if let Some(mut channel) = self.channel.borrow_mut()?.take() {
+            channel.send_eof().map_err(excp_from_err)?;
+            channel.close().map_err(excp_from_err)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[pyclass]
+/// A port on the remote server that forwards incoming connections to the client.
+pub struct RemoteForward {
+    /// The session the forward belongs to.
+    sess: Session,
+    /// Serializes the use of the session with other threads.
+    lock: Arc<SessionLock>,
+    /// The listener that queues incoming connections, borrowed while holding the lock.
+    listener: LockedState<Listener>,
+    /// The port the server is listening on.
+    port: u16,
+}
+
+#[pymethods]
+impl RemoteForward {
+    /// The port the server is listening on.
+    ///
+    /// When port 0 was requested, this is the port that the server chose.
+    #[getter]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Waits for the next incoming connection and returns its channel.
+    ///
+    /// If no connection arrives before the timeout expires, a `TimeoutError` is raised.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Optional timeout (in seconds). Defaults to waiting indefinitely.
+    #[pyo3(signature = (timeout=None))]
+    pub fn accept(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<ForwardedChannel> {
+        let timeout = timeout
+            .map(|timeout| duration_from_secs("timeout", timeout))
+            .transpose()?;
+        let _guard = self.lock.acquire(py);
+        let mut listener = self.listener.borrow_mut()?;
+        let channel =
+            with_timeout(Some(&self.sess), timeout, || listener.accept()).map_err(excp_from_err)?;
+
+        Ok(ForwardedChannel {
+            sess: self.sess.clone(),
+            lock: Arc::clone(&self.lock),
+            channel: LockedState::new(Some(channel)),
+        })
+    }
+}
+
+#[pyfunction]
+/// Copies a file from one server to another, streaming the data through the local host without
+/// staging it on disk.
+///
+/// The destination file is created (or truncated) with the permissions of the source file.
+///
+/// Returns the number of bytes copied.
+///
+/// # Arguments
+///
+/// * `src_client` - The client connected to the source server.
+/// * `src_path` - The path to the file on the source server.
+/// * `dst_client` - The client connected to the destination server.
+/// * `dst_path` - The path to copy the file to on the destination server.
+pub fn copy_between(
+    py: Python<'_>,
+    src_client: PyRef<'_, SSHClient>,
+    src_path: String,
+    dst_client: PyRef<'_, SSHClient>,
+    dst_path: String,
+) -> PyResult<u64> {
+    let _guards = lock::acquire_pair(py, &src_client.lock, &dst_client.lock);
+    let (Some(src_sess), Some(dst_sess)) = (&src_client.sess, &dst_client.sess) else {
+        return Err(SessionException::new_err(
+            "No active SSH session".to_string(),
+        ));
+    };
+
+    let src_sftp = src_sess.sftp().map_err(excp_from_err)?;
+    let dst_sftp = dst_sess.sftp().map_err(excp_from_err)?;
+
+    let mut src = src_sftp.open(Path::new(&src_path)).map_err(excp_from_err)?;
+    let mode = src
+        .stat()
+        .map_err(excp_from_err)?
+        .perm
+        .map_or(0o644, |perm| perm & PERMISSION_BITS);
+    let mut dst = dst_sftp
+        .open_mode(
+            Path::new(&dst_path),
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+            mode as i32,
+            OpenType::File,
+        )
+        .map_err(excp_from_err)?;
+
+    let copied = io::copy(&mut src, &mut dst).map_err(excp_from_err)?;
+    dst.flush().map_err(excp_from_err)?;
+
+    Ok(copied)
+}
+
+/// Checks whether the SFTP error indicates that the path does not exist.
+///
+/// # Arguments
+///
+/// * `err` - The error to check.
+fn is_not_found(err: &ssh2::Error) -> bool {
+    matches!(
+        err.code(),
+        ErrorCode::SFTP(LIBSSH2_FX_NO_SUCH_FILE) | ErrorCode::SFTP(LIBSSH2_FX_NO_SUCH_PATH)
+    )
+}
+
+/// Convenience function that concatenates a base and a child path into a [`PathBuf`].
+///
+/// If the base is `None`, the child path is returned as a [`PathBuf`].
+///
+/// # Arguments
+///
+/// * `base` - Optional base path.
+/// * `path` - The child path.
+fn path_from_string(base: Option<String>, path: String) -> PathBuf {
+    if let Some(base) = base {
+        let mut base_path = PathBuf::new();
+        base_path.push(base);
+        base_path.push(path);
+
+        return base_path;
+    }
+
+    Path::new(&path).to_path_buf()
+}
+
+#[pyclass]
+/// A file on a remote server.
+pub struct File {
+    /// Serializes the use of the session with other threads.
+    lock: Arc<SessionLock>,
+    /// The state of the file, borrowed while holding the lock.
+    state: LockedState<FileState>,
+}
+
+/// The state of a [`File`].
+struct FileState {
+    /// The session the file was opened on.
+    sess: Session,
+    /// The stat cache of the SFTP client the file was opened with.
+    stat_cache: SharedStatCache,
+    /// Underlying remote file handle.
+    file: ssh2::File,
+    /// The path of the file.
+    path: PathBuf,
+    /// The flags the file was opened with.
+    flags: OpenFlags,
+    /// Whether the file was opened in append mode.
+    append: bool,
+}
+
+impl File {
+    /// Creates a new [`File`] from a remote file handle.
+    ///
+    /// In append mode, the handle is positioned at the end of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `sess` - The session the file was opened on.
+    /// * `lock` - The lock serializing the use of the session.
+    /// * `stat_cache` - The stat cache of the SFTP client the file was opened with.
+    /// * `file` - The remote file handle.
+    /// * `path` - The path of the file.
+    /// * `flags` - The flags the file was opened with.
+    fn new(
+        sess: Session,
+        lock: Arc<SessionLock>,
+        stat_cache: SharedStatCache,
+        file: ssh2::File,
+        path: PathBuf,
+        flags: OpenFlags,
+    ) -> PyResult<Self> {
+        let append = flags.contains(OpenFlags::APPEND);
+        let mut file = FileState {
+            sess,
+            stat_cache,
+            file,
+            path,
+            flags,
+            append,
+        };
+
+        if append {
+            file.seek_to_end()?;
+        }
+
+        Ok(Self {
+            lock,
+            state: LockedState::new(file),
+        })
+    }
+}
+
+impl FileState {
+    /// Drops the cached attributes of the file, after it was modified.
+    fn invalidate_cached(&self) {
+        if let Some(cache) = lock_stat_cache(&self.stat_cache).as_mut() {
+            cache.invalidate(&self.path);
+        }
+    }
+
+    /// Positions the handle at the current end of the file and returns the new position.
+    fn seek_to_end(&mut self) -> PyResult<u64> {
+        let size = self.file.stat().map_err(excp_from_err)?.size.unwrap_or(0);
+        self.file.seek(SeekFrom::Start(size)).map_err(excp_from_err)
+    }
+
+    /// Opens another handle to the file, on its own SFTP channel.
+    fn open_worker(&self) -> Result<ssh2::File, ssh2::Error> {
+        self.sess.sftp()?.open_mode(
+            &self.path,
+            self.flags & (OpenFlags::READ | OpenFlags::WRITE),
+            0o644,
+            OpenType::File,
+        )
+    }
+
+    /// Runs positioned transfers with several requests in flight at once.
+    ///
+    /// The transfers run on the file's own handle along with additional handles, which are opened
+    /// (each on its own SFTP channel) for the duration of the call for as long as the server
+    /// accepts them. The session is switched to non-blocking mode so that each handle can wait on
+    /// its own request while the others make progress. The position of the file's own handle is
+    /// restored afterwards.
+    ///
+    /// Returns the number of bytes transferred for each job, which is less than its length only if
+    /// the end of the file was reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting on the server.
+    /// * `jobs` - The offset and length of each transfer.
+    /// * `step` - Performs a single non-blocking transfer for a job, given the handle, the index
+    ///   of the job and the number of bytes already transferred. Returns the number of bytes
+    ///   transferred, or 0 at the end of the file.
+    fn pipeline(
+        &mut self,
+        py: Python<'_>,
+        jobs: &[(u64, usize)],
+        mut step: impl FnMut(&mut ssh2::File, usize, usize) -> io::Result<usize>,
+    ) -> PyResult<Vec<usize>> {
+        let mut extra = Vec::new();
+
+        // In append mode, the server writes at the end of the file whatever the offset, so the
+        // file's own handle cannot be used for positioned writes.
+        if self.append {
+            extra.push(self.open_worker().map_err(excp_from_err)?);
+        }
+
+        while extra.len() + usize::from(!self.append) < BATCH_CHANNELS.min(jobs.len()) {
+            match self.open_worker() {
+                Ok(worker) => extra.push(worker),
+                Err(_) => break,
+            }
+        }
+
+        let position = self.file.stream_position().map_err(excp_from_err)?;
+        let own = (!self.append).then_some(&mut self.file);
+        let mut workers: Vec<&mut ssh2::File> = own.into_iter().chain(&mut extra).collect();
+        let mut transferred = vec![0; jobs.len()];
+        let mut in_flight: Vec<Option<usize>> = vec![None; workers.len()];
+        let mut next = 0;
+
+        let result = non_blocking(&self.sess, || -> PyResult<()> {
+            loop {
+                let mut progressed = false;
+
+                for (worker, slot) in workers.iter_mut().zip(in_flight.iter_mut()) {
+                    if slot.is_none() && next < jobs.len() {
+                        worker
+                            .seek(SeekFrom::Start(jobs[next].0))
+                            .map_err(excp_from_err)?;
+                        *slot = Some(next);
+                        next += 1;
+                    }
+
+                    let Some(index) = *slot else {
+                        continue;
+                    };
+
+                    if transferred[index] >= jobs[index].1 {
+                        *slot = None;
+                        continue;
+                    }
+
+                    match step(worker, index, transferred[index]) {
+                        Ok(0) => *slot = None,
+                        Ok(n) => transferred[index] += n,
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => continue,
+                        Err(err) => return Err(excp_from_err(err)),
+                    }
+
+                    progressed = true;
+                }
+
+                if in_flight.iter().all(Option::is_none) && next >= jobs.len() {
+                    return Ok(());
+                }
+
+                if !progressed {
+                    py.allow_threads(|| std::thread::sleep(BATCH_POLL_INTERVAL));
+                    py.check_signals()?;
+                }
+            }
+        });
+
+        self.file
+            .seek(SeekFrom::Start(position))
+            .map_err(excp_from_err)?;
+        result?;
+
+        Ok(transferred)
+    }
+}
+
+impl Drop for FileState {
+    fn drop(&mut self) {
+        // Servers may only update the attributes of a file once it is closed.
+        if self.flags.contains(OpenFlags::WRITE) {
+            self.invalidate_cached();
+        }
+    }
+}
+
+#[pymethods]
+impl File {
+    /// Reads and returns the contents of the file.
+    pub fn read(&self, py: Python<'_>) -> PyResult<String> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let mut buf = String::new();
+        this.file.read_to_string(&mut buf).map_err(excp_from_err)?;
+
+        Ok(buf)
+    }
+
+    /// Writes the specified data to the file.
+    ///
+    /// In append mode, the data is always written at the end of the file, even if it was extended
+    /// by someone else since it was opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to write to the file.
+    pub fn write(&self, py: Python<'_>, data: String) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        if this.append {
+            this.seek_to_end()?;
+        }
+
+        let result = this
+            .file
+            .write_all(data.as_bytes())
+            .and_then(|_| this.file.flush());
+        this.invalidate_cached();
+
+        result.map_err(excp_from_err)
+    }
+
+    /// Changes the position of the handle and returns the new absolute position.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset, relative to the position indicated by `whence`.
+    /// * `whence` - `0` for the start of the file, `1` for the current position and `2` for the
+    ///   end of the file. Defaults to `0`.
+    #[pyo3(signature = (offset, whence=0))]
+    pub fn seek(&self, py: Python<'_>, offset: i64, whence: u8) -> PyResult<u64> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let pos = match whence {
+            0 => SeekFrom::Start(u64::try_from(offset).map_err(|_| {
+                PyValueError::new_err(format!("negative seek position {}", offset))
+            })?),
+            1 => SeekFrom::Current(offset),
+            2 => {
+                let end = this.seek_to_end()?;
+                SeekFrom::Start(end.checked_add_signed(offset).ok_or_else(|| {
+                    PyValueError::new_err(format!("negative seek position {}", offset))
+                })?)
+            }
+            _ => return Err(PyValueError::new_err(format!("invalid whence: {}", whence))),
+        };
+
+        this.file.seek(pos).map_err(excp_from_err)
+    }
+
+    /// Returns the current position of the handle.
+    pub fn tell(&self, py: Python<'_>) -> PyResult<u64> {
+        let _guard = self.lock.acquire(py);
+
+        self.state
+            .borrow_mut()?
+            .file
+            .stream_position()
+            .map_err(excp_from_err)
+    }
+
+    /// Reads several ranges of the file at once, keeping multiple requests in flight.
+    ///
+    /// Returns the data of each range, in the same order as the ranges. A range that extends past
+    /// the end of the file returns only the data up to the end.
+    ///
+    /// **NOTE**: The requests are spread over additional SFTP channels (as many as the server
+    /// allows) that are closed once the call returns. The position of the handle is not affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The offset and length of each range to read.
+    pub fn readv<'py>(
+        &self,
+        py: Python<'py>,
+        ranges: Vec<(u64, usize)>,
+    ) -> PyResult<Vec<&'py PyBytes>> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let mut bufs: Vec<Vec<u8>> = ranges.iter().map(|&(_, len)| vec![0; len]).collect();
+        let transferred = this.pipeline(py, &ranges, |worker, index, done| {
+            worker.read(&mut bufs[index][done..])
+        })?;
+
+        Ok(bufs
+            .iter_mut()
+            .zip(transferred)
+            .map(|(buf, len)| PyBytes::new(py, &buf[..len]))
+            .collect())
+    }
+
+    /// Writes data at several offsets of the file at once, keeping multiple requests in flight.
+    ///
+    /// **NOTE**: The requests are spread over additional SFTP channels (as many as the server
+    /// allows) that are closed once the call returns. The position of the handle is not affected,
+    /// and the offsets are honoured even in append mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunks` - The offset and data of each write.
+    pub fn writev(&self, py: Python<'_>, chunks: Vec<(u64, Cow<[u8]>)>) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let jobs: Vec<(u64, usize)> = chunks
+            .iter()
+            .map(|(offset, data)| (*offset, data.len()))
+            .collect();
+        let transferred = this.pipeline(py, &jobs, |worker, index, done| {
+            worker.write(&chunks[index].1[done..])
+        });
+        this.invalidate_cached();
+        let transferred = transferred?;
+
+        if transferred
+            .iter()
+            .zip(&jobs)
+            .any(|(done, (_, len))| done < len)
+        {
+            return Err(excp_from_err(io::Error::new(
+                ErrorKind::WriteZero,
+                "failed to write all data",
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// Attributes of a file on a remote server.
+///
+/// Attributes that the server did not provide are `None`.
+pub struct FileStat(pub ssh2::FileStat);
+
+#[pymethods]
+impl FileStat {
+    /// The size of the file (in bytes).
+    #[getter]
+    pub fn size(&self) -> Option<u64> {
+        self.0.size
+    }
+
+    /// The user ID of the file's owner.
+    #[getter]
+    pub fn uid(&self) -> Option<u32> {
+        self.0.uid
+    }
+
+    /// The group ID of the file's owner.
+    #[getter]
+    pub fn gid(&self) -> Option<u32> {
+        self.0.gid
+    }
+
+    /// The POSIX-style permissions (mode) of the file, including the file type bits.
+    #[getter]
+    pub fn perm(&self) -> Option<u32> {
+        self.0.perm
+    }
+
+    /// The last access time of the file (as a UNIX timestamp).
+    #[getter]
+    pub fn atime(&self) -> Option<u64> {
+        self.0.atime
+    }
+
+    /// The last modification time of the file (as a UNIX timestamp).
+    #[getter]
+    pub fn mtime(&self) -> Option<u64> {
+        self.0.mtime
+    }
+
+    /// Checks if the file is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.0.is_dir()
+    }
+
+    /// Checks if the file is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.0.is_file()
+    }
+}
+
+/// A [`StatCache`] shared by an SFTP client and the files opened with it, if enabled.
+type SharedStatCache = Arc<Mutex<Option<StatCache>>>;
+
+/// Locks a [`SharedStatCache`].
+///
+/// # Arguments
+///
+/// * `cache` - The shared cache.
+fn lock_stat_cache(cache: &SharedStatCache) -> MutexGuard<'_, Option<StatCache>> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A cache of remote file attributes, used to avoid redundant `stat` round trips.
+struct StatCache {
+    /// How long an entry remains valid after it was fetched.
+    ttl: Duration,
+    /// Cached attributes and the time they were fetched, keyed by remote path.
+    entries: HashMap<PathBuf, (ssh2::FileStat, Instant)>,
+}
+
+impl StatCache {
+    /// Creates a new, empty [`StatCache`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - How long an entry remains valid after it was fetched.
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the cached attributes for the path, if present and not yet expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The remote path.
+    fn get(&self, path: &Path) -> Option<ssh2::FileStat> {
+        self.entries
+            .get(path)
+            .filter(|(_, fetched)| fetched.elapsed() < self.ttl)
+            .map(|(stat, _)| stat.clone())
+    }
+
+    /// Caches the attributes for the path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The remote path.
+    /// * `stat` - The attributes of the path.
+    fn insert(&mut self, path: PathBuf, stat: ssh2::FileStat) {
+        self.entries.insert(path, (stat, Instant::now()));
+    }
+
+    /// Drops the cached attributes for the path, everything beneath it and its parent directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The remote path that was modified.
+    fn invalidate(&mut self, path: &Path) {
+        let parent = path.parent();
+
+        self.entries
+            .retain(|cached, _| !cached.starts_with(path) && Some(cached.as_path()) != parent);
+    }
+}
+
+#[pyclass]
+/// The differences between a local and a remote directory tree, as reported by
+/// [`SFTPClient::compare`].
+///
+/// All paths are relative to the roots of the compared trees.
+pub struct TreeComparison {
+    /// Files that exist locally but not on the remote server.
+    #[pyo3(get)]
+    pub missing: Vec<String>,
+    /// Files that exist on the remote server but not locally.
+    #[pyo3(get)]
+    pub extra: Vec<String>,
+    /// Files whose sizes differ.
+    #[pyo3(get)]
+    pub size_mismatch: Vec<String>,
+    /// Files whose sizes match but whose modification times differ.
+    #[pyo3(get)]
+    pub mtime_mismatch: Vec<String>,
+    /// Files whose sizes match but whose contents differ (only populated when checksums are
+    /// compared).
+    #[pyo3(get)]
+    pub checksum_mismatch: Vec<String>,
+}
+
+#[pymethods]
+impl TreeComparison {
+    /// Checks if the trees are identical (as far as the comparison could tell).
+    pub fn is_identical(&self) -> bool {
+        self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.size_mismatch.is_empty()
+            && self.mtime_mismatch.is_empty()
+            && self.checksum_mismatch.is_empty()
+    }
+}
+
+/// How symbolic links are handled when transferring a directory tree.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SymlinkPolicy {
+    /// Links are dereferenced, and their targets transferred in their place.
+    Follow,
+    /// Links are ignored.
+    Skip,
+    /// Links are recreated (with the same target) on the destination.
+    Preserve,
+}
+
+impl SymlinkPolicy {
+    /// Parses a symbolic link policy from its name (`"follow"`, `"skip"` or `"preserve"`).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the policy.
+    fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "follow" => Ok(Self::Follow),
+            "skip" => Ok(Self::Skip),
+            "preserve" => Ok(Self::Preserve),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid symlink policy: {name}"
+            ))),
+        }
+    }
+}
+
+/// Returns the error raised when following a symbolic link would loop back to a directory that is
+/// already being transferred.
+///
+/// # Arguments
+///
+/// * `path` - The path of the looping link.
+fn symlink_loop_error(path: &Path) -> PyErr {
+    SFTPException::new_err(format!("Symbolic link loop detected: {}", path.display()))
+}
+
+/// Creates a local symbolic link, replacing any existing file at its path.
+///
+/// # Arguments
+///
+/// * `target` - The path the link points to.
+/// * `link` - The path of the link.
+#[cfg(unix)]
+fn symlink_local(target: &Path, link: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(link).is_ok() {
+        fs::remove_file(link)?;
+    }
+
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Creates a local symbolic link, replacing any existing file at its path.
+///
+/// # Arguments
+///
+/// * `target` - The path the link points to.
+/// * `link` - The path of the link.
+#[cfg(not(unix))]
+fn symlink_local(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "Preserving symbolic links is not supported on this platform",
+    ))
+}
+
+/// Walks a local directory tree and returns every regular file in it, keyed by its path relative
+/// to the root.
+///
+/// Symbolic links are not followed.
+///
+/// # Arguments
+///
+/// * `root` - The root of the local tree.
+fn walk_local(root: &Path) -> io::Result<HashMap<String, fs::Metadata>> {
+    let mut files = HashMap::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let path = entry.path();
+
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if metadata.is_file() {
+                if let Ok(relative) = path.strip_prefix(root) {
+                    files.insert(relative_path_string(relative), metadata);
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Converts a relative path into a `/`-separated string, so that local and remote paths can be
+/// compared on every platform.
+///
+/// # Arguments
+///
+/// * `path` - The relative path.
+fn relative_path_string(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Parses a line of `sha1sum` output into the path and its checksum.
+///
+/// Names containing a backslash, newline or carriage return are printed escaped, with the line
+/// prefixed by a backslash.
+///
+/// # Arguments
+///
+/// * `line` - The line to parse, without the trailing newline.
+fn parse_checksum_line(line: &[u8]) -> Option<(PathBuf, String)> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+
+    let split = line.windows(2).position(|pair| pair == b"  ")?;
+    let checksum = std::str::from_utf8(&line[..split]).ok()?;
+    let mut name = Vec::with_capacity(line.len() - split - 2);
+    let mut bytes = line[split + 2..].iter();
+
+    while let Some(&b) = bytes.next() {
+        if !escaped || b != b'\\' {
+            name.push(b);
+            continue;
+        }
+
+        name.push(match bytes.next()? {
+            b'\\' => b'\\',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            _ => return None,
+        });
+    }
+
+    Some((
+        PathBuf::from(String::from_utf8_lossy(&name).into_owned()),
+        checksum.to_string(),
+    ))
+}
+
+/// Quotes a string for safe use as a single word in a POSIX shell command.
+///
+/// Words made up only of characters that are never special to the shell are returned unchanged.
+///
+/// # Arguments
+///
+/// * `word` - The string to quote.
+fn shell_quote(word: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c);
+
+    if !word.is_empty() && word.chars().all(is_safe) {
+        return word.to_string();
+    }
+
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+#[pyfunction]
+/// Quotes a string for safe use as a single word in a POSIX shell command.
+///
+/// Words made up only of characters that are never special to the shell are returned unchanged.
+///
+/// # Arguments
+///
+/// * `arg` - The string to quote.
+pub fn quote(arg: &str) -> String {
+    shell_quote(arg)
+}
+
+#[pyfunction]
+/// Builds a POSIX shell command line from a list of arguments, quoting each one as needed.
+///
+/// # Arguments
+///
+/// * `argv` - The command and its arguments.
+pub fn quote_command(argv: Vec<String>) -> String {
+    argv.iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[pyclass]
+/// A change to a remote file, as reported by [`FileWatcher`].
+pub struct WatchEvent {
+    /// The path of the file that changed.
+    #[pyo3(get)]
+    pub path: String,
+    /// The kind of change: `"created"`, `"deleted"` or `"modified"` (size or modification time
+    /// changed).
+    #[pyo3(get)]
+    pub kind: &'static str,
+    /// The attributes of the file before the change (`None` if it was created).
+    #[pyo3(get)]
+    pub old: Option<FileStat>,
+    /// The attributes of the file after the change (`None` if it was deleted).
+    #[pyo3(get)]
+    pub new: Option<FileStat>,
+}
+
+#[pyclass]
+/// An iterator over changes to a remote file or directory, created by [`SFTPClient::watch`].
+///
+/// Each iteration blocks until at least one change is detected.
+pub struct FileWatcher {
+    /// Dedicated SFTP channel used for polling.
+    client: Sftp,
+    /// Serializes the use of the session with other threads.
+    lock: Arc<SessionLock>,
+    /// The watched path.
+    path: PathBuf,
+    /// Interval between polls.
+    interval: Duration,
+    /// Attributes of the watched files as of the last poll.
+    snapshot: HashMap<PathBuf, ssh2::FileStat>,
+    /// Changes that were detected but not returned yet.
+    pending: Vec<WatchEvent>,
+}
+
+impl FileWatcher {
+    /// Returns the current attributes of the watched path, or of its entries if it is a directory.
+    ///
+    /// A path that does not exist yields an empty snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while other threads are using the session.
+    fn poll(&self, py: Python<'_>) -> PyResult<HashMap<PathBuf, ssh2::FileStat>> {
+        let _guard = self.lock.acquire(py);
+        let stat = match self.client.stat(&self.path) {
+            Ok(stat) => stat,
+            Err(err) if is_not_found(&err) => return Ok(HashMap::new()),
+            Err(err) => return Err(excp_from_err(err)),
+        };
+
+        if !stat.is_dir() {
+            return Ok(HashMap::from([(self.path.clone(), stat)]));
+        }
+
+        match self.client.readdir(&self.path) {
+            Ok(entries) => Ok(entries.into_iter().collect()),
+            Err(err) if is_not_found(&err) => Ok(HashMap::new()),
+            Err(err) => Err(excp_from_err(err)),
+        }
+    }
+
+    /// Compares a new snapshot with the previous one, queuing an event for every change.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The new snapshot.
+    fn diff(&mut self, snapshot: HashMap<PathBuf, ssh2::FileStat>) {
+        let mut events = Vec::new();
+
+        for (path, new) in &snapshot {
+            let kind = match self.snapshot.get(path) {
+                None => "created",
+                Some(old) if old.size != new.size || old.mtime != new.mtime => "modified",
+                Some(_) => continue,
+            };
+
+            events.push(WatchEvent {
+                path: path.to_string_lossy().into_owned(),
+                kind,
+                old: self.snapshot.get(path).cloned().map(FileStat),
+                new: Some(FileStat(new.clone())),
+            });
+        }
+
+        for (path, old) in &self.snapshot {
+            if !snapshot.contains_key(path) {
+                events.push(WatchEvent {
+                    path: path.to_string_lossy().into_owned(),
+                    kind: "deleted",
+                    old: Some(FileStat(old.clone())),
+                    new: None,
+                });
+            }
+        }
+
+        events.sort_by(|a, b| a.path.cmp(&b.path));
+        self.pending.extend(events);
+        self.snapshot = snapshot;
+    }
+}
+
+#[pymethods]
+impl FileWatcher {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<WatchEvent>> {
+        while self.pending.is_empty() {
+            let interval = self.interval;
+            py.allow_threads(|| std::thread::sleep(interval));
+            py.check_signals()?;
+
+            let snapshot = self.poll(py)?;
+            self.diff(snapshot);
+        }
+
+        Ok(Some(self.pending.remove(0)))
+    }
+}
+
+/// Checks whether an SFTP error means that the channel carrying the SFTP subsystem is gone.
+///
+/// # Arguments
+///
+/// * `err` - The error to check.
+fn is_channel_lost(err: &ssh2::Error) -> bool {
+    matches!(
+        err.code(),
+        ErrorCode::Session(
+            LIBSSH2_ERROR_SOCKET_SEND
+                | LIBSSH2_ERROR_SOCKET_DISCONNECT
+                | LIBSSH2_ERROR_CHANNEL_FAILURE
+                | LIBSSH2_ERROR_CHANNEL_CLOSED
+                | LIBSSH2_ERROR_CHANNEL_EOF_SENT
+        ) | ErrorCode::SFTP(LIBSSH2_FX_NO_CONNECTION | LIBSSH2_FX_CONNECTION_LOST)
+    )
+}
+
+/// Checks whether a session's connection to the server is broken, going by the session's last
+/// error.
+///
+/// # Arguments
+///
+/// * `sess` - The session to check, after an operation on it failed.
+pub(crate) fn is_transport_lost(sess: &Session) -> bool {
+    matches!(
+        ssh2::Error::last_session_error(sess).map(|err| err.code()),
+        Some(ErrorCode::Session(
+            LIBSSH2_ERROR_SOCKET_SEND | LIBSSH2_ERROR_SOCKET_DISCONNECT | LIBSSH2_ERROR_SOCKET_RECV
+        ))
+    )
+}
+
+/// Converts an error from an operation on the SFTP channel into a Python exception, recording
+/// whether the channel was lost.
+///
+/// # Arguments
+///
+/// * `lost` - The flag to set if the channel was lost.
+/// * `err` - The error.
+fn sftp_error(lost: &Cell<bool>, err: ssh2::Error) -> PyErr {
+    if is_channel_lost(&err) {
+        lost.set(true);
+    }
+
+    excp_from_err(err)
+}
+
+#[pyclass]
+/// The SFTP client.
+///
+/// The client can be shared by several threads: its operations are run one at a time, along with
+/// the other operations on the SSH session.
+pub struct SFTPClient {
+    /// Serializes the use of the session with other threads.
+    lock: Arc<SessionLock>,
+    /// The state of the client, borrowed while holding the lock.
+    state: LockedState<SFTPClientState>,
+}
+
+/// The state of an [`SFTPClient`].
+struct SFTPClientState {
+    /// The session the SFTP client belongs to.
+    sess: Session,
+    /// Underlying SFTP client.
+    client: Option<Sftp>,
+    /// Current working directory.
+    cwd: Option<String>,
+    /// Optional cache of remote file attributes, shared with the files opened with the client.
+    stat_cache: SharedStatCache,
+    /// Whether to reopen the SFTP subsystem after its channel is lost.
+    auto_reopen: bool,
+    /// Whether the SFTP channel was found to be lost by the last failed operation.
+    lost: Cell<bool>,
+    /// The client the SFTP client was opened from, if it has a reconnect policy.
+    owner: Option<Py<SSHClient>>,
+}
+
+impl SFTPClient {
+    /// Whether the SFTP session was closed (see [`SFTPClient::is_closed`]), or `None` if another
+    /// operation is using the client.
+    pub(crate) fn try_is_closed(&self) -> Option<bool> {
+        self.state
+            .borrow_mut()
+            .ok()
+            .map(|this| this.client.is_none())
+    }
+}
+
+impl SFTPClientState {
+    /// Reopens the SFTP subsystem if the previous operation found its channel lost: on a
+    /// re-established SSH session if the connection to the server was lost and the client has a
+    /// reconnect policy, or on the existing SSH session if automatic reopening is enabled.
+    fn revive(&mut self) -> PyResult<()> {
+        if !self.lost.get() || self.client.is_none() {
+            return Ok(());
+        }
+
+        if let (Some(owner), true) = (&self.owner, is_transport_lost(&self.sess)) {
+            let sess = Python::with_gil(|py| -> PyResult<Option<Session>> {
+                let mut owner = owner.try_borrow_mut(py)?;
+
+                if owner.sess.as_ref().is_none_or(is_transport_lost) {
+                    owner.reconnect(py)?;
+                }
+
+                Ok(owner.sess.clone())
+            })?;
+
+            if let Some(sess) = sess {
+                self.client = Some(sess.sftp().map_err(excp_from_err)?);
+                self.sess = sess;
+                self.lost.set(false);
+            }
+        } else if self.auto_reopen {
+            self.client = Some(self.sess.sftp().map_err(excp_from_err)?);
+            self.lost.set(false);
+        }
+
+        Ok(())
+    }
+
+    /// Runs an SFTP operation, running it once more on a re-established session if it failed
+    /// because the connection to the server was lost and the client has a reconnect policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The operation, which revives the SFTP subsystem before using it.
+    fn retrying<T>(&mut self, mut op: impl FnMut(&mut Self) -> PyResult<T>) -> PyResult<T> {
+        match op(self) {
+            Err(_) if self.lost.get() && self.owner.is_some() && is_transport_lost(&self.sess) => {
+                op(self)
+            }
+            result => result,
+        }
+    }
+
+    /// Returns the attributes of the remote path, consulting the stat cache if it is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The remote path.
+    fn cached_stat(&mut self, path: &Path) -> PyResult<ssh2::FileStat> {
+        self.revive()?;
+
+        let Some(client) = self.client.as_ref() else {
+            return Err(SFTPException::new_err("SFTP session not open".to_string()));
+        };
+
+        if let Some(stat) = lock_stat_cache(&self.stat_cache)
+            .as_ref()
+            .and_then(|cache| cache.get(path))
+        {
+            return Ok(stat);
+        }
+
+        let stat = client
+            .stat(path)
+            .map_err(|err| sftp_error(&self.lost, err))?;
+
+        if let Some(cache) = lock_stat_cache(&self.stat_cache).as_mut() {
+            cache.insert(path.to_path_buf(), stat.clone());
+        }
+
+        Ok(stat)
+    }
+
+    /// Walks the remote tree rooted at the path and returns every entry along with its attributes.
+    ///
+    /// The root is always the first entry. Symbolic links are returned but never followed.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The root of the remote tree.
+    fn walk(&mut self, root: &Path) -> PyResult<Vec<(PathBuf, ssh2::FileStat)>> {
+        let root_stat = self.cached_stat(root)?;
+        let mut pending = Vec::new();
+
+        if root_stat.is_dir() {
+            pending.push(root.to_path_buf());
+        }
+
+        let mut entries = vec![(root.to_path_buf(), root_stat)];
+
+        while let Some(dir) = pending.pop() {
+            let Some(client) = self.client.as_ref() else {
+                return Err(SFTPException::new_err("SFTP session not open".to_string()));
+            };
+
+            for (path, stat) in client
+                .readdir(&dir)
+                .map_err(|err| sftp_error(&self.lost, err))?
+            {
+                if stat.file_type().is_symlink() {
+                    entries.push((path, stat));
+                    continue;
+                }
+
+                if stat.is_dir() {
+                    pending.push(path.clone());
+                }
+
+                if let Some(cache) = lock_stat_cache(&self.stat_cache).as_mut() {
+                    cache.insert(path.clone(), stat.clone());
+                }
+
+                entries.push((path, stat));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Applies the attributes to the path (and, if requested, everything beneath it).
+    ///
+    /// Only the attributes that are set in `attrs` are changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The remote path.
+    /// * `attrs` - The attributes to apply.
+    /// * `recursive` - Whether to apply the attributes to the entire tree rooted at the path.
+    fn setstat(&mut self, path: &Path, attrs: ssh2::FileStat, recursive: bool) -> PyResult<()> {
+        let paths = if recursive {
+            self.walk(path)?
+                .into_iter()
+                .filter(|(_, stat)| !stat.file_type().is_symlink())
+                .map(|(path, _)| path)
+                .collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        self.revive()?;
+
+        let Some(client) = self.client.as_ref() else {
+            return Err(SFTPException::new_err("SFTP session not open".to_string()));
+        };
+
+        for path in &paths {
+            client
+                .setstat(path, attrs.clone())
+                .map_err(|err| sftp_error(&self.lost, err))?;
+        }
+
+        self.invalidate_cached(path);
+
+        Ok(())
+    }
+
+    /// Runs an SFTP operation on many paths, keeping several requests in flight at once.
+    ///
+    /// The client's own SFTP channel is used along with additional ones, which are opened for the
+    /// duration of the batch for as long as the server accepts them (so the batch runs on the
+    /// client's channel alone if the server refuses any more). The session is switched to
+    /// non-blocking mode so that each channel can wait on its own request while the others make
+    /// progress. Results are returned in the same order as the paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting on the server.
+    /// * `paths` - The remote paths to operate on.
+    /// * `op` - The operation to run on each path.
+    fn batch<T>(
+        &self,
+        py: Python<'_>,
+        paths: &[PathBuf],
+        op: impl Fn(&Sftp, &Path) -> Result<T, ssh2::Error>,
+    ) -> PyResult<Vec<Result<T, ssh2::Error>>> {
+        let Some(client) = self.client.as_ref() else {
+            return Err(SFTPException::new_err("SFTP session not open".to_string()));
+        };
+
+        let mut extra = Vec::new();
+
+        while extra.len() + 1 < BATCH_CHANNELS.min(paths.len()) {
+            match self.sess.sftp() {
+                Ok(sftp) => extra.push(sftp),
+                Err(_) => break,
+            }
+        }
+
+        let channels: Vec<&Sftp> = std::iter::once(client).chain(&extra).collect();
+        let mut results: Vec<Option<Result<T, ssh2::Error>>> = paths.iter().map(|_| None).collect();
+        let mut in_flight: Vec<Option<usize>> = vec![None; channels.len()];
+        let mut next = 0;
+
+        non_blocking(&self.sess, || -> PyResult<()> {
+            loop {
+                let mut progressed = false;
+
+                for (channel, slot) in channels.iter().zip(in_flight.iter_mut()) {
+                    if slot.is_none() && next < paths.len() {
+                        *slot = Some(next);
+                        next += 1;
+                    }
+
+                    let Some(index) = *slot else {
+                        continue;
+                    };
+
+                    match op(channel, &paths[index]) {
+                        Err(err) if err.code() == ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => {}
+                        result => {
+                            results[index] = Some(result);
+                            *slot = None;
+                            progressed = true;
+                        }
+                    }
+                }
+
+                if in_flight.iter().all(Option::is_none) && next >= paths.len() {
+                    return Ok(());
+                }
+
+                if !progressed {
+                    py.allow_threads(|| std::thread::sleep(BATCH_POLL_INTERVAL));
+                    py.check_signals()?;
+                }
+            }
+        })?;
+
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// Computes the SHA-1 checksums of remote files by running `sha1sum` on the server.
+    ///
+    /// Returns the checksums keyed by path. Files that could not be read are omitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting for output.
+    /// * `paths` - The remote paths to checksum.
+    fn remote_checksums(
+        &self,
+        py: Python<'_>,
+        paths: &[PathBuf],
+    ) -> PyResult<HashMap<PathBuf, String>> {
+        let mut checksums = HashMap::new();
+
+        for chunk in paths.chunks(CHECKSUM_BATCH_SIZE) {
+            let args: Vec<String> = chunk
+                .iter()
+                .map(|path| shell_quote(&path.to_string_lossy()))
+                .collect();
+
+            let mut chan = self.sess.channel_session().map_err(excp_from_err)?;
+            chan.exec(&format!("sha1sum -- {}", args.join(" ")))
+                .map_err(excp_from_err)?;
+
+            let mut output = Vec::new();
+            let stderr = drain_outputs(py, &self.sess, &mut chan, |chunk| {
+                output.extend_from_slice(chunk);
+                Ok(())
+            })?;
+            // `sha1sum` exits with status 1 when some of the files could not be read.
+            check_exit_status(&mut chan, "sha1sum", &stderr, &[0, 1])?;
+
+            checksums.extend(
+                output
+                    .split(|&b| b == b'\n')
+                    .filter_map(parse_checksum_line),
+            );
+        }
+
+        Ok(checksums)
+    }
+
+    /// Drops any cached attributes affected by a modification of the remote path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The remote path that was modified.
+    fn invalidate_cached(&mut self, path: &Path) {
+        if let Some(cache) = lock_stat_cache(&self.stat_cache).as_mut() {
+            cache.invalidate(path);
+        }
+    }
+}
+
+#[pymethods]
+impl SFTPClient {
+    /// Changes the current working directory to the specified directory.
+    ///
+    /// If the specified directory is `None`, then the current working directory is unset.
+    ///
+    /// Once the current working directory is set, all SFTP operations will be relative to this path.
+    ///
+    /// **NOTE**: SFTP does not have a concept of a "current working directory", and so, this function
+    /// tries to emulate it. Currently, only **absolute** paths are supported. This *MAY* change in the
+    /// future, but is not guaranteed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to change to.
+    pub fn chdir(&self, py: Python<'_>, dir: Option<String>) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                if let Some(path) = &dir {
+                    let path = Path::new(&path);
+
+                    if let Err(_) = client
+                        .opendir(path)
+                        .map_err(|err| sftp_error(&this.lost, err))
+                    {
+                        return Err(io::Error::new(
+                            ErrorKind::NotFound,
+                            format!("Path {} does not exist on server", path.display()),
+                        ))
+                        .map_err(excp_from_err)?;
+                    }
+                }
+
+                this.cwd = dir.clone();
+            } else {
+                return Err(SFTPException::new_err("SFTP session not open".to_string()));
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Returns the current working directory.
+    pub fn getcwd(&self, py: Python<'_>) -> PyResult<Option<String>> {
+        let _guard = self.lock.acquire(py);
+
+        Ok(self.state.borrow_mut()?.cwd.clone())
+    }
+
+    /// Retrieves the attributes of a file on the remote server, following symbolic links.
+    ///
+    /// If the stat cache is enabled (see [`SFTPClient::set_stat_cache`]), cached attributes are
+    /// returned while they are still fresh.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    pub fn stat(&self, py: Python<'_>, path: String) -> PyResult<FileStat> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let path = path_from_string(this.cwd.clone(), path);
+        this.retrying(|this| this.cached_stat(&path)).map(FileStat)
+    }
+
+    /// Retrieves the attributes of a file on the remote server, without following symbolic links.
+    ///
+    /// **NOTE**: Results of this method are never cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    pub fn lstat(&self, py: Python<'_>, path: String) -> PyResult<FileStat> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let path = path_from_string(this.cwd.clone(), path.clone());
+                return Ok(FileStat(
+                    client
+                        .lstat(&path)
+                        .map_err(|err| sftp_error(&this.lost, err))?,
+                ));
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Changes the permissions of a file on the remote server.
+    ///
+    /// **NOTE**: When applied recursively, symbolic links are neither followed nor modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    /// * `mode` - POSIX-style permissions to apply.
+    /// * `recursive` - Whether to apply the permissions to everything beneath the path. Defaults to `false`.
+    #[pyo3(signature = (path, mode, recursive=false))]
+    pub fn chmod(&self, py: Python<'_>, path: String, mode: u32, recursive: bool) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let path = path_from_string(this.cwd.clone(), path);
+        let attrs = ssh2::FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: Some(mode),
+            atime: None,
+            mtime: None,
+        };
+
+        this.retrying(|this| this.setstat(&path, attrs.clone(), recursive))
+    }
+
+    /// Changes the owner and group of a file on the remote server.
+    ///
+    /// **NOTE**: When applied recursively, symbolic links are neither followed nor modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    /// * `uid` - The user ID of the new owner.
+    /// * `gid` - The group ID of the new group.
+    /// * `recursive` - Whether to apply the ownership to everything beneath the path. Defaults to `false`.
+    #[pyo3(signature = (path, uid, gid, recursive=false))]
+    pub fn chown(
+        &self,
+        py: Python<'_>,
+        path: String,
+        uid: u32,
+        gid: u32,
+        recursive: bool,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let path = path_from_string(this.cwd.clone(), path);
+        let attrs = ssh2::FileStat {
+            size: None,
+            uid: Some(uid),
+            gid: Some(gid),
+            perm: None,
+            atime: None,
+            mtime: None,
+        };
+
+        this.retrying(|this| this.setstat(&path, attrs.clone(), recursive))
+    }
+
+    /// Watches a remote file or directory for changes, by polling its attributes.
+    ///
+    /// Returns an iterator that yields a [`WatchEvent`] whenever a file is created, deleted or
+    /// modified (its size or modification time changes). When `path` is a directory, its direct
+    /// entries are watched. The polling runs on a dedicated SFTP channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The remote path to watch.
+    /// * `interval` - The interval between polls (in seconds). Defaults to 1.
+    #[pyo3(signature = (path, interval=1.0))]
+    pub fn watch(&self, py: Python<'_>, path: String, interval: f64) -> PyResult<FileWatcher> {
+        if !interval.is_finite() || interval <= 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "Invalid interval: {interval}"
+            )));
+        }
+
+        let interval = duration_from_secs("interval", interval)?;
+        let _guard = self.lock.acquire(py);
+        let this = self.state.borrow_mut()?;
+
+        if this.client.is_none() {
+            return Err(SFTPException::new_err("SFTP session not open".to_string()));
+        }
+
+        let mut watcher = FileWatcher {
+            client: this.sess.sftp().map_err(excp_from_err)?,
+            lock: Arc::clone(&self.lock),
+            path: path_from_string(this.cwd.clone(), path),
+            interval,
+            snapshot: HashMap::new(),
+            pending: Vec::new(),
+        };
+        watcher.snapshot = watcher.poll(py)?;
+
+        Ok(watcher)
+    }
+
+    /// Compares a local directory tree with a remote one, without transferring any file contents.
+    ///
+    /// Files are matched by their paths relative to the two roots and compared by size and
+    /// modification time. Optionally, files whose sizes match can also be compared by checksum,
+    /// which requires `sha1sum` on the server.
+    ///
+    /// **NOTE**: Only regular files are compared. Symbolic links are not followed.
+    ///
+    /// # Arguments
+    ///
+    /// * `local_dir` - The root of the local tree.
+    /// * `remote_dir` - The root of the remote tree.
+    /// * `checksum` - Whether to compare checksums of files whose sizes match. Defaults to `false`.
+    #[pyo3(signature = (local_dir, remote_dir, checksum=false))]
+    pub fn compare(
+        &self,
+        py: Python<'_>,
+        local_dir: String,
+        remote_dir: String,
+        checksum: bool,
+    ) -> PyResult<TreeComparison> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let remote_root = path_from_string(this.cwd.clone(), remote_dir);
+        let local = walk_local(Path::new(&local_dir)).map_err(excp_from_err)?;
+        let remote: HashMap<String, (PathBuf, ssh2::FileStat)> = this
+            .walk(&remote_root)?
+            .into_iter()
+            .filter(|(_, stat)| stat.is_file())
+            .filter_map(|(path, stat)| {
+                let relative = relative_path_string(path.strip_prefix(&remote_root).ok()?);
+                Some((relative, (path, stat)))
+            })
+            .collect();
+
+        let mut comparison = TreeComparison {
+            missing: Vec::new(),
+            extra: Vec::new(),
+            size_mismatch: Vec::new(),
+            mtime_mismatch: Vec::new(),
+            checksum_mismatch: Vec::new(),
+        };
+        let mut same_size = Vec::new();
+
+        for (relative, metadata) in &local {
+            let Some((path, stat)) = remote.get(relative) else {
+                comparison.missing.push(relative.clone());
+                continue;
+            };
+
+            if stat.size != Some(metadata.len()) {
+                comparison.size_mismatch.push(relative.clone());
+                continue;
+            }
+
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+                .map(|mtime| mtime.as_secs());
+
+            if stat.mtime != mtime {
+                comparison.mtime_mismatch.push(relative.clone());
+            }
+
+            same_size.push((relative.clone(), path.clone()));
+        }
+
+        comparison.extra = remote
+            .keys()
+            .filter(|relative| !local.contains_key(*relative))
+            .cloned()
+            .collect();
+
+        if checksum {
+            let paths: Vec<PathBuf> = same_size.iter().map(|(_, path)| path.clone()).collect();
+            let checksums = this.remote_checksums(py, &paths)?;
+
+            for (relative, path) in same_size {
+                let file =
+                    fs::File::open(Path::new(&local_dir).join(&relative)).map_err(excp_from_err)?;
+                let digest = hash::sha1_reader(BufReader::new(file)).map_err(excp_from_err)?;
+
+                if checksums.get(&path) != Some(&hash::hex(&digest)) {
+                    comparison.checksum_mismatch.push(relative);
+                }
+            }
+        }
+
+        for list in [
+            &mut comparison.missing,
+            &mut comparison.extra,
+            &mut comparison.size_mismatch,
+            &mut comparison.mtime_mismatch,
+            &mut comparison.checksum_mismatch,
+        ] {
+            list.sort();
+        }
+
+        Ok(comparison)
+    }
+
+    /// Retrieves the attributes of many files at once, following symbolic links.
+    ///
+    /// Requests are pipelined over several SFTP channels (as many as the server allows) instead of
+    /// waiting for each round trip in turn, which is much faster for large numbers of paths.
+    ///
+    /// Returns the attributes in the same order as the paths, with `None` for paths that do not
+    /// exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The paths to the files.
+    pub fn batch_stat(
+        &self,
+        py: Python<'_>,
+        paths: Vec<String>,
+    ) -> PyResult<Vec<Option<FileStat>>> {
+        let _guard = self.lock.acquire(py);
+        let this = self.state.borrow_mut()?;
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|path| path_from_string(this.cwd.clone(), path))
+            .collect();
+        let results = this.batch(py, &paths, |sftp, path| sftp.stat(path))?;
+
+        paths
+            .into_iter()
+            .zip(results)
+            .map(|(path, result)| match result {
+                Ok(stat) => {
+                    if let Some(cache) = lock_stat_cache(&this.stat_cache).as_mut() {
+                        cache.insert(path, stat.clone());
+                    }
+
+                    Ok(Some(FileStat(stat)))
+                }
+                Err(err) if is_not_found(&err) => Ok(None),
+                Err(err) => Err(excp_from_err(err)),
+            })
+            .collect()
+    }
+
+    /// Removes many files at once.
+    ///
+    /// Requests are pipelined over several SFTP channels (as many as the server allows) instead of
+    /// waiting for each round trip in turn, which is much faster for large numbers of paths.
+    ///
+    /// Every path is attempted, even if removing some of them fails; the first failure is raised
+    /// afterwards.
+    ///
+    /// **NOTE**: This only works for files. For directories, use [`SFTPClient::rmdir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The paths to the files to remove.
+    /// * `ignore_missing` - Whether paths that do not exist are silently skipped. Defaults to `false`.
+    #[pyo3(signature = (paths, ignore_missing=false))]
+    pub fn batch_remove(
+        &self,
+        py: Python<'_>,
+        paths: Vec<String>,
+        ignore_missing: bool,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|path| path_from_string(this.cwd.clone(), path))
+            .collect();
+        let results = this.batch(py, &paths, |sftp, path| sftp.unlink(path))?;
+
+        for path in &paths {
+            this.invalidate_cached(path);
+        }
+
+        results
+            .into_iter()
+            .filter_map(Result::err)
+            .find(|err| !(ignore_missing && is_not_found(err)))
+            .map_or(Ok(()), |err| Err(excp_from_err(err)))
+    }
+
+    /// Computes the total size of the regular files in a remote tree.
+    ///
+    /// **NOTE**: Symbolic links are not followed and do not contribute to the totals.
+    ///
+    /// Returns a tuple of the total size (in bytes) and the number of regular files.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The root of the remote tree.
+    pub fn du(&self, py: Python<'_>, path: String) -> PyResult<(u64, u64)> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+        let path = path_from_string(this.cwd.clone(), path);
+
+        Ok(this
+            .walk(&path)?
+            .into_iter()
+            .filter(|(_, stat)| stat.is_file())
+            .fold((0, 0), |(size, files), (_, stat)| {
+                (size + stat.size.unwrap_or(0), files + 1)
+            }))
+    }
+
+    /// Enables, reconfigures or disables the stat cache.
+    ///
+    /// When enabled, the attributes returned by [`SFTPClient::stat`] (and used by operations that
+    /// walk remote trees) are cached for `ttl` seconds. Entries are dropped automatically when the
+    /// path is modified through this client (including writes to files opened with it), but
+    /// changes made by other clients are only picked up once the entries expire or are invalidated
+    /// explicitly.
+    ///
+    /// Reconfiguring the cache discards all cached entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - How long (in seconds) cached attributes remain valid. `None` disables the cache.
+    #[pyo3(signature = (ttl=None))]
+    pub fn set_stat_cache(&self, py: Python<'_>, ttl: Option<f64>) -> PyResult<()> {
+        let ttl = ttl.map(|ttl| duration_from_secs("ttl", ttl)).transpose()?;
+        let _guard = self.lock.acquire(py);
+        *lock_stat_cache(&self.state.borrow_mut()?.stat_cache) = ttl.map(StatCache::new);
+
+        Ok(())
+    }
+
+    /// Drops cached attributes.
+    ///
+    /// If a path is given, only the entries for that path, everything beneath it and its parent
+    /// directory are dropped. Otherwise, the entire cache is cleared.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Optional path to invalidate.
+    #[pyo3(signature = (path=None))]
+    pub fn invalidate_stat_cache(&self, py: Python<'_>, path: Option<String>) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        match path {
+            Some(path) => {
+                let path = path_from_string(this.cwd.clone(), path);
+                this.invalidate_cached(&path);
+            }
+            None => {
+                if let Some(cache) = lock_stat_cache(&this.stat_cache).as_mut() {
+                    cache.entries.clear();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates a folder on the remote server with the specified numeric mode.
+    ///
+    /// The mode is a plain integer, so it is best written as an octal literal (e.g. `0o755` rather
+    /// than `493`). Only the permission bits (`0o0000` to `0o7777`) may be set.
+    ///
+    /// Servers usually apply their umask to the requested mode. When a mode is given explicitly
+    /// and the created folder ends up with different permissions, they are corrected afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` The directory to create.
+    /// * `mode` - POSIX-style permissions for the newly-created folder. Defaults to `0o777` (subject
+    ///   to the server's umask).
+    /// * `exist_ok` - Whether to succeed silently if the directory already exists. Defaults to `false`.
+    #[pyo3(signature = (dir, mode=None, exist_ok=false))]
+    pub fn mkdir(
+        &self,
+        py: Python<'_>,
+        dir: String,
+        mode: Option<i32>,
+        exist_ok: bool,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            if let Some(mode) = mode {
+                if !(0..=PERMISSION_BITS as i32).contains(&mode) {
+                    return Err(PyValueError::new_err(format!(
+                        "invalid mode: {:#o} (must be between 0o0 and {:#o})",
+                        mode, PERMISSION_BITS
+                    )));
+                }
+            }
+
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let path = path_from_string(this.cwd.clone(), dir.clone());
+
+                if let Err(err) = client.mkdir(&path, mode.unwrap_or(DEFAULT_DIR_MODE)) {
+                    if exist_ok && client.stat(&path).is_ok_and(|stat| stat.is_dir()) {
+                        return Ok(());
+                    }
+
+                    return Err(sftp_error(&this.lost, err));
+                }
+
+                if let Some(mode) = mode.map(|mode| mode as u32) {
+                    let stat = client
+                        .stat(&path)
+                        .map_err(|err| sftp_error(&this.lost, err))?;
+
+                    if stat.perm.map(|perm| perm & PERMISSION_BITS) != Some(mode) {
+                        let attrs = ssh2::FileStat {
+                            size: None,
+                            uid: None,
+                            gid: None,
+                            perm: Some(mode),
+                            atime: None,
+                            mtime: None,
+                        };
+                        client
+                            .setstat(&path, attrs)
+                            .map_err(|err| sftp_error(&this.lost, err))?;
+                    }
+                }
+
+                this.invalidate_cached(&path);
+
+                return Ok(());
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Removes a file from the remote server.
+    ///
+    /// **NOTE**: This only works for files. For directories, use [`SFTPClient::rmdir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file to remove.
+    pub fn unlink(&self, py: Python<'_>, path: String) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let path = path_from_string(this.cwd.clone(), path.clone());
+                client
+                    .unlink(&path)
+                    .map_err(|err| sftp_error(&this.lost, err))?;
+                this.invalidate_cached(&path);
+
+                return Ok(());
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Removes a file from the remote server.
+    ///
+    /// **NOTE**: This method is just an alias to [`SFTPClient::unlink`] to mimic compatibility with paramiko.
+    ///
+    /// **NOTE**: This only works for files. For directories, use [`SFTPClient::rmdir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file to remove.
+    pub fn remove(&self, py: Python<'_>, path: String) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+
+        self.unlink(py, path)
+    }
+
+    /// Removes a directory from the remove server.
+    ///
+    /// **NOTE**: This only works for directories. For files, use [`SFTPClient::remove`].
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The path to the directory to remove.
+    pub fn rmdir(&self, py: Python<'_>, dir: String) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let path = path_from_string(this.cwd.clone(), dir.clone());
+                client
+                    .rmdir(&path)
+                    .map_err(|err| sftp_error(&this.lost, err))?;
+                this.invalidate_cached(&path);
+
+                return Ok(());
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Renames a file or folder on the remote server.
+    ///
+    /// If the destination exists and `overwrite` is `true`, the rename is first attempted as is
+    /// (which replaces the destination atomically on servers that support it). If the server
+    /// refuses, the destination is removed and the rename retried, which is **not** atomic.
+    ///
+    /// # Arguments
+    ///
+    /// * `oldpath` - The current path.
+    /// * `newpath` - The new path.
+    /// * `overwrite` - Whether to replace an existing destination. Defaults to `true`.
+    #[pyo3(signature = (oldpath, newpath, overwrite=true))]
+    pub fn rename(
+        &self,
+        py: Python<'_>,
+        oldpath: String,
+        newpath: String,
+        overwrite: bool,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let oldpath = path_from_string(this.cwd.clone(), oldpath.clone());
+                let newpath = path_from_string(this.cwd.clone(), newpath.clone());
+                let existing = client.lstat(&newpath).ok();
+
+                if existing.is_some() && !overwrite {
+                    return Err(excp_from_err(io::Error::new(
+                        ErrorKind::AlreadyExists,
+                        format!("Path {} already exists on server", newpath.display()),
+                    )));
+                }
+
+                if let Err(err) = client.rename(&oldpath, &newpath, None) {
+                    let Some(existing) = existing else {
+                        return Err(sftp_error(&this.lost, err));
+                    };
+
+                    if existing.is_dir() {
+                        client
+                            .rmdir(&newpath)
+                            .map_err(|err| sftp_error(&this.lost, err))?;
+                    } else {
+                        client
+                            .unlink(&newpath)
+                            .map_err(|err| sftp_error(&this.lost, err))?;
+                    }
+
+                    client
+                        .rename(&oldpath, &newpath, None)
+                        .map_err(|err| sftp_error(&this.lost, err))?;
+                }
+
+                this.invalidate_cached(&oldpath);
+                this.invalidate_cached(&newpath);
+
+                return Ok(());
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Opens a file on the remote server.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The name of the file (if file is in `cwd`) OR the path to the file.
+    /// * `mode` - Python-style file mode.
+    pub fn open(&self, py: Python<'_>, filename: String, mode: Option<&str>) -> PyResult<File> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            let flags = mode.unwrap_or("r");
+            let flags = match flags {
+                "r" => OpenFlags::READ,
+                "r+" => OpenFlags::READ | OpenFlags::WRITE,
+                "w" => OpenFlags::TRUNCATE | OpenFlags::WRITE,
+                "w+" => OpenFlags::WRITE | OpenFlags::TRUNCATE | OpenFlags::READ,
+                "a" => OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::APPEND,
+                "a+" => OpenFlags::CREATE | OpenFlags::APPEND | OpenFlags::READ | OpenFlags::WRITE,
+                _ => return Err(PyValueError::new_err(format!("invalid mode: '{}'", flags))),
+            };
+
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let path = path_from_string(this.cwd.clone(), filename.clone());
+                let file = client
+                    .open_mode(&path, flags, 0o644, OpenType::File)
+                    .map_err(|err| sftp_error(&this.lost, err))?;
+
+                if flags != OpenFlags::READ {
+                    this.invalidate_cached(&path);
+                }
+
+                return File::new(
+                    this.sess.clone(),
+                    Arc::clone(&self.lock),
+                    Arc::clone(&this.stat_cache),
+                    file,
+                    path,
+                    flags,
+                );
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Opens a file on the remote server.
+    ///
+    /// **NOTE**: This method is just an alias to [`SFTPClient::open`] to mimic compatibility with paramiko.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The name of the file (if the file is in `cwd`) OR the path to the file.
+    /// * `mode` - Python-style file mode.
+    pub fn file(&self, py: Python<'_>, filename: String, mode: Option<&str>) -> PyResult<File> {
+        let _guard = self.lock.acquire(py);
+
+        self.open(py, filename, mode)
+    }
+
+    /// Copies a file from the remote server to the local host.
+    ///
+    /// # Arguments
+    ///
+    /// * `remotepath` - The remote file path.
+    /// * `localpath` - The local path to copy the file to.
+    pub fn get(&self, py: Python<'_>, remotepath: String, localpath: String) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let remotepath = path_from_string(this.cwd.clone(), remotepath.clone());
+
+                let mut buf = String::new();
+                let mut file = client
+                    .open(&remotepath)
+                    .map_err(|err| sftp_error(&this.lost, err))?;
+                file.read_to_string(&mut buf).map_err(excp_from_err)?;
+
+                return Ok(fs::write(&localpath, buf).map_err(excp_from_err)?);
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Copies a local file to the remote server.
+    ///
+    /// # Arguments
+    ///
+    /// * `localpath` - The path to the local file.
+    /// * `remotepath` - The remote path to copy the file to.
+    pub fn put(&self, py: Python<'_>, localpath: String, remotepath: String) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            this.revive()?;
+
+            if let Some(client) = this.client.as_mut() {
+                let remotepath = path_from_string(this.cwd.clone(), remotepath.clone());
+
+                let content = fs::read_to_string(&localpath).map_err(excp_from_err)?;
+                let mut file = client
+                    .create(&remotepath)
+                    .map_err(|err| sftp_error(&this.lost, err))?;
+                file.write_all(content.as_bytes()).map_err(excp_from_err)?;
+                this.invalidate_cached(&remotepath);
+
+                return Ok(());
+            }
+
+            Err(SFTPException::new_err("SFTP session not open".to_string()))
+        })
+    }
+
+    /// Copies a file from the remote server to the local host, compressing it with `gzip` on the
+    /// server and decompressing it locally as it arrives.
+    ///
+    /// This greatly reduces transfer times for text-heavy files on slow links.
+    ///
+    /// The file is downloaded to a temporary file next to `localpath`, which only replaces it once
+    /// the transfer has succeeded.
+    ///
+    /// **NOTE**: This requires `gzip` to be available on the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `remotepath` - The remote file path.
+    /// * `localpath` - The local path to copy the file to.
+    pub fn get_compressed(
+        &self,
+        py: Python<'_>,
+        remotepath: String,
+        localpath: String,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let this = self.state.borrow_mut()?;
+
+        if this.client.is_none() {
+            return Err(SFTPException::new_err("SFTP session not open".to_string()));
+        }
+
+        let remotepath = path_from_string(this.cwd.clone(), remotepath);
+
+        let mut chan = this.sess.channel_session().map_err(excp_from_err)?;
+        chan.exec(&format!(
+            "gzip -c -- {}",
+            shell_quote(&remotepath.to_string_lossy())
+        ))
+        .map_err(excp_from_err)?;
+
+        let temp = local_temp_path(&localpath);
+        let mut local = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .map_err(excp_from_err)?;
+
+        let result = (|| {
+            let mut decoder = gzip::Decoder::new().map_err(excp_from_err)?;
+            let mut decompressed = Vec::new();
+
+            let stderr = drain_outputs(py, &this.sess, &mut chan, |chunk| {
+                decoder
+                    .write(chunk, &mut decompressed)
+                    .map_err(excp_from_err)?;
+                local.write_all(&decompressed).map_err(excp_from_err)?;
+                decompressed.clear();
+
+                Ok(())
+            })?;
+
+            check_exit_status(&mut chan, "gzip", &stderr, &[0])?;
+            decoder.finish().map_err(excp_from_err)?;
+
+            fs::rename(&temp, &localpath).map_err(excp_from_err)
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+
+        result
+    }
+
+    /// Copies a local file to the remote server, compressing it locally and decompressing it with
+    /// `gzip` on the server as it arrives.
+    ///
+    /// This greatly reduces transfer times for text-heavy files on slow links.
+    ///
+    /// **NOTE**: This requires `gzip` to be available on the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `localpath` - The path to the local file.
+    /// * `remotepath` - The remote path to copy the file to.
+    pub fn put_compressed(
+        &self,
+        py: Python<'_>,
+        localpath: String,
+        remotepath: String,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        if this.client.is_none() {
+            return Err(SFTPException::new_err("SFTP session not open".to_string()));
+        }
+
+        let remotepath = path_from_string(this.cwd.clone(), remotepath);
+        let mut local = fs::File::open(&localpath).map_err(excp_from_err)?;
+
+        let mut chan = this.sess.channel_session().map_err(excp_from_err)?;
+        chan.exec(&format!(
+            "gzip -dc > {}",
+            shell_quote(&remotepath.to_string_lossy())
+        ))
+        .map_err(excp_from_err)?;
+
+        let mut encoder = gzip::Encoder::new().map_err(excp_from_err)?;
+        let mut chunk = [0; CHUNK_SIZE];
+        let mut compressed = Vec::new();
+
+        loop {
+            let n = local.read(&mut chunk).map_err(excp_from_err)?;
+
+            if n == 0 {
+                encoder.finish(&mut compressed).map_err(excp_from_err)?;
+            } else {
+                encoder
+                    .write(&chunk[..n], &mut compressed)
+                    .map_err(excp_from_err)?;
+            }
+
+            // Writing fails if `gzip` exits early (e.g. when the remote file cannot be created),
+            // in which case its `stderr` explains why.
+            if let Err(err) = chan.write_all(&compressed) {
+                this.invalidate_cached(&remotepath);
+                check_remote_command(&mut chan, "gzip")?;
+                return Err(excp_from_err(err));
+            }
+            compressed.clear();
+
+            if n == 0 {
+                break;
+            }
+        }
+
+        chan.send_eof().map_err(excp_from_err)?;
+        this.invalidate_cached(&remotepath);
+
+        check_remote_command(&mut chan, "gzip")
+    }
+
+    /// Recursively copies a remote directory tree to the local host.
+    ///
+    /// Missing local directories are created; existing files are overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `remotepath` - The remote directory.
+    /// * `localpath` - The local directory to copy the tree to.
+    /// * `symlinks` - How symbolic links are handled: `"follow"` copies their targets in their
+    ///   place, `"skip"` ignores them and `"preserve"` recreates them locally (which is only
+    ///   supported on Unix). Following a link back to a directory that is being copied raises an
+    ///   error. Defaults to `"follow"`.
+    #[pyo3(signature = (remotepath, localpath, symlinks="follow"))]
+    pub fn get_dir(
+        &self,
+        py: Python<'_>,
+        remotepath: String,
+        localpath: String,
+        symlinks: &str,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            let policy = SymlinkPolicy::from_name(symlinks)?;
+            this.revive()?;
+
+            let Some(client) = this.client.as_ref() else {
+                return Err(SFTPException::new_err("SFTP session not open".to_string()));
+            };
+
+            let remotepath = path_from_string(this.cwd.clone(), remotepath.clone());
+            let root = client
+                .realpath(&remotepath)
+                .map_err(|err| sftp_error(&this.lost, err))?;
+            let mut pending = vec![(remotepath, PathBuf::from(&localpath), vec![root])];
+
+            while let Some((dir, local_dir, ancestors)) = pending.pop() {
+                fs::create_dir_all(&local_dir).map_err(excp_from_err)?;
+
+                for (path, mut stat) in client
+                    .readdir(&dir)
+                    .map_err(|err| sftp_error(&this.lost, err))?
+                {
+                    let (Some(name), Some(parent)) = (path.file_name(), ancestors.last()) else {
+                        continue;
+                    };
+                    let local = local_dir.join(name);
+                    let mut real = parent.join(name);
+
+                    if stat.file_type().is_symlink() {
+                        match policy {
+                            SymlinkPolicy::Skip => continue,
+                            SymlinkPolicy::Preserve => {
+                                let target = client
+                                    .readlink(&path)
+                                    .map_err(|err| sftp_error(&this.lost, err))?;
+                                symlink_local(&target, &local).map_err(excp_from_err)?;
+
+                                continue;
+                            }
+                            SymlinkPolicy::Follow => {
+                                stat = client
+                                    .stat(&path)
+                                    .map_err(|err| sftp_error(&this.lost, err))?;
+                                real = client
+                                    .realpath(&path)
+                                    .map_err(|err| sftp_error(&this.lost, err))?;
+                            }
+                        }
+                    }
+
+                    if stat.is_dir() {
+                        if ancestors.contains(&real) {
+                            return Err(symlink_loop_error(&path));
+                        }
+
+                        let mut ancestors = ancestors.clone();
+                        ancestors.push(real);
+                        pending.push((path, local, ancestors));
+                    } else if stat.is_file() {
+                        let mut remote = client
+                            .open(&path)
+                            .map_err(|err| sftp_error(&this.lost, err))?;
+                        let mut file = fs::File::create(&local).map_err(excp_from_err)?;
+                        io::copy(&mut remote, &mut file).map_err(excp_from_err)?;
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Recursively copies a local directory tree to the remote server.
+    ///
+    /// Missing remote directories are created; existing files are overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `localpath` - The local directory.
+    /// * `remotepath` - The remote directory to copy the tree to.
+    /// * `symlinks` - How symbolic links are handled: `"follow"` copies their targets in their
+    ///   place, `"skip"` ignores them and `"preserve"` recreates them on the server. Following a
+    ///   link back to a directory that is being copied raises an error. Defaults to `"follow"`.
+    #[pyo3(signature = (localpath, remotepath, symlinks="follow"))]
+    pub fn put_dir(
+        &self,
+        py: Python<'_>,
+        localpath: String,
+        remotepath: String,
+        symlinks: &str,
+    ) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.retrying(|this| {
+            let policy = SymlinkPolicy::from_name(symlinks)?;
+            this.revive()?;
+
+            let Some(client) = this.client.as_ref() else {
+                return Err(SFTPException::new_err("SFTP session not open".to_string()));
+            };
+
+            let remotepath = path_from_string(this.cwd.clone(), remotepath.clone());
+            let root = fs::canonicalize(&localpath).map_err(excp_from_err)?;
+            let mut pending = vec![(PathBuf::from(&localpath), remotepath.clone(), vec![root])];
+
+            while let Some((dir, remote_dir, ancestors)) = pending.pop() {
+                if !client.stat(&remote_dir).is_ok_and(|stat| stat.is_dir()) {
+                    client
+                        .mkdir(&remote_dir, 0o755)
+                        .map_err(|err| sftp_error(&this.lost, err))?;
+                }
+
+                for entry in fs::read_dir(&dir).map_err(excp_from_err)? {
+                    let entry = entry.map_err(excp_from_err)?;
+                    let Some(parent) = ancestors.last() else {
+                        continue;
+                    };
+                    let name = entry.file_name();
+                    let path = entry.path();
+                    let remote = remote_dir.join(&name);
+                    let mut real = parent.join(&name);
+                    let mut metadata = entry.metadata().map_err(excp_from_err)?;
+
+                    if metadata.file_type().is_symlink() {
+                        match policy {
+                            SymlinkPolicy::Skip => continue,
+                            SymlinkPolicy::Preserve => {
+                                let target = fs::read_link(&path).map_err(excp_from_err)?;
+                                let _ = client.unlink(&remote);
+                                client
+                                    .symlink(&target, &remote)
+                                    .map_err(|err| sftp_error(&this.lost, err))?;
+
+                                continue;
+                            }
+                            SymlinkPolicy::Follow => {
+                                metadata = fs::metadata(&path).map_err(excp_from_err)?;
+                                real = fs::canonicalize(&path).map_err(excp_from_err)?;
+                            }
+                        }
+                    }
+
+                    if metadata.is_dir() {
+                        if ancestors.contains(&real) {
+                            return Err(symlink_loop_error(&path));
+                        }
+
+                        let mut ancestors = ancestors.clone();
+                        ancestors.push(real);
+                        pending.push((path, remote, ancestors));
+                    } else if metadata.is_file() {
+                        let mut local = fs::File::open(&path).map_err(excp_from_err)?;
+                        let mut file = client
+                            .create(&remote)
+                            .map_err(|err| sftp_error(&this.lost, err))?;
+                        io::copy(&mut local, &mut file).map_err(excp_from_err)?;
+                    }
+                }
+            }
+
+            this.invalidate_cached(&remotepath);
+
+            Ok(())
+        })
+    }
+
+    /// Checks if the SFTP session is closed.
+    pub fn is_closed(&self, py: Python<'_>) -> PyResult<bool> {
+        let _guard = self.lock.acquire(py);
+
+        Ok(self.state.borrow_mut()?.client.is_none())
+    }
+
+    /// Closes the SFTP session.
+    pub fn close(&self, py: Python<'_>) -> PyResult<()> {
+        let _guard = self.lock.acquire(py);
+        let mut this = self.state.borrow_mut()?;
+
+        this.client.take();
+        lock_stat_cache(&this.stat_cache).take();
+
+        Ok(())
+    }
+}
+
+/// Waits for a helper command run on the server to finish, and raises an [`SFTPException`]
+/// (including its `stderr` output) if it failed.
+///
+/// # Arguments
+///
+/// * `chan` - The channel the command runs on. Its `stdout` must already have been consumed.
+/// * `name` - The name of the command, used in the error message.
+fn check_remote_command(chan: &mut Channel, name: &str) -> PyResult<()> {
+    let mut stderr = Vec::new();
+    chan.stderr()
+        .read_to_end(&mut stderr)
+        .map_err(excp_from_err)?;
+
+    check_exit_status(chan, name, &stderr, &[0])
+}
+
+/// Waits for a helper command run on the server to finish once its output has been consumed,
+/// and raises an [`SFTPException`] (including its `stderr` output) if it failed.
+///
+/// # Arguments
+///
+/// * `chan` - The channel the command runs on. Its `stdout` and `stderr` must already have been
+///   consumed.
+/// * `name` - The name of the command, used in the error message.
+/// * `stderr` - The command's `stderr` output.
+/// * `success` - The exit statuses that indicate success.
+fn check_exit_status(
+    chan: &mut Channel,
+    name: &str,
+    stderr: &[u8],
+    success: &[i32],
+) -> PyResult<()> {
+    chan.wait_close().map_err(excp_from_err)?;
+
+    match chan.exit_status().map_err(excp_from_err)? {
+        status if success.contains(&status) => Ok(()),
+        status => Err(SFTPException::new_err(format!(
+            "{name} exited with status {status}: {}",
+            String::from_utf8_lossy(stderr).trim()
+        ))),
+    }
+}
+
+/// A single authentication method of [`AuthMethods`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AuthStep {
+    /// [`PasswordAuth`].
+    Password,
+    /// [`PrivateKeyAuth`].
+    PrivateKey,
+    /// [`PrivateKeyMemoryAuth`].
+    PrivateKeyMemory,
+    /// [`AgentAuth`].
+    Agent,
+    /// [`HostBasedAuth`].
+    HostBased,
+    /// [`KeyboardInteractiveAuth`].
+    KeyboardInteractive,
+}
+
+impl AuthStep {
+    /// Every method, in the order they are attempted by default.
+    const ALL: [Self; 6] = [
+        Self::Password,
+        Self::PrivateKey,
+        Self::PrivateKeyMemory,
+        Self::Agent,
+        Self::HostBased,
+        Self::KeyboardInteractive,
+    ];
+
+    /// Returns the name of the method, as used for [`AuthMethods`]'s `order`.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Password => "password",
+            Self::PrivateKey => "private_key",
+            Self::PrivateKeyMemory => "private_key_memory",
+            Self::Agent => "agent",
+            Self::HostBased => "host_based",
+            Self::KeyboardInteractive => "keyboard_interactive",
+        }
+    }
+
+    /// Parses the name of a method, as used for [`AuthMethods`]'s `order`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the method.
+    fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "password" => Ok(Self::Password),
+            "private_key" => Ok(Self::PrivateKey),
+            "private_key_memory" => Ok(Self::PrivateKeyMemory),
+            "agent" => Ok(Self::Agent),
+            "host_based" => Ok(Self::HostBased),
+            "keyboard_interactive" => Ok(Self::KeyboardInteractive),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid authentication method: {name}"
+            ))),
+        }
+    }
+}
+
+impl AuthMethods {
+    /// Returns the configured methods, in the order they are attempted: first those listed in
+    /// `order`, then the rest in the default order.
+    fn steps(&self) -> Vec<AuthStep> {
+        let mut steps: Vec<AuthStep> = Vec::new();
+
+        for step in self.order.iter().chain(&AuthStep::ALL) {
+            if !steps.contains(step) {
+                steps.push(*step);
+            }
+        }
+
+        steps
+            .into_iter()
+            .filter(|step| match step {
+                AuthStep::Password => self.password.is_some(),
+                AuthStep::PrivateKey => self.private_key.is_some(),
+                AuthStep::PrivateKeyMemory => self.private_key_memory.is_some(),
+                AuthStep::Agent => self.agent.is_some(),
+                AuthStep::HostBased => self.host_based.is_some(),
+                AuthStep::KeyboardInteractive => self.keyboard_interactive.is_some(),
+            })
+            .collect()
+    }
+
+    /// Attempts to authenticate a session with a single method.
+    ///
+    /// Does nothing if the method is not configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `sess` - The session to authenticate.
+    /// * `username` - The SSH username.
+    /// * `step` - The method to attempt.
+    fn attempt(
+        &self,
+        py: Python<'_>,
+        sess: &Session,
+        username: &str,
+        step: AuthStep,
+    ) -> PyResult<()> {
+        match step {
+            AuthStep::Password => {
+                if let Some(password) = &self.password {
+                    password.authenticate(py, sess, username)?;
+                }
+            }
+            AuthStep::PrivateKey => {
+                if let Some(private_key) = &self.private_key {
+                    let key = Path::new(&private_key.private_key);
+                    let cert = match &private_key.cert_file {
+                        Some(cert) => Some(PathBuf::from(cert)),
+                        None => keys::certificate_for(key),
+                    };
+                    let contents = fs::read(key).map_err(excp_from_err)?;
+                    let contents = String::from_utf8_lossy(&contents);
+
+                    check_key_type(&private_key.private_key, &contents)?;
+
+                    if keys::is_ppk(&contents) {
+                        let passphrase = private_key.resolve_passphrase(py)?;
+                        let converted = keys::ppk_to_openssh(&contents, passphrase.as_deref())
+                            .map_err(PyValueError::new_err)?;
+                        userauth_pubkey_converted(sess, username, &converted, cert.as_deref())
+                            .map_err(excp_from_err)?;
+
+                        return Ok(());
+                    }
+
+                    let passphrase = private_key.resolve_passphrase(py)?;
+
+                    sess.userauth_pubkey_file(
+                        username,
+                        cert.as_deref(),
+                        key,
+                        passphrase.as_deref(),
+                    )
+                    .map_err(excp_from_err)?;
+                }
+            }
+            AuthStep::PrivateKeyMemory => {
+                if let Some(private_key_memory) = &self.private_key_memory {
+                    check_key_type("in-memory private key", &private_key_memory.private_key)?;
+                    userauth_pubkey_memory(sess, username, private_key_memory)
+                        .map_err(excp_from_err)?;
+                }
+            }
+            AuthStep::Agent => {
+                if let Some(agent) = &self.agent {
+                    userauth_agent(sess, username, agent)?;
+                }
+            }
+            AuthStep::HostBased => {
+                if let Some(host_based) = &self.host_based {
+                    sess.userauth_hostbased_file(
+                        username,
+                        Path::new(&host_based.public_key),
+                        Path::new(&host_based.private_key),
+                        host_based.passphrase.as_deref(),
+                        &host_based.hostname,
+                        host_based.local_username.as_deref(),
+                    )
+                    .map_err(excp_from_err)?;
+                }
+            }
+            AuthStep::KeyboardInteractive => {
+                if let Some(keyboard_interactive) = &self.keyboard_interactive {
+                    let mut responder = KeyboardInteractiveResponder {
+                        auth: keyboard_interactive,
+                        py,
+                        error: None,
+                    };
+                    let result = sess.userauth_keyboard_interactive(username, &mut responder);
+
+                    if let Some(err) = responder.error {
+                        return Err(err);
+                    }
+
+                    result.map_err(excp_from_err)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Authenticates a session using the given authentication methods.
+///
+/// The methods are attempted one at a time (until the session is authenticated) in the following
+/// order:
+///
+/// [`PasswordAuth`] > [`PrivateKeyAuth`] > [`PrivateKeyMemoryAuth`] > [`AgentAuth`] >
+/// [`HostBasedAuth`] > [`KeyboardInteractiveAuth`]
+///
+/// Servers that require several methods (e.g. a public key *and* a password) report the first
+/// accepted method as a partial success. In that case, the remaining methods are attempted again,
+/// in the same order, until the session is fully authenticated.
+///
+/// Returns `true` once authenticated, or `false` if no methods were given. If all the methods fail,
+/// an [`AuthenticationException`] is raised (see [`authentication_failed`]).
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `auth` - The authentication methods to use.
+fn authenticate(
+    py: Python<'_>,
+    sess: &Session,
+    username: &str,
+    auth: AuthMethods,
+) -> PyResult<bool> {
+    let mut remaining = auth.steps();
+
+    if remaining.is_empty() {
+        return Ok(false);
+    }
+
+    let mut allowed = sess.auth_methods(username).ok().map(str::to_string);
+    let mut attempted = Vec::new();
+    let mut last_error = None;
+
+    'chain: while !sess.authenticated() {
+        for index in 0..remaining.len() {
+            attempted.push(remaining[index].name());
+
+            if let Err(err) = auth.attempt(py, sess, username, remaining[index]) {
+                last_error = Some(err);
+            }
+
+            if sess.authenticated() {
+                return Ok(true);
+            }
+
+            // `libssh2` reports a partial success as a failure, but the server then accepts a
+            // different set of methods to continue with.
+            let now = sess.auth_methods(username).ok().map(str::to_string);
+
+            if allowed.is_some() && now.is_some() && now != allowed {
+                allowed = now;
+                remaining.remove(index);
+
+                continue 'chain;
+            }
+        }
+
+        break;
+    }
+
+    if sess.authenticated() {
+        return Ok(true);
+    }
+
+    Err(authentication_failed(
+        py, sess, username, attempted, last_error,
+    ))
+}
+
+/// Builds the error raised when every authentication method failed.
+///
+/// Errors that say more than that authentication failed (e.g. a [`PassphraseRequiredException`]
+/// or a timeout) are returned unchanged. Otherwise, an [`AuthenticationException`] is returned,
+/// with the names of the attempted methods and of the methods the server still allows as its
+/// second and third arguments.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `sess` - The session that failed to authenticate.
+/// * `username` - The SSH username.
+/// * `attempted` - The names of the attempted methods, in order.
+/// * `last_error` - The error from the last attempted method.
+fn authentication_failed(
+    py: Python<'_>,
+    sess: &Session,
+    username: &str,
+    attempted: Vec<&'static str>,
+    last_error: Option<PyErr>,
+) -> PyErr {
+    let message = match last_error {
+        Some(err) if !err.get_type(py).is(py.get_type::<SessionException>()) => return err,
+        Some(err) => format!("Authentication failed: {}", err.value(py)),
+        None => "Authentication failed".to_string(),
+    };
+    let allowed: Vec<String> = sess
+        .auth_methods(username)
+        .map(|methods| methods.split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+
+    AuthenticationException::new_err((message, attempted, allowed))
+}
+
+/// Authenticates a session with the default private keys (see [`keys::default_keys`]), trying each
+/// in turn.
+///
+/// Keys protected by a passphrase are skipped, and certificates stored next to the keys are used.
+/// Returns `true` once authenticated, or `false` if there were no keys to try. If all the keys
+/// fail, an [`AuthenticationException`] is raised (see [`authentication_failed`]).
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `sess` - The session to authenticate.
+/// * `username` - The SSH username.
+/// * `keys` - The paths of the private keys.
+fn authenticate_default_keys(
+    py: Python<'_>,
+    sess: &Session,
+    username: &str,
+    keys: &[PathBuf],
+) -> PyResult<bool> {
+    let mut last_error = None;
+
+    // Querying the accepted methods first makes `libssh2` receive the server's banner, if any.
+    let _ = sess.auth_methods(username);
+
+    for key in keys {
+        let encrypted = fs::read_to_string(key)
+            .map(|key| keys::is_encrypted(&key))
+            .unwrap_or(true);
+
+        if encrypted {
+            continue;
+        }
+
+        let cert = keys::certificate_for(key);
+
+        match sess.userauth_pubkey_file(username, cert.as_deref(), key, None) {
+            Ok(()) => return Ok(true),
+            Err(err) => last_error = Some(excp_from_err(err)),
+        }
+    }
+
+    match last_error {
+        Some(err) => Err(authentication_failed(
+            py,
+            sess,
+            username,
+            vec![AuthStep::PrivateKey.name()],
+            Some(err),
+        )),
+        None => Ok(false),
+    }
+}
+
+/// The algorithm types accepted by the `algorithms` argument of [`SSHClient::connect`], with
+/// their names for error messages and the methods they restrict (in both directions).
+const ALGORITHM_TYPES: [(&str, &str, &[MethodType]); 4] = [
+    ("kex", "key exchange", &[MethodType::Kex]),
+    (
+        "cipher",
+        "cipher",
+        &[MethodType::CryptCs, MethodType::CryptSc],
+    ),
+    ("mac", "MAC", &[MethodType::MacCs, MethodType::MacSc]),
+    (
+        "compression",
+        "compression",
+        &[MethodType::CompCs, MethodType::CompSc],
+    ),
+];
+
+/// Restricts the algorithms of a type a session negotiates during the handshake.
+///
+/// Fails with a `ValueError` if an algorithm is not supported by the `libssh2` build in use.
+///
+/// # Arguments
+///
+/// * `sess` - The session, before the handshake.
+/// * `method` - The type of algorithms.
+/// * `name` - The name of the type of algorithms, for error messages (e.g. `"host key"`).
+/// * `algorithms` - The algorithms, in order of preference.
+fn set_method_pref(
+    sess: &Session,
+    method: MethodType,
+    name: &str,
+    algorithms: &[String],
+) -> PyResult<()> {
+    let supported = sess.supported_algs(method).map_err(excp_from_err)?;
+
+    if algorithms.is_empty() {
+        return Err(PyValueError::new_err(format!("No {name} algorithms given")));
+    }
+
+    if let Some(unsupported) = algorithms
+        .iter()
+        .find(|alg| !supported.contains(&alg.as_str()))
+    {
+        return Err(PyValueError::new_err(format!(
+            "Unsupported {name} algorithm: {unsupported} (supported: {})",
+            supported.join(", ")
+        )));
+    }
+
+    sess.method_pref(method, &algorithms.join(","))
+        .map_err(excp_from_err)
+}
+
+/// Restricts the host key algorithms a session negotiates during the handshake.
+///
+/// Fails with a `ValueError` if an algorithm is not supported by the `libssh2` build in use.
+///
+/// # Arguments
+///
+/// * `sess` - The session, before the handshake.
+/// * `algorithms` - The host key algorithms, in order of preference.
+fn set_host_key_algorithms(sess: &Session, algorithms: &[String]) -> PyResult<()> {
+    set_method_pref(sess, MethodType::HostKey, "host key", algorithms)
+}
+
+/// Restricts the key exchange, cipher, MAC and compression algorithms a session negotiates during
+/// the handshake (see the `algorithms` argument of [`SSHClient::connect`]).
+///
+/// Fails with a `ValueError` if a type of algorithms is unknown, or an algorithm is not supported
+/// by the `libssh2` build in use.
+///
+/// # Arguments
+///
+/// * `sess` - The session, before the handshake.
+/// * `algorithms` - The algorithms of each type, in order of preference.
+fn set_algorithms(sess: &Session, algorithms: &HashMap<String, Vec<String>>) -> PyResult<()> {
+    for (kind, algorithms) in algorithms {
+        let Some((_, name, methods)) = ALGORITHM_TYPES
+            .iter()
+            .find(|(key, _, _)| key.eq_ignore_ascii_case(kind))
+        else {
+            return Err(PyValueError::new_err(format!(
+                "Unknown algorithm type: {kind} (expected kex, cipher, mac or compression)"
+            )));
+        };
+
+        for method in methods.iter() {
+            set_method_pref(sess, *method, name, algorithms)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses an IP address, which may be enclosed in brackets if it is an IPv6 address.
+///
+/// # Arguments
+///
+/// * `ip` - The IP address.
+fn parse_ip(ip: &str) -> PyResult<IpAddr> {
+    net::strip_brackets(ip)
+        .parse()
+        .map_err(|_| PyValueError::new_err(format!("Invalid IP address: {ip}")))
+}
+
+#[derive(FromPyObject)]
+/// A connected socket: either a socket object or a raw file descriptor.
+pub enum SocketLike {
+    /// A raw file descriptor.
+    #[pyo3(annotation = "int")]
+    Fd(i64),
+    /// A socket object, with a `fileno()` method (e.g. a `socket.socket`).
+    #[pyo3(annotation = "socket.socket")]
+    Socket(PyObject),
+}
+
+impl SocketLike {
+    /// Returns the socket's file descriptor.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    fn fileno(&self, py: Python<'_>) -> PyResult<i64> {
+        match self {
+            Self::Fd(fd) => Ok(*fd),
+            Self::Socket(sock) => sock.call_method0(py, "fileno")?.extract(py),
+        }
+    }
+}
+
+/// Duplicates a connected socket's file descriptor, so that an SSH session can be run over it
+/// while the caller keeps (and may close) the original one.
+///
+/// The duplicate is switched to blocking mode, which also applies to the original.
+///
+/// # Arguments
+///
+/// * `fd` - The socket's file descriptor.
+#[cfg(unix)]
+fn stream_from_fd(fd: i64) -> PyResult<TcpStream> {
+    let Some(fd) = RawFd::try_from(fd).ok().filter(|fd| *fd >= 0) else {
+        return Err(PyValueError::new_err(format!(
+            "Invalid file descriptor: {fd}"
+        )));
+    };
+    // SAFETY: the descriptor is only borrowed for the duration of the duplication, which fails
+    // cleanly if it is not open.
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) }
+        .try_clone_to_owned()
+        .map_err(excp_from_err)?;
+    let stream = TcpStream::from(fd);
+    stream.set_nonblocking(false).map_err(excp_from_err)?;
+
+    Ok(stream)
+}
+
+/// Duplicates a connected socket's file descriptor.
+///
+/// Not supported on this platform.
+///
+/// # Arguments
+///
+/// * `_fd` - The socket's file descriptor.
+#[cfg(not(unix))]
+fn stream_from_fd(_fd: i64) -> PyResult<TcpStream> {
+    Err(SessionException::new_err(
+        "Connecting over an existing socket is only supported on Unix platforms".to_string(),
+    ))
+}
+
+/// Options for establishing the connection underlying an SSH session (see [`open_session`]).
+#[derive(Clone, Default)]
+struct TransportOptions {
+    /// The address families to connect over.
+    family: AddressFamily,
+    /// Optional callable used instead of the system resolver.
+    resolver: Option<PyObject>,
+    /// Optional host key algorithms to negotiate, in order of preference.
+    host_key_algorithms: Option<Vec<String>>,
+    /// Optional local address to connect from.
+    source_address: Option<SocketAddr>,
+    /// Optional proxy server to tunnel the connection through.
+    proxy: Option<Proxy>,
+    /// Whether to request `zlib` compression of the transport.
+    compress: bool,
+    /// Optional key exchange, cipher, MAC and compression algorithms to negotiate, by type.
+    algorithms: Option<HashMap<String, Vec<String>>>,
+    /// Optional identification string to send instead of the `libssh2` one.
+    banner: Option<String>,
+    /// Optional socket options for the TCP connection.
+    tcp: Option<TcpOptions>,
+}
+
+/// Creates a session configured with the negotiation options (compression, banner and
+/// algorithms), ready for its transport to be set.
+///
+/// # Arguments
+///
+/// * `options` - Options for establishing the connection.
+fn new_session(options: &TransportOptions) -> PyResult<Session> {
+    let sess = Session::new().map_err(excp_from_err)?;
+    sess.set_compress(options.compress);
+
+    if let Some(banner) = &options.banner {
+        sess.set_banner(banner).map_err(excp_from_err)?;
+    }
+
+    if let Some(algorithms) = &options.host_key_algorithms {
+        set_host_key_algorithms(&sess, algorithms)?;
+    }
+
+    if let Some(algorithms) = &options.algorithms {
+        set_algorithms(&sess, algorithms)?;
+    }
+
+    Ok(sess)
+}
+
+/// Connects to an SSH server and performs the SSH handshake, without authenticating.
+///
+/// Returns the session, along with a handle to its TCP connection for reading statistics (if the
+/// socket could be duplicated).
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `host` - The host name or address.
+/// * `port` - The SSH port.
+/// * `timeout` - The timeout for the TCP connection (in seconds).
+/// * `options` - Options for establishing the connection.
+fn open_session(
+    py: Python<'_>,
+    host: &str,
+    port: u16,
+    timeout: u32,
+    options: &TransportOptions,
+) -> PyResult<(Session, Option<TcpStream>)> {
+    let mut sess = new_session(options)?;
+
+    if let Some(path) = net::unix_socket_path(host) {
+        if options.proxy.is_some() || options.source_address.is_some() {
+            return Err(PyValueError::new_err(
+                "Unix socket hosts cannot be combined with a proxy or a source address".to_string(),
+            ));
+        }
+
+        connect_unix_socket(&mut sess, path)?;
+        sess.handshake().map_err(excp_from_err)?;
+
+        return Ok((sess, None));
+    }
+
+    let host = net::strip_brackets(host);
+
+    // When going through a proxy, the TCP connection is made to the proxy, which resolves the host
+    // itself.
+    let (peer_host, peer_port) = match &options.proxy {
+        Some(proxy) => (proxy.host.as_str(), proxy.port),
+        None => (host, port),
+    };
+    let addrs = match &options.resolver {
+        Some(resolver) => {
+            let resolved: Vec<(String, u16)> = resolver.call1(py, (peer_host,))?.extract(py)?;
+            let addrs = resolved
+                .into_iter()
+                .map(|(ip, port)| parse_ip(&ip).map(|ip| SocketAddr::new(ip, port)))
+                .collect::<PyResult<_>>()?;
+
+            net::select(peer_host, addrs, options.family)
+        }
+        None => net::resolve(peer_host, peer_port, options.family),
+    }
+    .and_then(|addrs| match &options.source_address {
+        // Only addresses of the source address's family can be reached from it.
+        Some(source) => net::select(peer_host, addrs, AddressFamily::of(source)),
+        None => Ok(addrs),
+    })
+    .map_err(excp_from_err)?;
+    let timeout = Duration::from_secs(timeout as u64);
+    let mut tcp = net::connect(addrs, timeout, options.source_address)
+        .map_err(|failures| connect_error(py, peer_host, failures))?;
+
+    if let Some(tcp_options) = &options.tcp {
+        tcp_options.apply(&tcp)?;
+    }
+
+    if let Some(proxy) = &options.proxy {
+        proxy
+            .open_tunnel(
+                &mut tcp,
+                host,
+                port,
+                (!timeout.is_zero()).then_some(timeout),
+            )
+            .map_err(excp_from_err)?;
+    }
+
+    let stats_handle = tcp.try_clone().ok();
+    sess.set_tcp_stream(tcp);
+    sess.handshake().map_err(excp_from_err)?;
+
+    Ok((sess, stats_handle))
+}
+
+/// Connects a session to an SSH server listening on a Unix socket.
+///
+/// # Arguments
+///
+/// * `sess` - The session, before the handshake.
+/// * `path` - The path to the socket.
+#[cfg(unix)]
+fn connect_unix_socket(sess: &mut Session, path: &str) -> PyResult<()> {
+    if path.is_empty() {
+        return Err(PyValueError::new_err(
+            "No Unix socket path given (expected unix:///path/to/socket)".to_string(),
+        ));
+    }
+
+    let stream = UnixStream::connect(path).map_err(excp_from_err)?;
+    sess.set_tcp_stream(stream);
+
+    Ok(())
+}
+
+/// Connects a session to an SSH server listening on a Unix socket.
+///
+/// Not supported on this platform.
+///
+/// # Arguments
+///
+/// * `_sess` - The session, before the handshake.
+/// * `_path` - The path to the socket.
+#[cfg(not(unix))]
+fn connect_unix_socket(_sess: &mut Session, _path: &str) -> PyResult<()> {
+    Err(SessionException::new_err(
+        "Unix sockets are only supported on Unix platforms".to_string(),
+    ))
+}
+
+/// Creates the error raised when no connection could be established to a host.
+///
+/// If a single address was tried, its own error is raised; otherwise, a
+/// [`NoValidConnectionsError`] lists every attempt.
+///
+/// # Arguments
+///
+/// * `py` - Python GIL token.
+/// * `host` - The host name or address.
+/// * `failures` - The addresses that were tried, along with the error each attempt failed with.
+fn connect_error(py: Python<'_>, host: &str, failures: Vec<(SocketAddr, io::Error)>) -> PyErr {
+    if failures.len() <= 1 {
+        return match failures.into_iter().next() {
+            Some((_, err)) => excp_from_err(err),
+            None => PyConnectionError::new_err(format!("No addresses to connect to for {host}")),
+        };
+    }
+
+    let summary = failures
+        .iter()
+        .map(|(addr, err)| format!("{addr} ({err})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let errors: Vec<(String, u16, PyObject)> = failures
+        .into_iter()
+        .map(|(addr, err)| {
+            (
+                addr.ip().to_string(),
+                addr.port(),
+                excp_from_err(err).into_value(py).into_py(py),
+            )
+        })
+        .collect();
+
+    NoValidConnectionsError::new_err((format!("Unable to connect to {host}: {summary}"), errors))
+}
+
+#[pyclass]
+/// Transport-level statistics of an SSH session, as reported by [`SSHClient::metrics`].
+pub struct TransportMetrics {
+    /// Bytes sent over the connection and acknowledged by the server (`None` if the platform does
+    /// not report it).
+    #[pyo3(get)]
+    pub bytes_sent: Option<u64>,
+    /// Bytes received over the connection (`None` if the platform does not report it).
+    #[pyo3(get)]
+    pub bytes_received: Option<u64>,
+    /// Number of channels opened by the client for commands and SFTP sessions.
+    #[pyo3(get)]
+    pub channels_opened: u64,
+    /// Time since the session was established (in seconds).
+    #[pyo3(get)]
+    pub uptime: f64,
+}
+
+#[pyclass]
+/// The algorithms negotiated for an SSH session, as reported by
+/// [`SSHClient::negotiated_methods`]. Algorithms that may differ per direction are reported for
+/// both the client-to-server (`_cs`) and the server-to-client (`_sc`) direction.
+pub struct NegotiatedMethods {
+    /// The key exchange algorithm (e.g. `curve25519-sha256`).
+    #[pyo3(get)]
+    pub kex: String,
+    /// The host key algorithm (e.g. `ssh-ed25519`).
+    #[pyo3(get)]
+    pub host_key: String,
+    /// The cipher from the client to the server (e.g. `aes256-gcm@openssh.com`).
+    #[pyo3(get)]
+    pub cipher_cs: String,
+    /// The cipher from the server to the client.
+    #[pyo3(get)]
+    pub cipher_sc: String,
+    /// The MAC algorithm from the client to the server (e.g. `hmac-sha2-256`).
+    #[pyo3(get)]
+    pub mac_cs: String,
+    /// The MAC algorithm from the server to the client.
+    #[pyo3(get)]
+    pub mac_sc: String,
+    /// The compression algorithm from the client to the server (e.g. `none` or `zlib@openssh.com`).
+    #[pyo3(get)]
+    pub compression_cs: String,
+    /// The compression algorithm from the server to the client.
+    #[pyo3(get)]
+    pub compression_sc: String,
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// A server's host key, as returned by [`SSHClient::get_host_key`].
+pub struct HostKey {
+    /// The key type (e.g. `ssh-ed25519`).
+    #[pyo3(get)]
+    pub key_type: String,
+    /// The key in SSH wire format, base64-encoded (as it appears in `known_hosts` files).
+    #[pyo3(get)]
+    pub base64: String,
+    /// The legacy MD5 fingerprint of the key (`MD5:` followed by colon-separated hexadecimal
+    /// bytes).
+    #[pyo3(get)]
+    pub md5_fingerprint: String,
+    /// The SHA256 fingerprint of the key (`SHA256:` followed by the unpadded base64 digest).
+    #[pyo3(get)]
+    pub sha256_fingerprint: String,
+}
+
+impl HostKey {
+    /// Creates a new [`HostKey`] from a key in SSH wire format.
+    ///
+    /// Returns `None` if the key is malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `blob` - The host key, in SSH wire format.
+    pub fn from_blob(blob: &[u8]) -> Option<Self> {
+        Some(Self {
+            key_type: keys::public_key_type(blob)?.to_string(),
+            base64: keys::base64_encode(blob),
+            md5_fingerprint: keys::md5_fingerprint(blob),
+            sha256_fingerprint: keys::fingerprint(blob),
+        })
+    }
+}
+
+/// Creates a [`BadHostKeyException`], with the host name, the expected host key (if any) and the
+/// presented host key as further arguments.
+///
+/// # Arguments
+///
+/// * `message` - The error message.
+/// * `name` - The host name (as formatted by [`known_hosts::host_name`]).
+/// * `expected` - The expected host key, in SSH wire format, if known.
+/// * `actual` - The host key the server presented, in SSH wire format.
+fn bad_host_key(message: String, name: &str, expected: Option<&[u8]>, actual: &[u8]) -> PyErr {
+    BadHostKeyException::new_err((
+        message,
+        name.to_string(),
+        expected.and_then(HostKey::from_blob),
+        HostKey::from_blob(actual),
+    ))
+}
+
+#[pyclass]
+#[derive(Clone, Copy)]
+/// Missing host key policy that refuses to connect to hosts whose key is not in `known_hosts`.
+pub struct RejectPolicy;
+
+#[pymethods]
+impl RejectPolicy {
+    #[new]
+    /// Creates a new [`RejectPolicy`].
+    pub fn __new__() -> Self {
+        Self
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Copy)]
+/// Missing host key policy that accepts hosts whose key is not in `known_hosts`, and adds their key
+/// to it.
+pub struct AutoAddPolicy {
+    /// Whether the host names of added keys are hashed.
+    #[pyo3(get)]
+    pub hashed: bool,
+}
+
+#[pymethods]
+impl AutoAddPolicy {
+    #[new]
+    #[pyo3(signature = (hashed=false))]
+    /// Creates a new [`AutoAddPolicy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed` - Whether to hash the host names of added keys, like OpenSSH's
+    ///   `HashKnownHosts yes`. Defaults to `false`.
+    pub fn __new__(hashed: bool) -> Self {
+        Self { hashed }
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Copy)]
+/// Missing host key policy that accepts hosts whose key is not in `known_hosts`, emitting a
+/// warning.
+pub struct WarningPolicy;
+
+#[pymethods]
+impl WarningPolicy {
+    #[new]
+    /// Creates a new [`WarningPolicy`].
+    pub fn __new__() -> Self {
+        Self
+    }
+}
+
+#[derive(FromPyObject, Clone, Copy)]
+/// What an [`SSHClient`] does when connecting to a host whose key is not in `known_hosts`.
+pub enum MissingHostKeyPolicy {
+    /// Refuse to connect.
+    #[pyo3(annotation = "RejectPolicy")]
+    Reject(RejectPolicy),
+    /// Connect, and add the host key to `known_hosts`.
+    #[pyo3(annotation = "AutoAddPolicy")]
+    AutoAdd(AutoAddPolicy),
+    /// Connect, emitting a warning.
+    #[pyo3(annotation = "WarningPolicy")]
+    Warning(WarningPolicy),
+}
+
+#[derive(Clone)]
+/// The arguments of [`SSHClient::connect`], kept by a connected client so that the connection can
+/// be re-established.
+pub(crate) struct ConnectArgs {
+    pub(crate) host: String,
+    pub(crate) username: String,
+    pub(crate) auth: Option<AuthMethods>,
+    pub(crate) port: Option<u16>,
+    pub(crate) timeout: Option<u32>,
+    pub(crate) family: Option<String>,
+    pub(crate) resolver: Option<PyObject>,
+    pub(crate) look_for_keys: bool,
+    pub(crate) expected_fingerprint: Option<String>,
+    pub(crate) host_key_algorithms: Option<Vec<String>>,
+    pub(crate) source_address: Option<(String, u16)>,
+    pub(crate) proxy: Option<String>,
+    pub(crate) jump: Option<Jump>,
+    pub(crate) use_ssh_config: bool,
+    pub(crate) compress: bool,
+    pub(crate) algorithms: Option<HashMap<String, Vec<String>>>,
+    pub(crate) tcp_options: Option<TcpOptions>,
+}
+
+impl ConnectArgs {
+    /// Creates the arguments for connecting to a host, with the defaults of
+    /// [`SSHClient::connect`] for everything else.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `username` - The SSH username.
+    pub(crate) fn new(host: String, username: String) -> Self {
+        Self {
+            host,
+            username,
+            auth: None,
+            port: None,
+            timeout: None,
+            family: None,
+            resolver: None,
+            look_for_keys: true,
+            expected_fingerprint: None,
+            host_key_algorithms: None,
+            source_address: None,
+            proxy: None,
+            jump: None,
+            use_ssh_config: false,
+            compress: false,
+            algorithms: None,
+            tcp_options: None,
+        }
+    }
+}
+
+#[pyclass]
+/// The SSH client.
+///
+/// The client can be shared by several threads: operations on its session, including those made
+/// through the command outputs, SFTP clients, files and forwarded ports opened on it, are run one at
+/// a time. Threads waiting for their turn release the GIL.
+pub struct SSHClient {
+    /// Established SSH session.
+    sess: Option<Session>,
+    /// Serializes the use of the session by several threads. Shared with the objects opened on the
+    /// session (e.g. command outputs and SFTP clients), and kept when the session is re-established.
+    lock: Arc<SessionLock>,
+    /// Number of times opening a channel is retried after a failure.
+    channel_retries: u32,
+    /// Delay between attempts to open a channel.
+    channel_retry_delay: Duration,
+    /// Number of times establishing a connection is retried after a transient failure.
+    connect_retries: u32,
+    /// Delay before the first retry of a connection, doubled for every further retry.
+    connect_retry_delay: Duration,
+    /// Maximum random delay added to every connection retry delay.
+    connect_retry_jitter: Duration,
+    /// Additional `known_hosts` files loaded with [`SSHClient::load_host_keys`].
+    host_key_files: Vec<PathBuf>,
+    /// What to do when connecting to a host whose key is not known.
+    missing_host_key_policy: MissingHostKeyPolicy,
+    /// Optional callable that verifies host keys instead of `known_hosts`.
+    host_key_verifier: Option<PyObject>,
+    /// Optional application-managed host key store, consulted instead of `known_hosts`.
+    host_key_store: Option<PyObject>,
+    /// CAs trusted to sign host certificates, added with [`SSHClient::add_host_ca`].
+    host_cas: KnownHosts,
+    /// Optional identification string sent to servers, set with
+    /// [`SSHClient::set_client_banner`].
+    client_banner: Option<String>,
+    /// Timeout for blocking operations on the session (in milliseconds, 0 for none), set with
+    /// [`SSHClient::set_timeout`].
+    operation_timeout: u32,
+    /// Maximum number of attempts to re-establish the session after the connection to the server
+    /// is lost (0 to never re-establish it).
+    reconnect_retries: u32,
+    /// Delay before the first attempt to re-establish the session, doubled for every further
+    /// attempt.
+    reconnect_delay: Duration,
+    /// The arguments of the last successful [`SSHClient::connect`] call.
+    connect_args: Option<ConnectArgs>,
+    /// Handle to the session's TCP connection, used to query transport statistics.
+    tcp: Option<TcpStream>,
+    /// When the session was established.
+    connected_at: Option<Instant>,
+    /// Number of channels opened by the client.
+    channels_opened: AtomicU64,
+}
+
+impl SSHClient {
+    /// Returns the established session, if any.
+    pub(crate) fn session(&self) -> Option<&Session> {
+        self.sess.as_ref()
+    }
+
+    /// Waits until no other thread is using the client's session, and returns a guard that keeps
+    /// other threads from using it until dropped (see [`SessionLock`]).
+    ///
+    /// The client is not borrowed while waiting, so that the thread using the session can still
+    /// borrow it mutably (e.g. to close the session).
+    ///
+    /// # Arguments
+    ///
+    /// * `slf` - The client.
+    /// * `py` - Python GIL token, released while waiting.
+    fn lock(slf: &PyCell<Self>, py: Python<'_>) -> PyResult<SessionGuard> {
+        let lock = Arc::clone(&slf.try_borrow()?.lock);
+
+        Ok(lock.acquire(py))
+    }
+
+    /// Sends the server a disconnect message, then closes the connection (see
+    /// [`SSHClient::disconnect`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Optional reason for disconnecting.
+    fn disconnect_session(&mut self, message: Option<&str>) -> PyResult<()> {
+        let Some(sess) = self.sess.take() else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+        let result = sess.disconnect(
+            Some(DisconnectCode::ByApplication),
+            message.unwrap_or(""),
+            None,
+        );
+
+        // Other objects (e.g. command outputs) may still hold the session, so the connection is
+        // shut down rather than left open until they are all dropped.
+        if let Some(tcp) = self.tcp.take() {
+            let _ = tcp.shutdown(Shutdown::Both);
+        }
+
+        self.connected_at.take();
+
+        result.map_err(excp_from_err)
+    }
+
+    /// Closes the session, if any (see [`SSHClient::close`]).
+    fn close_session(&mut self) {
+        if self.sess.is_some() {
+            let _ = self.disconnect_session(None);
+        }
+
+        self.tcp.take();
+        self.connected_at.take();
+    }
+
+    /// Establishes a session with the given [`SSHClient::connect`] arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `args` - The arguments.
+    pub(crate) fn connect_with(&mut self, py: Python<'_>, args: ConnectArgs) -> PyResult<()> {
+        self.connect(
+            py,
+            args.host,
+            Some(args.username),
+            args.auth,
+            args.port,
+            args.timeout,
+            args.family,
+            args.resolver,
+            args.look_for_keys,
+            args.expected_fingerprint,
+            args.host_key_algorithms,
+            args.source_address,
+            args.proxy,
+            args.jump,
+            args.use_ssh_config,
+            args.compress,
+            args.algorithms,
+            args.tcp_options,
+        )
+    }
+
+    /// Re-establishes the session after the connection to the server was lost, with the arguments
+    /// of the last [`SSHClient::connect`] call, according to the client's reconnect policy.
+    ///
+    /// Failed attempts are retried with an exponential backoff; the error of the last attempt is
+    /// raised if they all fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting between attempts.
+    fn reconnect(&mut self, py: Python<'_>) -> PyResult<()> {
+        let Some(args) = self.connect_args.clone() else {
+            return Err(SessionException::new_err(
+                "Clients connected with connect_socket() cannot be reconnected".to_string(),
+            ));
+        };
+        let mut attempts = 0;
+
+        // The lost session is unusable, so it is dropped even if it cannot be re-established.
+        self.close_session();
+
+        loop {
+            let delay = self
+                .reconnect_delay
+                .saturating_mul(2u32.pow(attempts.min(16)));
+            py.allow_threads(|| std::thread::sleep(delay));
+            py.check_signals()?;
+            attempts += 1;
+
+            match self.connect_with(py, args.clone()) {
+                Ok(()) if self.sess.is_some() => return Ok(()),
+                Ok(()) => {
+                    return Err(AuthenticationException::new_err(
+                        "Failed to authenticate while reconnecting".to_string(),
+                    ))
+                }
+                Err(_) if attempts < self.reconnect_retries => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Opens a new session channel, retrying according to the client's channel retry policy.
+    ///
+    /// Failures are raised as [`ChannelException`]; the session itself remains usable.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting between attempts.
+    /// * `sess` - The session to open the channel on.
+    fn open_channel(&self, py: Python<'_>, sess: &Session) -> PyResult<Channel> {
+        let mut attempts = 0;
+
+        loop {
+            match sess.channel_session() {
+                Ok(chan) => {
+                    self.channels_opened.fetch_add(1, Ordering::Relaxed);
+
+                    return Ok(chan);
+                }
+                // Only the server refusing the channel is worth retrying, not e.g. a lost
+                // connection.
+                Err(err)
+                    if attempts < self.channel_retries
+                        && matches!(
+                            err.code(),
+                            ErrorCode::Session(
+                                LIBSSH2_ERROR_CHANNEL_FAILURE | LIBSSH2_ERROR_EAGAIN
+                            )
+                        ) =>
+                {
+                    attempts += 1;
+
+                    let delay = self.channel_retry_delay;
+                    py.allow_threads(|| std::thread::sleep(delay));
+                    py.check_signals()?;
+                }
+                Err(err) => {
+                    return Err(ChannelException::new_err(format!(
+                        "Failed to open channel: {}",
+                        err
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Connects to an SSH server and performs the SSH handshake (see [`open_session`]), retrying
+    /// according to the client's connection retry policy.
+    ///
+    /// Only transient failures (OS-level connection errors, timeouts and handshake failures) are
+    /// retried, with an exponential backoff.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token, released while waiting between attempts.
+    /// * `host` - The host name or address.
+    /// * `port` - The SSH port.
+    /// * `timeout` - The timeout for the TCP connection (in seconds).
+    /// * `options` - Options for establishing the connection.
+    fn open_session_retrying(
+        &self,
+        py: Python<'_>,
+        host: &str,
+        port: u16,
+        timeout: u32,
+        options: &TransportOptions,
+    ) -> PyResult<(Session, Option<TcpStream>)> {
+        let mut attempts = 0;
+
+        loop {
+            match open_session(py, host, port, timeout, options) {
+                Err(err)
+                    if attempts < self.connect_retries
+                        && (err.is_instance_of::<PyIOError>(py)
+                            || err.is_instance_of::<SessionException>(py)) =>
+                {
+                    let delay = self
+                        .connect_retry_delay
+                        .saturating_mul(2u32.pow(attempts.min(16)))
+                        .saturating_add(random_delay(self.connect_retry_jitter));
+                    attempts += 1;
+
+                    py.allow_threads(|| std::thread::sleep(delay));
+                    py.check_signals()?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Connects to a server through jump hosts and performs the SSH handshake, without
+    /// authenticating to the server.
+    ///
+    /// The first jump host is connected to directly (see [`SSHClient::open_session_retrying`]),
+    /// and each following one through a `direct-tcpip` channel opened on the previous one, like
+    /// the server itself. Each jump host has its host key verified like the server's, and is
+    /// authenticated to before going further.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `jumps` - The jump hosts, in order.
+    /// * `host` - The server's host name or address.
+    /// * `port` - The server's SSH port.
+    /// * `timeout` - The timeout for the TCP connection to the first jump host (in seconds).
+    /// * `options` - Options for establishing the connection.
+    #[cfg(unix)]
+    fn open_session_via(
+        &self,
+        py: Python<'_>,
+        jumps: Vec<JumpHost>,
+        host: &str,
+        port: u16,
+        timeout: u32,
+        options: &TransportOptions,
+    ) -> PyResult<Session> {
+        let jump_port = |jump: &JumpHost| -> u16 {
+            jump.port
+                .or(defaults::lookup(&jump.host).port)
+                .unwrap_or(DEFAULT_PORT)
+        };
+        let mut jumps = jumps.into_iter();
+        let Some(mut current) = jumps.next() else {
+            return Err(PyValueError::new_err("No jump hosts given".to_string()));
+        };
+
+        // The host key algorithms are meant for the server, not the jump hosts.
+        let jump_options = TransportOptions {
+            host_key_algorithms: None,
+            ..options.clone()
+        };
+        let (mut sess, tcp) = self.open_session_retrying(
+            py,
+            &current.host,
+            jump_port(&current),
+            timeout,
+            &jump_options,
+        )?;
+        let mut transport = tcp.map(OwnedFd::from);
+
+        loop {
+            self.log_in_to_jump_host(py, &sess, &current, jump_port(&current))?;
+
+            let next = jumps.next();
+            let (next_host, next_port) = match &next {
+                Some(jump) => (net::strip_brackets(&jump.host), jump_port(jump)),
+                None => (net::strip_brackets(host), port),
+            };
+            let Some(fd) = transport.take() else {
+                return Err(SessionException::new_err(format!(
+                    "Failed to duplicate the connection to jump host {}",
+                    current.host
+                )));
+            };
+            let local = jump::open_tunnel(&sess, fd, next_host, next_port)?;
+            transport = local.try_clone().ok().map(OwnedFd::from);
+
+            sess = match next {
+                Some(_) => new_session(&jump_options)?,
+                None => new_session(options)?,
+            };
+            sess.set_tcp_stream(local);
+            sess.handshake().map_err(excp_from_err)?;
+
+            match next {
+                Some(jump) => current = jump,
+                None => return Ok(sess),
+            }
+        }
+    }
+
+    /// Connects to a server through jump hosts and performs the SSH handshake.
+    ///
+    /// Jump hosts are only supported on Unix platforms.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `jumps` - The jump hosts, in order.
+    /// * `host` - The server's host name or address.
+    /// * `port` - The server's SSH port.
+    /// * `timeout` - The timeout for the TCP connection to the first jump host (in seconds).
+    /// * `options` - Options for establishing the connection.
+    #[cfg(not(unix))]
+    fn open_session_via(
+        &self,
+        _py: Python<'_>,
+        _jumps: Vec<JumpHost>,
+        _host: &str,
+        _port: u16,
+        _timeout: u32,
+        _options: &TransportOptions,
+    ) -> PyResult<Session> {
+        Err(SessionException::new_err(
+            "Jump hosts are not supported on this platform".to_string(),
+        ))
+    }
+
+    /// Verifies the host key of a jump host and authenticates to it.
+    ///
+    /// Settings that the jump host leaves out fall back to the defaults registered for it with
+    /// [`set_default`](crate::defaults::set_default), then to the local user and the default
+    /// private keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `sess` - The session to the jump host, after the handshake.
+    /// * `jump` - The jump host.
+    /// * `port` - The jump host's SSH port.
+    #[cfg(unix)]
+    fn log_in_to_jump_host(
+        &self,
+        py: Python<'_>,
+        sess: &Session,
+        jump: &JumpHost,
+        port: u16,
+    ) -> PyResult<()> {
+        self.verify_host_key(py, sess, &jump.host, port, None)?;
+
+        let Some(username) = jump.username.clone().or_else(defaults::local_username) else {
+            return Err(PyValueError::new_err(format!(
+                "No username provided for jump host {} and the local user could not be determined",
+                jump.host
+            )));
+        };
+        let authenticated = match jump.auth.clone().or(defaults::lookup(&jump.host).auth) {
+            Some(auth) => authenticate(py, sess, &username, auth)?,
+            None => authenticate_default_keys(py, sess, &username, &keys::default_keys())?,
+        };
+
+        if !authenticated {
+            return Err(SessionException::new_err(format!(
+                "No authentication methods provided for jump host {}",
+                jump.host
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Verifies the host key presented by a server against the user's `known_hosts` file, the
+    /// files loaded with [`SSHClient::load_host_keys`] and any `known_hosts` file registered for
+    /// the host with [`set_default`](crate::defaults::set_default).
+    ///
+    /// Fails with a [`BadHostKeyException`] if the host is known with a different key. Unknown
+    /// hosts are handled according to the client's missing host key policy.
+    ///
+    /// If an expected fingerprint is given, the key is checked against it instead. Otherwise, if
+    /// the client has a host key verifier, the key is checked by calling it, and if it has a host
+    /// key store, the store is consulted instead of `known_hosts`.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `sess` - The session, after the handshake.
+    /// * `host` - The host name or address.
+    /// * `port` - The SSH port.
+    /// * `expected_fingerprint` - Optional SHA256 fingerprint (`SHA256:...`) the key must have.
+    fn verify_host_key(
+        &self,
+        py: Python<'_>,
+        sess: &Session,
+        host: &str,
+        port: u16,
+        expected_fingerprint: Option<&str>,
+    ) -> PyResult<()> {
+        let Some((key, _)) = sess.host_key() else {
+            return Err(SessionException::new_err(
+                "Server did not provide a host key".to_string(),
+            ));
+        };
+        let host = net::strip_brackets(host);
+        let name = known_hosts::host_name(host, port);
+        let fingerprint = keys::fingerprint(key);
+
+        if let Some(expected) = expected_fingerprint {
+            // ssh-keygen omits the base64 padding, but other tools may not.
+            if fingerprint != expected.trim_end_matches('=') {
+                return Err(bad_host_key(
+                    format!(
+                        "Host key for {name} does not match the expected fingerprint (expected \
+                         {expected}, got {fingerprint})"
+                    ),
+                    &name,
+                    None,
+                    key,
+                ));
+            }
+
+            return Ok(());
+        }
+
+        if let Some(verifier) = &self.host_key_verifier {
+            let key_type = keys::public_key_type(key).unwrap_or_default();
+
+            let accepted: bool = verifier
+                .call1(py, (&name, key_type, PyBytes::new(py, key), &fingerprint))?
+                .extract(py)?;
+
+            if !accepted {
+                return Err(bad_host_key(
+                    format!(
+                        "Host key for {name} was rejected by the host key verifier (got \
+                         {fingerprint})"
+                    ),
+                    &name,
+                    None,
+                    key,
+                ));
+            }
+
+            return Ok(());
+        }
+
+        // Certificates are checked against stores, and added to them, as the plain key they
+        // certify.
+        let plain = Certificate::parse(key).map(|certificate| certificate.public_key);
+        let plain = plain.as_deref().unwrap_or(key);
+        let default_path = known_hosts::default_path();
+        let host_known_hosts = defaults::lookup(host).known_hosts;
+        let (check, source) = match &self.host_key_store {
+            Some(store) => match self.host_cas.check_key(py, host, port, key)? {
+                HostKeyCheck::NotFound => (
+                    known_hosts::check_store(py, store, host, port, plain)?,
+                    "the host key store",
+                ),
+                check => (check, "the trusted host CAs"),
+            },
+            None => {
+                let mut known = self.host_cas.clone();
+
+                if let Some(path) = default_path.as_ref().filter(|path| path.is_file()) {
+                    known.load_file(path).map_err(excp_from_err)?;
+                }
+
+                for path in &self.host_key_files {
+                    known.load_file(path).map_err(excp_from_err)?;
+                }
+
+                if let Some(path) = host_known_hosts.as_ref().filter(|path| path.is_file()) {
+                    known.load_file(path).map_err(excp_from_err)?;
+                }
+
+                (known.check_key(py, host, port, key)?, "known_hosts")
+            }
+        };
+
+        match check {
+            HostKeyCheck::Mismatch(expected) => {
+                known_hosts::report_host_key_change(py, &name, &expected, key);
+
+                Err(bad_host_key(
+                    format!(
+                        "Host key for {name} does not match the one in {source} (expected {}, \
+                         got {fingerprint}); this may indicate a man-in-the-middle attack",
+                        keys::fingerprint(&expected)
+                    ),
+                    &name,
+                    Some(&expected),
+                    key,
+                ))
+            }
+            HostKeyCheck::Match => Ok(()),
+            HostKeyCheck::Revoked => Err(bad_host_key(
+                format!("Host key for {name} is revoked in {source} (got {fingerprint})"),
+                &name,
+                None,
+                key,
+            )),
+            HostKeyCheck::InvalidCertificate(reason) => Err(bad_host_key(
+                format!("Host certificate for {name} is not valid: {reason}"),
+                &name,
+                None,
+                key,
+            )),
+            HostKeyCheck::NotFound => match self.missing_host_key_policy {
+                MissingHostKeyPolicy::Reject(_) => Err(bad_host_key(
+                    format!("Host {name} is not in {source} (key {fingerprint})"),
+                    &name,
+                    None,
+                    key,
+                )),
+                MissingHostKeyPolicy::AutoAdd(policy) => {
+                    if let Some(store) = &self.host_key_store {
+                        store.call_method1(py, "set", (host, port, HostKey::from_blob(plain)))?;
+
+                        return Ok(());
+                    }
+
+                    // Keys are added to the file registered for the host, or else to the most
+                    // recently loaded file, like OpenSSH's `UserKnownHostsFile`.
+                    let Some(path) = host_known_hosts
+                        .as_ref()
+                        .or(self.host_key_files.last())
+                        .or(default_path.as_ref())
+                    else {
+                        return Err(SessionException::new_err(
+                            "No known_hosts file to add the host key to".to_string(),
+                        ));
+                    };
+
+                    known_hosts::append(path, host, port, plain, policy.hashed)
+                        .map_err(excp_from_err)
+                }
+                MissingHostKeyPolicy::Warning(_) => PyErr::warn(
+                    py,
+                    py.get_type::<PyUserWarning>(),
+                    &format!("Unknown host key for {name}: {fingerprint}"),
+                    1,
+                ),
+            },
+        }
+    }
+
+    /// Opens a new session channel and starts the command on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `sess` - The session to open the channel on.
+    /// * `command` - The command to run.
+    /// * `pty` - Optional options for allocating a PTY for the command.
+    fn start_command(
+        &self,
+        py: Python<'_>,
+        sess: &Session,
+        command: &str,
+        pty: Option<&PtyOptions>,
+    ) -> PyResult<Channel> {
+        let mut chan = self.open_channel(py, sess)?;
+
+        if let Some(pty) = pty {
+            pty.request(&mut chan)?;
+        }
+
+        chan.exec(command).map_err(|err| {
+            ChannelException::new_err(format!("Failed to execute command: {}", err))
+        })?;
+
+        Ok(chan)
+    }
+
+    /// Checks that a directory exists on the server, raising a `FileNotFoundError` if it does not.
     ///
-    /// If the specified directory is `None`, then the current working directory is unset.
+    /// # Arguments
     ///
-    /// Once the current working directory is set, all SFTP operations will be relative to this path.
+    /// * `py` - Python GIL token.
+    /// * `sess` - The session to check the directory with.
+    /// * `path` - The remote directory.
+    fn check_remote_dir(&self, py: Python<'_>, sess: &Session, path: &str) -> PyResult<()> {
+        let mut chan =
+            self.start_command(py, sess, &format!("test -d {}", shell_quote(path)), None)?;
+        chan.wait_eof().map_err(excp_from_err)?;
+        chan.wait_close().map_err(excp_from_err)?;
+
+        if chan.exit_status().map_err(excp_from_err)? != 0 {
+            return Err(PyFileNotFoundError::new_err(format!(
+                "No such remote directory: {path}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Starts an audit record for a command executed by the client.
     ///
-    /// **NOTE**: SFTP does not have a concept of a "current working directory", and so, this function
-    /// tries to emulate it. Currently, only **absolute** paths are supported. This *MAY* change in the
-    /// future, but is not guaranteed.
+    /// Returns `None` if no audit hook is registered.
     ///
     /// # Arguments
     ///
-    /// * `dir` - The directory to change to.
-    pub fn chdir(&mut self, dir: Option<String>) -> PyResult<()> {
-        if let Some(client) = self.client.as_mut() {
-            if let Some(path) = &dir {
-                let path = Path::new(&path);
-
-                if let Err(_) = client.opendir(path) {
-                    return Err(io::Error::new(
-                        ErrorKind::NotFound,
-                        format!("Path {} does not exist on server", path.display()),
-                    ))
-                    .map_err(excp_from_err)?;
+    /// * `command` - The command.
+    fn audit(&self, command: &str) -> Option<AuditRecord> {
+        let args = self.connect_args.as_ref()?;
+
+        AuditRecord::start(&args.host, &args.username, command)
+    }
+
+    /// Runs a command to completion and returns its `stdout` contents and exit status.
+    ///
+    /// The command's `stderr` is discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - Python GIL token.
+    /// * `command` - The command to run.
+    fn run(&self, py: Python<'_>, command: &str) -> PyResult<(Vec<u8>, i32)> {
+        let Some(sess) = &self.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+
+        let audit = self.audit(command);
+        let mut chan = self.start_command(py, sess, command, None)?;
+
+        let mut stdout = Vec::new();
+        drain_outputs(py, sess, &mut chan, |chunk| {
+            stdout.extend_from_slice(chunk);
+
+            Ok(())
+        })?;
+
+        chan.wait_close().map_err(excp_from_err)?;
+        let exit_status = chan.exit_status().map_err(excp_from_err)?;
+
+        if let Some(audit) = audit {
+            audit.finish(py, Some(exit_status));
+        }
+
+        Ok((stdout, exit_status))
+    }
+}
+
+/// Generates a unique path for a remote temporary file.
+///
+/// # Arguments
+///
+/// * `purpose` - What the file is used for (e.g. `"stderr"`), included in its name.
+fn temp_path(purpose: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+
+    format!(
+        "/tmp/russh-{purpose}-{}-{}-{}",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Generates a unique path for a local temporary file next to `path`, so that it can be renamed
+/// over `path` once complete.
+///
+/// # Arguments
+///
+/// * `path` - The local path the temporary file stands in for.
+fn local_temp_path(path: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    format!(
+        "{path}.russh-{}-{}.part",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Returns a pseudo-random delay between zero and `max`, used to spread out retries.
+///
+/// # Arguments
+///
+/// * `max` - The maximum delay.
+fn random_delay(max: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+
+    max.mul_f64(f64::from(nanos % 1000) / 1000.0)
+}
+
+/// Parses the output of `env`, `printenv` or `set` into a map of environment variables.
+///
+/// Lines that do not start a new `NAME=value` pair are treated as continuations of the previous
+/// value (multi-line values).
+///
+/// # Arguments
+///
+/// * `output` - The command output.
+fn parse_environ(output: &str) -> HashMap<String, String> {
+    let mut environ = HashMap::new();
+    let mut last: Option<String> = None;
+
+    for line in output.lines() {
+        let entry = line.split_once('=').filter(|(name, _)| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '(' || c == ')')
+        });
+
+        match (entry, &last) {
+            (Some((name, value)), _) => {
+                environ.insert(name.to_string(), value.to_string());
+                last = Some(name.to_string());
+            }
+            (None, Some(name)) => {
+                if let Some(value) = environ.get_mut(name) {
+                    value.push('\n');
+                    value.push_str(line);
                 }
             }
+            (None, None) => {}
+        }
+    }
 
-            self.cwd = dir;
-        } else {
-            return Err(SFTPException::new_err("SFTP session not open".to_string()));
+    environ
+}
+
+#[pymethods]
+impl SSHClient {
+    #[new]
+    /// Creates a new [`SSHClient`].
+    pub fn __new__() -> Self {
+        Self {
+            sess: None,
+            lock: SessionLock::new(),
+            channel_retries: 0,
+            channel_retry_delay: DEFAULT_CHANNEL_RETRY_DELAY,
+            connect_retries: 0,
+            connect_retry_delay: DEFAULT_CONNECT_RETRY_DELAY,
+            connect_retry_jitter: Duration::ZERO,
+            host_key_files: Vec::new(),
+            missing_host_key_policy: MissingHostKeyPolicy::Warning(WarningPolicy),
+            host_key_verifier: None,
+            host_key_store: None,
+            host_cas: KnownHosts::default(),
+            client_banner: None,
+            operation_timeout: 0,
+            reconnect_retries: 0,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+            connect_args: None,
+            tcp: None,
+            connected_at: None,
+            channels_opened: AtomicU64::new(0),
+        }
+    }
+
+    /// Establishes an SSH connection and sets the created session on the client.
+    ///
+    /// If multiple authentication methods are specified, then they are all attempted one at a time
+    /// (until one succeeds) in the following order, unless [`AuthMethods`] specifies another one:
+    ///
+    /// [`PasswordAuth`] > [`PrivateKeyAuth`] > [`PrivateKeyMemoryAuth`] > [`AgentAuth`] >
+    /// [`HostBasedAuth`] > [`KeyboardInteractiveAuth`]
+    ///
+    /// If all the authentication methods fail, an [`AuthenticationException`] is raised, carrying
+    /// the message from the last attempted method, the names of the attempted methods and the
+    /// methods the server still allows.
+    ///
+    /// If the server requires several methods (e.g. a public key *and* a password), the remaining
+    /// methods are attempted again after each partial success, until the session is fully
+    /// authenticated.
+    ///
+    /// Before authenticating, the server's host key is verified against `~/.ssh/known_hosts` and
+    /// the files loaded with [`SSHClient::load_host_keys`]. If the host is known with a different
+    /// key, a [`BadHostKeyException`] is raised; unknown hosts are handled according to the
+    /// missing host key policy (see [`SSHClient::set_missing_host_key_policy`]). Host certificates
+    /// signed by a CA added with [`SSHClient::add_host_ca`] are validated instead. A host key store
+    /// set with [`SSHClient::set_host_key_store`] is consulted instead of the `known_hosts` files.
+    /// A host key verifier set with [`SSHClient::set_host_key_verifier`] replaces these checks,
+    /// and `expected_fingerprint`, if given, replaces all of them.
+    ///
+    /// Arguments that are omitted fall back to the defaults registered for the host with
+    /// [`set_default`](crate::defaults::set_default), if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address. IPv6 addresses may be enclosed in brackets (e.g.
+    ///   `"[::1]"`). A `unix://` URL (e.g. `"unix:///run/sshd.sock"`) connects to a server
+    ///   listening on a Unix socket instead (only supported on Unix platforms), whose host key is
+    ///   looked up in `known_hosts` under the URL; `port` is then only used for that lookup.
+    /// * `username` - The SSH username. Defaults to the local user.
+    /// * `auth` - The authentication methods to use. Required unless defaults provide them, or the
+    ///   default private keys are looked for.
+    /// * `port` The SSH port. Defaults to 22.
+    /// * `timeout` - The timeout for the TCP connection (in seconds). Defaults to 30.
+    /// * `family` - The address family to connect over: `"any"`, `"ipv4"` or `"ipv6"`. Defaults to
+    ///   `"any"`, in which case connection attempts to the host's IPv6 and IPv4 addresses are
+    ///   raced, so that a broken path does not delay the connection. If the host has several
+    ///   addresses and none can be connected to, a [`NoValidConnectionsError`] listing every
+    ///   attempt is raised.
+    /// * `resolver` - Optional callable used instead of the system resolver. It is called with the
+    ///   host and must return a list of `(ip, port)` tuples to connect to; `port` is then ignored.
+    /// * `look_for_keys` - Whether to try the default private keys (`~/.ssh/id_ed25519`,
+    ///   `~/.ssh/id_ecdsa` and `~/.ssh/id_rsa`, like OpenSSH does) when no authentication methods
+    ///   are provided. Keys protected by a passphrase are skipped. Defaults to `true`.
+    /// * `expected_fingerprint` - Optional SHA256 fingerprint of the server's host key, as printed
+    ///   by `ssh-keygen -l` (e.g. `"SHA256:nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8"`). If the
+    ///   key does not match, a [`BadHostKeyException`] is raised before authenticating.
+    /// * `host_key_algorithms` - Optional host key algorithms to negotiate, in order of preference
+    ///   (e.g. `["ssh-ed25519", "rsa-sha2-512"]`), so that the server presents the key that is in
+    ///   `known_hosts`. Algorithms that are not listed are disabled. List a
+    ///   `*-cert-v01@openssh.com` algorithm first to have the server present its host certificate.
+    ///   Defaults to the `libssh2` defaults.
+    /// * `source_address` - Optional `(ip, port)` tuple of the local address to connect from, e.g.
+    ///   on hosts with several interfaces where firewalls only allow a specific source address.
+    ///   Use port 0 to let the system pick a port. Only addresses of the same family are connected
+    ///   to. Only supported on Unix platforms.
+    /// * `proxy` - Optional URL of a proxy server to tunnel the connection through, for networks
+    ///   where direct connections are blocked: `socks5://[user[:password]@]host[:port]` for a
+    ///   SOCKS5 proxy (the port defaults to 1080), or `http://[user[:password]@]host[:port]` for
+    ///   an HTTP proxy supporting the `CONNECT` method (the port defaults to 8080). Special
+    ///   characters in the username and password must be percent-encoded. The proxy resolves the
+    ///   host; `family`, `resolver` and `source_address` then apply to the connection to the
+    ///   proxy.
+    /// * `jump` - Optional [`JumpHost`] (bastion) to reach the host through, or list of jump hosts
+    ///   to go through in order, like OpenSSH's `ProxyJump`. Each jump host's key is verified like
+    ///   the host's. The connection options (`timeout`, `family`, `resolver`, `source_address` and
+    ///   `proxy`) then apply to the connection to the first jump host. Only supported on Unix
+    ///   platforms.
+    /// * `use_ssh_config` - Whether to apply the options of `~/.ssh/config` that match the host
+    ///   (see [`SSHConfig`]): `HostName`, `User`, `Port`, `IdentityFile` (used instead of the
+    ///   default private keys when no authentication methods are provided) and `ProxyJump`.
+    ///   Arguments that are given take precedence. Defaults to `false`.
+    /// * `compress` - Whether to request `zlib` compression of the transport during negotiation,
+    ///   which speeds up bulk transfers over slow links. The connection is not compressed if the
+    ///   server does not support it. Defaults to `false`.
+    /// * `algorithms` - Optional algorithms to negotiate, as a dictionary mapping a type of
+    ///   algorithms (`"kex"`, `"cipher"`, `"mac"` or `"compression"`) to the algorithms of that
+    ///   type, in order of preference (e.g. `{"cipher": ["chacha20-poly1305@openssh.com",
+    ///   "aes256-gcm@openssh.com"]}`), so that legacy algorithms can be disabled per policy.
+    ///   Algorithms that are not listed are disabled; types that are not given keep the `libssh2`
+    ///   defaults. Also applies to the jump hosts.
+    /// * `tcp_options` - Optional socket options for the TCP connection (see [`TcpOptions`]), e.g.
+    ///   `TCP_NODELAY` for interactive sessions or larger buffers for bulk transfers. When going
+    ///   through a proxy or jump hosts, they apply to the connection to the proxy or the first jump
+    ///   host.
+    #[pyo3(signature = (
+        host,
+        username=None,
+        auth=None,
+        port=None,
+        timeout=None,
+        family=None,
+        resolver=None,
+        look_for_keys=true,
+        expected_fingerprint=None,
+        host_key_algorithms=None,
+        source_address=None,
+        proxy=None,
+        jump=None,
+        use_ssh_config=false,
+        compress=false,
+        algorithms=None,
+        tcp_options=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn connect(
+        &mut self,
+        py: Python<'_>,
+        host: String,
+        username: Option<String>,
+        auth: Option<AuthMethods>,
+        port: Option<u16>,
+        timeout: Option<u32>,
+        family: Option<String>,
+        resolver: Option<PyObject>,
+        look_for_keys: bool,
+        expected_fingerprint: Option<String>,
+        host_key_algorithms: Option<Vec<String>>,
+        source_address: Option<(String, u16)>,
+        proxy: Option<String>,
+        jump: Option<Jump>,
+        use_ssh_config: bool,
+        compress: bool,
+        algorithms: Option<HashMap<String, Vec<String>>>,
+        tcp_options: Option<TcpOptions>,
+    ) -> PyResult<()> {
+        // The session is replaced once operations of other threads on the current one are done.
+        let lock = Arc::clone(&self.lock);
+        let _guard = lock.acquire(py);
+        let ssh_config = match use_ssh_config {
+            true => Some(SSHConfig::load_default().map_err(excp_from_err)?),
+            false => None,
+        };
+        let host_config = ssh_config
+            .as_ref()
+            .map(|config| config.host_config(&host))
+            .unwrap_or_default();
+        let Some(username) = username
+            .or_else(|| host_config.user.clone())
+            .or_else(defaults::local_username)
+        else {
+            return Err(PyValueError::new_err(
+                "No username provided and the local user could not be determined".to_string(),
+            ));
+        };
+
+        if let Some(fingerprint) = &expected_fingerprint {
+            if !fingerprint.starts_with("SHA256:") {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid host key fingerprint: {fingerprint} (expected SHA256:...)"
+                )));
+            }
+        }
+
+        let args = ConnectArgs {
+            host: host.clone(),
+            username: username.clone(),
+            auth: auth.clone(),
+            port,
+            timeout,
+            family: family.clone(),
+            resolver: resolver.clone(),
+            look_for_keys,
+            expected_fingerprint: expected_fingerprint.clone(),
+            host_key_algorithms: host_key_algorithms.clone(),
+            source_address: source_address.clone(),
+            proxy: proxy.clone(),
+            jump: jump.clone(),
+            use_ssh_config,
+            compress,
+            algorithms: algorithms.clone(),
+            tcp_options: tcp_options.clone(),
+        };
+        let options = TransportOptions {
+            family: match family.as_deref() {
+                Some(name) => AddressFamily::from_name(name).ok_or_else(|| {
+                    PyValueError::new_err(format!("Invalid address family: {name}"))
+                })?,
+                None => AddressFamily::Any,
+            },
+            resolver,
+            host_key_algorithms,
+            source_address: source_address
+                .map(|(ip, port)| parse_ip(&ip).map(|ip| SocketAddr::new(ip, port)))
+                .transpose()?,
+            proxy: proxy
+                .map(|url| {
+                    // The URL is left out of the message, as it may contain a password.
+                    Proxy::parse(&url).ok_or_else(|| {
+                        PyValueError::new_err(
+                            "Invalid proxy URL (expected a socks5:// or http:// URL)".to_string(),
+                        )
+                    })
+                })
+                .transpose()?,
+            compress,
+            algorithms,
+            banner: self.client_banner.clone(),
+            tcp: tcp_options,
+        };
+        let defaults = defaults::lookup(&host);
+        let auth = auth.or(defaults.auth);
+        let identity_files: Vec<PathBuf> = host_config
+            .identity_files
+            .into_iter()
+            .filter(|path| path.is_file())
+            .collect();
+        let default_keys = match auth {
+            None if !identity_files.is_empty() => identity_files,
+            None if look_for_keys => keys::default_keys(),
+            _ => Vec::new(),
+        };
+
+        if auth.is_none() && default_keys.is_empty() {
+            return Err(SessionException::new_err(
+                "No authentication methods provided".to_string(),
+            ));
+        }
+
+        let host = host_config.hostname.unwrap_or(host);
+        let port = port
+            .or(host_config.port)
+            .or(defaults.port)
+            .unwrap_or(DEFAULT_PORT);
+        let timeout = timeout.or(defaults.timeout).unwrap_or(DEFAULT_TIMEOUT);
+        let jumps = match (jump, &host_config.proxy_jump, &ssh_config) {
+            (Some(jump), _, _) => jump.into_hosts(),
+            (None, Some(proxy_jump), Some(config)) => config.jump_hosts(proxy_jump),
+            _ => Vec::new(),
+        };
+
+        if !jumps.is_empty() && net::unix_socket_path(&host).is_some() {
+            return Err(PyValueError::new_err(
+                "Unix socket hosts cannot be reached through jump hosts".to_string(),
+            ));
+        }
+
+        let (sess, stats_handle) = match jumps.is_empty() {
+            true => self.open_session_retrying(py, &host, port, timeout, &options)?,
+            false => (
+                self.open_session_via(py, jumps, &host, port, timeout, &options)?,
+                None,
+            ),
+        };
+        sess.set_timeout(self.operation_timeout);
+
+        self.verify_host_key(py, &sess, &host, port, expected_fingerprint.as_deref())?;
+
+        let authenticated = match auth {
+            Some(auth) => authenticate(py, &sess, &username, auth)?,
+            None => authenticate_default_keys(py, &sess, &username, &default_keys)?,
+        };
+
+        if authenticated {
+            self.sess = Some(sess);
+            self.connect_args = Some(args);
+            self.tcp = stats_handle;
+            self.connected_at = Some(Instant::now());
+            self.channels_opened = AtomicU64::new(0);
+        }
+
+        Ok(())
+    }
+
+    /// Establishes an SSH session over an already connected socket and sets it on the client,
+    /// e.g. when the connection is made through a custom proxy, or for testing.
+    ///
+    /// Authentication works like with [`SSHClient::connect`]. The socket's file descriptor is
+    /// duplicated, so the socket may be closed afterwards without affecting the session; it is
+    /// switched to blocking mode.
+    ///
+    /// **NOTE**: Clients connected this way cannot be cloned with [`SSHClient::clone_client`].
+    /// Only supported on Unix platforms.
+    ///
+    /// # Arguments
+    ///
+    /// * `sock` - The connected socket: a socket object (e.g. a `socket.socket`) or a raw file
+    ///   descriptor.
+    /// * `host` - The host name or address the socket is connected to, used to verify the host key
+    ///   and to look up defaults.
+    /// * `username` - The SSH username. Defaults to the local user.
+    /// * `auth` - The authentication methods to use. Required unless defaults provide them, or the
+    ///   default private keys are looked for.
+    /// * `port` The SSH port the socket is connected to, used to verify the host key. Defaults to
+    ///   22.
+    /// * `look_for_keys` - Whether to try the default private keys when no authentication methods
+    ///   are provided. Defaults to `true`.
+    /// * `expected_fingerprint` - Optional SHA256 fingerprint of the server's host key (see
+    ///   [`SSHClient::connect`]).
+    /// * `host_key_algorithms` - Optional host key algorithms to negotiate, in order of preference
+    ///   (see [`SSHClient::connect`]).
+    /// * `compress` - Whether to request `zlib` compression of the transport. Defaults to `false`.
+    /// * `algorithms` - Optional algorithms to negotiate, by type (see [`SSHClient::connect`]).
+    #[pyo3(signature = (
+        sock,
+        host,
+        username=None,
+        auth=None,
+        port=None,
+        look_for_keys=true,
+        expected_fingerprint=None,
+        host_key_algorithms=None,
+        compress=false,
+        algorithms=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn connect_socket(
+        &mut self,
+        py: Python<'_>,
+        sock: SocketLike,
+        host: String,
+        username: Option<String>,
+        auth: Option<AuthMethods>,
+        port: Option<u16>,
+        look_for_keys: bool,
+        expected_fingerprint: Option<String>,
+        host_key_algorithms: Option<Vec<String>>,
+        compress: bool,
+        algorithms: Option<HashMap<String, Vec<String>>>,
+    ) -> PyResult<()> {
+        // The session is replaced once operations of other threads on the current one are done.
+        let lock = Arc::clone(&self.lock);
+        let _guard = lock.acquire(py);
+        let Some(username) = username.or_else(defaults::local_username) else {
+            return Err(PyValueError::new_err(
+                "No username provided and the local user could not be determined".to_string(),
+            ));
+        };
+
+        if let Some(fingerprint) = &expected_fingerprint {
+            if !fingerprint.starts_with("SHA256:") {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid host key fingerprint: {fingerprint} (expected SHA256:...)"
+                )));
+            }
+        }
+
+        let defaults = defaults::lookup(&host);
+        let auth = auth.or(defaults.auth);
+        let default_keys = match auth {
+            None if look_for_keys => keys::default_keys(),
+            _ => Vec::new(),
+        };
+
+        if auth.is_none() && default_keys.is_empty() {
+            return Err(SessionException::new_err(
+                "No authentication methods provided".to_string(),
+            ));
+        }
+
+        let port = port.or(defaults.port).unwrap_or(DEFAULT_PORT);
+        let options = TransportOptions {
+            host_key_algorithms,
+            compress,
+            algorithms,
+            banner: self.client_banner.clone(),
+            ..Default::default()
+        };
+        let stream = stream_from_fd(sock.fileno(py)?)?;
+        let stats_handle = stream.try_clone().ok();
+        let mut sess = new_session(&options)?;
+        sess.set_tcp_stream(stream);
+        sess.handshake().map_err(excp_from_err)?;
+        sess.set_timeout(self.operation_timeout);
+
+        self.verify_host_key(py, &sess, &host, port, expected_fingerprint.as_deref())?;
+
+        let authenticated = match auth {
+            Some(auth) => authenticate(py, &sess, &username, auth)?,
+            None => authenticate_default_keys(py, &sess, &username, &default_keys)?,
+        };
+
+        if authenticated {
+            self.sess = Some(sess);
+            self.connect_args = None;
+            self.tcp = stats_handle;
+            self.connected_at = Some(Instant::now());
+            self.channels_opened = AtomicU64::new(0);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the authentication methods that a server accepts for a user (e.g. `publickey`,
+    /// `password` or `keyboard-interactive`), by connecting to it and making a `none`
+    /// authentication attempt.
+    ///
+    /// The connection is closed again afterwards; the client itself is not affected. Returns an
+    /// empty list if the server accepts the `none` method, i.e. requires no authentication.
+    ///
+    /// Arguments that are omitted fall back to the defaults registered for the host with
+    /// [`set_default`](crate::defaults::set_default), if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `username` - The SSH username.
+    /// * `port` The SSH port. Defaults to 22.
+    /// * `timeout` - The timeout for the TCP connection (in seconds). Defaults to 30.
+    pub fn auth_methods(
+        &self,
+        py: Python<'_>,
+        host: String,
+        username: String,
+        port: Option<u16>,
+        timeout: Option<u32>,
+    ) -> PyResult<Vec<String>> {
+        let defaults = defaults::lookup(&host);
+        let port = port.or(defaults.port).unwrap_or(DEFAULT_PORT);
+        let timeout = timeout.or(defaults.timeout).unwrap_or(DEFAULT_TIMEOUT);
+
+        let options = TransportOptions {
+            banner: self.client_banner.clone(),
+            ..Default::default()
+        };
+        let (sess, _) = open_session(py, &host, port, timeout, &options)?;
+        let methods = sess.auth_methods(&username);
+        let methods = match methods {
+            Ok(methods) => methods.split(',').map(str::to_string).collect(),
+            Err(_) if sess.authenticated() => Vec::new(),
+            Err(err) => return Err(excp_from_err(err)),
+        };
+        let _ = sess.disconnect(None, "", None);
+
+        Ok(methods)
+    }
+
+    /// Returns the public keys that a server would accept for a user, by connecting to it and
+    /// asking about each key without signing anything.
+    ///
+    /// This lets tools pick the right key out of many before authenticating. Note that servers may
+    /// still count rejected keys towards their limit of authentication attempts (`MaxAuthTries`).
+    ///
+    /// The connection is closed again afterwards; the client itself is not affected. Arguments that
+    /// are omitted fall back to the defaults registered for the host with
+    /// [`set_default`](crate::defaults::set_default), if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name or address.
+    /// * `username` - The SSH username.
+    /// * `public_keys` - The public keys to check, each either the path to a public-key file or a
+    ///   key in OpenSSH format (e.g. `ssh-ed25519 AAAA...`).
+    /// * `port` The SSH port. Defaults to 22.
+    /// * `timeout` - The timeout for the TCP connection (in seconds). Defaults to 30.
+    pub fn check_public_keys(
+        &self,
+        py: Python<'_>,
+        host: String,
+        username: String,
+        public_keys: Vec<String>,
+        port: Option<u16>,
+        timeout: Option<u32>,
+    ) -> PyResult<Vec<String>> {
+        let blobs = public_keys
+            .iter()
+            .map(|key| {
+                let line = match fs::read_to_string(key) {
+                    Ok(contents) => contents,
+                    Err(_) => key.clone(),
+                };
+
+                keys::public_key_blob(&line)
+                    .ok_or_else(|| PyValueError::new_err(format!("Invalid public key: {key}")))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let defaults = defaults::lookup(&host);
+        let port = port.or(defaults.port).unwrap_or(DEFAULT_PORT);
+        let timeout = timeout.or(defaults.timeout).unwrap_or(DEFAULT_TIMEOUT);
+
+        let options = TransportOptions {
+            banner: self.client_banner.clone(),
+            ..Default::default()
+        };
+        let (sess, _) = open_session(py, &host, port, timeout, &options)?;
+        let mut accepted = Vec::new();
+
+        for (key, blob) in public_keys.into_iter().zip(blobs) {
+            let result = query_public_key(&sess, &username, &blob);
+
+            match result {
+                Ok(true) => accepted.push(key),
+                Ok(false) => {}
+                Err(err) => {
+                    let _ = sess.disconnect(None, "", None);
+                    return Err(excp_from_err(err));
+                }
+            }
+
+            if sess.authenticated() {
+                break;
+            }
+        }
+
+        let _ = sess.disconnect(None, "", None);
+
+        Ok(accepted)
+    }
+
+    /// Opens a new, independent session to the same destination, authenticating again with the
+    /// same arguments that this client was connected with.
+    ///
+    /// The new client does not share any channels with this one, so it can be used in parallel
+    /// (e.g. from another thread). The channel retry policy is copied over.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
+    #[pyo3(name = "clone")]
+    pub fn clone_client(&self, py: Python<'_>) -> PyResult<SSHClient> {
+        let Some(_) = &self.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+        let Some(args) = self.connect_args.clone() else {
+            return Err(SessionException::new_err(
+                "Clients connected with connect_socket() cannot be cloned".to_string(),
+            ));
+        };
+
+        let mut client = SSHClient {
+            sess: None,
+            lock: SessionLock::new(),
+            channel_retries: self.channel_retries,
+            channel_retry_delay: self.channel_retry_delay,
+            connect_retries: self.connect_retries,
+            connect_retry_delay: self.connect_retry_delay,
+            connect_retry_jitter: self.connect_retry_jitter,
+            host_key_files: self.host_key_files.clone(),
+            missing_host_key_policy: self.missing_host_key_policy,
+            host_key_verifier: self.host_key_verifier.clone(),
+            host_key_store: self.host_key_store.clone(),
+            host_cas: self.host_cas.clone(),
+            client_banner: self.client_banner.clone(),
+            operation_timeout: self.operation_timeout,
+            reconnect_retries: self.reconnect_retries,
+            reconnect_delay: self.reconnect_delay,
+            connect_args: None,
+            tcp: None,
+            connected_at: None,
+            channels_opened: AtomicU64::new(0),
+        };
+        client.connect_with(py, args)?;
+
+        Ok(client)
+    }
+
+    /// Returns transport-level statistics of the session, e.g. for exporting to a monitoring
+    /// system.
+    ///
+    /// **NOTE**: Byte counts come from the operating system's TCP statistics, which are currently
+    /// only available on Linux. `libssh2` does not report key re-exchanges, so they are not
+    /// included.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
+    pub fn metrics(&self) -> PyResult<TransportMetrics> {
+        let (Some(_), Some(connected_at)) = (&self.sess, self.connected_at) else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+
+        let counts = self.tcp.as_ref().and_then(net::transfer_counts);
+
+        Ok(TransportMetrics {
+            bytes_sent: counts.map(|(sent, _)| sent),
+            bytes_received: counts.map(|(_, received)| received),
+            channels_opened: self.channels_opened.load(Ordering::Relaxed),
+            uptime: connected_at.elapsed().as_secs_f64(),
+        })
+    }
+
+    /// Returns the algorithms negotiated for the session (key exchange, host key, cipher, MAC and
+    /// compression), e.g. for compliance audits or to debug interoperability issues.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
+    pub fn negotiated_methods(&self) -> PyResult<NegotiatedMethods> {
+        let Some(sess) = &self.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+        let method = |method: MethodType| sess.methods(method).unwrap_or_default().to_string();
+
+        Ok(NegotiatedMethods {
+            kex: method(MethodType::Kex),
+            host_key: method(MethodType::HostKey),
+            cipher_cs: method(MethodType::CryptCs),
+            cipher_sc: method(MethodType::CryptSc),
+            mac_cs: method(MethodType::MacCs),
+            mac_sc: method(MethodType::MacSc),
+            compression_cs: method(MethodType::CompCs),
+            compression_sc: method(MethodType::CompSc),
+        })
+    }
+
+    /// Checks whether the session is still usable, by making a round trip to the server: a
+    /// channel is opened and closed right away. Unlike checking that [`SSHClient::connect`] was
+    /// called, this detects connections that were dropped or whose server stopped responding,
+    /// e.g. before queuing work on the client.
+    ///
+    /// The server refusing the channel (e.g. because of its `MaxSessions` limit) still counts as a
+    /// response. Returns `false` if there is no active SSH session.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Time to wait for the server to respond (in seconds). Defaults to 5.
+    #[pyo3(signature = (timeout=None))]
+    pub fn is_active(slf: &PyCell<Self>, py: Python<'_>, timeout: Option<f64>) -> PyResult<bool> {
+        let _guard = Self::lock(slf, py)?;
+        let this = slf.borrow();
+        let Some(sess) = &this.sess else {
+            return Ok(false);
+        };
+
+        if is_transport_lost(sess) {
+            return Ok(false);
+        }
+
+        let seconds = timeout.unwrap_or(DEFAULT_PROBE_TIMEOUT);
+        let timeout = duration_from_secs("timeout", seconds)?;
+
+        if seconds <= 0.0 {
+            return Err(PyValueError::new_err(format!("Invalid timeout: {seconds}")));
         }
 
-        Ok(())
+        Ok(py.allow_threads(|| {
+            with_timeout(Some(sess), Some(timeout), || match sess.channel_session() {
+                Ok(mut chan) => {
+                    let _ = chan.close();
+
+                    true
+                }
+                Err(err) => matches!(
+                    err.code(),
+                    ErrorCode::Session(LIBSSH2_ERROR_CHANNEL_FAILURE)
+                ),
+            })
+        }))
+    }
+
+    /// Returns the banner the server sent before authentication (e.g. legal notices or warnings),
+    /// or `None` if it did not send one.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
+    pub fn get_auth_banner(&self) -> PyResult<Option<String>> {
+        let Some(sess) = &self.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+
+        Ok(sess.userauth_banner().ok().flatten().map(str::to_string))
+    }
+
+    /// Returns the identification string the server sent at the start of the connection (e.g.
+    /// `"SSH-2.0-OpenSSH_9.6"`), e.g. to log or audit server versions.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
+    pub fn get_server_banner(&self) -> PyResult<String> {
+        let Some(sess) = &self.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+
+        sess.banner_bytes()
+            .map(|banner| String::from_utf8_lossy(banner).into_owned())
+            .ok_or_else(|| SessionException::new_err("Server did not provide a banner"))
     }
 
-    /// Returns the current working directory.
-    pub fn getcwd(&self) -> Option<String> {
-        self.cwd.clone()
+    /// Returns the host key the server presented during the handshake, e.g. to log or display its
+    /// fingerprint for out-of-band verification.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
+    pub fn get_host_key(&self) -> PyResult<HostKey> {
+        let Some(sess) = &self.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+
+        sess.host_key()
+            .and_then(|(key, _)| HostKey::from_blob(key))
+            .ok_or_else(|| SessionException::new_err("Server did not provide a host key"))
     }
 
-    /// Creates a folder on the remote server with the specified numeric mode.
+    /// Opens an SFTP session using the SSH session.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
     ///
     /// # Arguments
     ///
-    /// * `dir` The directory to create.
-    /// * `mode` - POSIX-style permissions for the newly-created folder. Defaults to 511.
-    pub fn mkdir(&mut self, dir: String, mode: Option<i32>) -> PyResult<()> {
-        let mode = mode.unwrap_or(511);
+    /// * `auto_reopen` - Whether to transparently reopen the SFTP subsystem on the next operation
+    ///   if its channel dies while the SSH session survives (e.g. when the server restarts the
+    ///   subsystem). The operation that discovers the lost channel still fails. Defaults to `false`.
+    #[pyo3(signature = (auto_reopen=false))]
+    pub fn open_sftp(
+        slf: &PyCell<Self>,
+        py: Python<'_>,
+        auto_reopen: bool,
+    ) -> PyResult<SFTPClient> {
+        let _guard = Self::lock(slf, py)?;
+        let this = slf.borrow();
+
+        if let Some(sess) = &this.sess {
+            let client = Some(sess.sftp().map_err(excp_from_err)?);
+            this.channels_opened.fetch_add(1, Ordering::Relaxed);
+            let sess = sess.clone();
+            let owner = (this.reconnect_retries > 0).then(|| Py::from(slf));
 
-        if let Some(client) = self.client.as_mut() {
-            let path = path_from_string(self.cwd.clone(), dir);
-            return Ok(client.mkdir(&path, mode).map_err(excp_from_err)?);
+            return Ok(SFTPClient {
+                lock: Arc::clone(&this.lock),
+                state: LockedState::new(SFTPClientState {
+                    sess,
+                    client,
+                    cwd: None,
+                    stat_cache: SharedStatCache::default(),
+                    auto_reopen,
+                    lost: Cell::new(false),
+                    owner,
+                }),
+            });
         }
 
-        Err(SFTPException::new_err("SFTP session not open".to_string()))
+        Err(SessionException::new_err(
+            "No active SSH session".to_string(),
+        ))
     }
 
-    /// Removes a file from the remote server.
+    /// Uploads a local file to a path that the SSH user cannot write to (e.g. under `/etc`).
     ///
-    /// **NOTE**: This only works for files. For directories, use [`SFTPClient::rmdir`].
+    /// The file is first copied over SFTP to a temporary file, in a private directory created with
+    /// `mktemp -d` so that other users on the server cannot tamper with it. It is then moved into
+    /// place, and has its owner and permissions set, with `sudo`. The temporary directory is
+    /// removed afterwards, whether or not the upload succeeded.
+    ///
+    /// **NOTE**: This requires a POSIX shell and `sudo` on the server. If `sudo_password` is not
+    /// given, `sudo` must not ask for a password.
     ///
     /// # Arguments
     ///
-    /// * `path` - The path to the file to remove.
-    pub fn unlink(&mut self, path: String) -> PyResult<()> {
-        if let Some(client) = self.client.as_mut() {
-            let path = path_from_string(self.cwd.clone(), path);
-            return Ok(client.unlink(&path).map_err(excp_from_err)?);
+    /// * `localpath` - The path to the local file.
+    /// * `remotepath` - The remote path to move the file to.
+    /// * `owner` - Optional owner of the file, as `user` or `user:group`. Defaults to the user
+    ///   `sudo` runs commands as (usually `root`).
+    /// * `mode` - The permissions of the file. Defaults to `0o644`.
+    /// * `sudo_password` - Optional password to give `sudo` if it asks for one.
+    #[pyo3(signature = (localpath, remotepath, owner=None, mode=0o644, sudo_password=None))]
+    pub fn put_sudo(
+        slf: &PyCell<Self>,
+        py: Python<'_>,
+        localpath: String,
+        remotepath: String,
+        owner: Option<String>,
+        mode: u32,
+        sudo_password: Option<String>,
+    ) -> PyResult<()> {
+        let _guard = Self::lock(slf, py)?;
+        let this = slf.borrow();
+        let Some(sess) = &this.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+
+        let mut local = fs::File::open(&localpath).map_err(excp_from_err)?;
+        let sftp = sess.sftp().map_err(excp_from_err)?;
+        this.channels_opened.fetch_add(1, Ordering::Relaxed);
+
+        let (stdout, exit_status) = this.run(py, "mktemp -d")?;
+        let temp_dir = String::from_utf8_lossy(&stdout).trim_end().to_string();
+
+        if exit_status != 0 || temp_dir.is_empty() {
+            return Err(SessionException::new_err(format!(
+                "Failed to create a temporary directory (mktemp exited with status {exit_status})"
+            )));
         }
 
-        Err(SFTPException::new_err("SFTP session not open".to_string()))
+        let temp = format!("{temp_dir}/upload");
+        let upload = sftp
+            .open_mode(
+                Path::new(&temp),
+                OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+                0o600,
+                OpenType::File,
+            )
+            .map_err(excp_from_err)
+            .and_then(|mut remote| io::copy(&mut local, &mut remote).map_err(excp_from_err));
+
+        let result = upload.and_then(|_| {
+            let owner = match owner {
+                Some(owner) => shell_quote(&owner),
+                None => "\"$(id -u):$(id -g)\"".to_string(),
+            };
+            let remotepath = shell_quote(&remotepath);
+            let script = format!(
+                "mv -f -- {} {remotepath} && chown {owner} -- {remotepath} && chmod {mode:o} -- {remotepath}",
+                shell_quote(&temp),
+            );
+            let sudo = match sudo_password {
+                Some(_) => "sudo -S -p ''",
+                None => "sudo -n",
+            };
+            let command = format!("{sudo} sh -c {}", shell_quote(&script));
+
+            let audit = this.audit(&command);
+            let mut chan = this.start_command(py, sess, &command, None)?;
+
+            if let Some(password) = &sudo_password {
+                chan.write_all(format!("{password}\n").as_bytes())
+                    .map_err(excp_from_err)?;
+            }
+            chan.send_eof().map_err(excp_from_err)?;
+            io::copy(&mut chan, &mut io::sink()).map_err(excp_from_err)?;
+
+            let result = check_remote_command(&mut chan, "sudo");
+
+            if let Some(audit) = audit {
+                audit.finish(py, chan.exit_status().ok());
+            }
+
+            result
+        });
+
+        // The file is only left behind if a step failed.
+        let _ = sftp.unlink(Path::new(&temp));
+        let _ = sftp.rmdir(Path::new(&temp_dir));
+
+        result
     }
 
-    /// Removes a file from the remote server.
-    ///
-    /// **NOTE**: This method is just an alias to [`SFTPClient::unlink`] to mimic compatibility with paramiko.
+    /// Configures how opening a channel is retried after the server refuses it (e.g. when its
+    /// `MaxSessions` limit is reached). Other failures, such as a lost connection, are not retried.
     ///
-    /// **NOTE**: This only works for files. For directories, use [`SFTPClient::rmdir`].
+    /// If opening a channel still fails after all retries, a `ChannelException` is raised. Unless
+    /// the connection was lost, the session itself remains usable.
     ///
     /// # Arguments
     ///
-    /// * `path` - The path to the file to remove.
-    pub fn remove(&mut self, path: String) -> PyResult<()> {
-        self.unlink(path)
+    /// * `retries` - Number of times to retry. Defaults to 0 (no retries).
+    /// * `delay` - Delay between attempts (in seconds). Defaults to 1.
+    #[pyo3(signature = (retries=0, delay=None))]
+    pub fn set_channel_retry(&mut self, retries: u32, delay: Option<f64>) -> PyResult<()> {
+        self.channel_retries = retries;
+        self.channel_retry_delay = match delay {
+            Some(delay) => duration_from_secs("delay", delay)?,
+            None => DEFAULT_CHANNEL_RETRY_DELAY,
+        };
+
+        Ok(())
     }
 
-    /// Removes a directory from the remove server.
+    /// Loads the host keys of a `known_hosts` file, which are used in addition to
+    /// `~/.ssh/known_hosts` to verify the servers the client connects to.
     ///
-    /// **NOTE**: This only works for directories. For files, use [`SFTPClient::remove`].
+    /// The file is read again on every connection, so changes to it are picked up.
     ///
     /// # Arguments
     ///
-    /// * `dir` - The path to the directory to remove.
-    pub fn rmdir(&mut self, dir: String) -> PyResult<()> {
-        if let Some(client) = self.client.as_mut() {
-            let path = path_from_string(self.cwd.clone(), dir);
-            return Ok(client.rmdir(&path).map_err(excp_from_err)?);
+    /// * `filename` - The path to the `known_hosts` file.
+    pub fn load_host_keys(&mut self, filename: String) -> PyResult<()> {
+        let path = PathBuf::from(filename);
+        KnownHosts::default()
+            .load_file(&path)
+            .map_err(excp_from_err)?;
+
+        if !self.host_key_files.contains(&path) {
+            self.host_key_files.push(path);
         }
 
-        Err(SFTPException::new_err("SFTP session not open".to_string()))
+        Ok(())
     }
 
-    /// Opens a file on the remote server.
+    /// Sets what the client does when connecting to a host whose key is not in `known_hosts`.
+    ///
+    /// With an [`AutoAddPolicy`], the key is added to the most recently loaded file (see
+    /// [`SSHClient::load_host_keys`]), or to `~/.ssh/known_hosts`. With a [`RejectPolicy`], a
+    /// [`BadHostKeyException`] is raised. The default is a [`WarningPolicy`].
     ///
     /// # Arguments
     ///
-    /// * `filename` - The name of the file (if file is in `cwd`) OR the path to the file.
-    /// * `mode` - Python-style file mode.
-    pub fn open(&mut self, filename: String, mode: Option<&str>) -> PyResult<File> {
-        let flags = mode.unwrap_or("r");
-        let flags = match flags {
-            "r" => OpenFlags::READ,
-            "r+" => OpenFlags::READ | OpenFlags::WRITE,
-            "w" => OpenFlags::TRUNCATE | OpenFlags::WRITE,
-            "w+" => OpenFlags::WRITE | OpenFlags::TRUNCATE | OpenFlags::READ,
-            "a" => OpenFlags::CREATE | OpenFlags::APPEND,
-            "a+" => OpenFlags::CREATE | OpenFlags::APPEND | OpenFlags::READ | OpenFlags::WRITE,
-            _ => return Err(PyValueError::new_err(format!("invalid mode: '{}'", flags))),
-        };
-
-        if let Some(client) = self.client.as_mut() {
-            let path = path_from_string(self.cwd.clone(), filename);
-            return Ok(File(
-                client
-                    .open_mode(&path, flags, 0o644, OpenType::File)
-                    .map_err(excp_from_err)?,
-            ));
-        }
-
-        Err(SFTPException::new_err("SFTP session not open".to_string()))
+    /// * `policy` - The policy: a [`RejectPolicy`], [`AutoAddPolicy`] or [`WarningPolicy`].
+    pub fn set_missing_host_key_policy(&mut self, policy: MissingHostKeyPolicy) {
+        self.missing_host_key_policy = policy;
     }
 
-    /// Opens a file on the remote server.
+    /// Sets a callable that verifies the host keys of the servers the client connects to, for
+    /// applications with their own trust store (e.g. a database, or a prompt asking the user).
     ///
-    /// **NOTE**: This method is just an alias to [`SFTPClient::open`] to mimic compatibility with paramiko.
+    /// The verifier replaces the `known_hosts` checks and the missing host key policy. It is called
+    /// with the host name (`[host]:port` for ports other than 22), the key type, the key in SSH wire
+    /// format and its SHA256 fingerprint, and must return `True` to accept the key; otherwise a
+    /// [`BadHostKeyException`] is raised. Exceptions raised by the verifier are propagated.
     ///
     /// # Arguments
     ///
-    /// * `filename` - The name of the file (if the file is in `cwd`) OR the path to the file.
-    /// * `mode` - Python-style file mode.
-    pub fn file(&mut self, filename: String, mode: Option<&str>) -> PyResult<File> {
-        self.open(filename, mode)
+    /// * `verifier` - The verifier, or `None` to go back to checking `known_hosts`.
+    pub fn set_host_key_verifier(&mut self, verifier: Option<PyObject>) {
+        self.host_key_verifier = verifier;
     }
 
-    /// Copies a file from the remote server to the local host.
+    /// Trusts a CA to sign the host certificates of the servers the client connects to, in
+    /// addition to the `@cert-authority` entries of `known_hosts`.
+    ///
+    /// Servers presenting a certificate signed by the CA are accepted if the certificate is valid
+    /// for the host: it must be a host certificate, list the host among its principals (if it lists
+    /// any), be within its validity period and carry a valid signature. Otherwise, a
+    /// [`BadHostKeyException`] is raised.
+    ///
+    /// **NOTE**: Servers only present their certificate if a certificate algorithm is preferred
+    /// (see the `host_key_algorithms` argument of [`SSHClient::connect`]). Verifying the signature
+    /// requires the `cryptography` package, installed with the `certificates` extra
+    /// (`pip install russh[certificates]`).
     ///
     /// # Arguments
     ///
-    /// * `remotepath` - The remote file path.
-    /// * `localpath` - The local path to copy the file to.
-    pub fn get(&mut self, remotepath: String, localpath: String) -> PyResult<()> {
-        if let Some(client) = self.client.as_mut() {
-            let remotepath = path_from_string(self.cwd.clone(), remotepath);
+    /// * `key` - The CA's public key: a [`HostKey`], a key in OpenSSH format or a key in SSH wire
+    ///   format.
+    /// * `hosts` - Patterns of the hosts the CA is trusted for, as in `known_hosts` files (e.g.
+    ///   `"*.example.com"`). Defaults to all hosts.
+    #[pyo3(signature = (key, hosts=None))]
+    pub fn add_host_ca(&mut self, key: PublicKey, hosts: Option<Vec<String>>) -> PyResult<()> {
+        let key = key.into_blob()?;
+        let hosts = hosts.unwrap_or_else(|| vec!["*".to_string()]);
 
-            let mut buf = String::new();
-            let mut file = client.open(&remotepath).map_err(excp_from_err)?;
-            file.read_to_string(&mut buf).map_err(excp_from_err)?;
+        if let Some(pattern) = hosts.iter().find(|pattern| {
+            pattern.is_empty() || pattern.contains(|c: char| c.is_whitespace() || c == ',')
+        }) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid host pattern: {pattern:?}"
+            )));
+        }
 
-            return Ok(fs::write(&localpath, buf).map_err(excp_from_err)?);
+        if hosts.is_empty() {
+            return Err(PyValueError::new_err("No host patterns given".to_string()));
         }
 
-        Err(SFTPException::new_err("SFTP session not open".to_string()))
+        self.host_cas
+            .add_cert_authority(&hosts, &key)
+            .ok_or_else(|| PyValueError::new_err("Invalid public key".to_string()))
     }
 
-    /// Copies a local file to the remote server.
+    /// Sets an application-managed host key store (see [`HostKeyStore`]), e.g. backed by a
+    /// database, which is consulted instead of the `known_hosts` files.
+    ///
+    /// The store holds one key per host and port: a different key is rejected with a
+    /// [`BadHostKeyException`], and unknown hosts are handled according to the missing host key
+    /// policy, with an [`AutoAddPolicy`] adding their key to the store. Exceptions raised by the
+    /// store are propagated.
     ///
     /// # Arguments
     ///
-    /// * `localpath` - The path to the local file.
-    /// * `remotepath` - The remote path to copy the file to.
-    pub fn put(&mut self, localpath: String, remotepath: String) -> PyResult<()> {
-        if let Some(client) = self.client.as_mut() {
-            let remotepath = path_from_string(self.cwd.clone(), remotepath);
-
-            let content = fs::read_to_string(&localpath).map_err(excp_from_err)?;
-            let mut file = client.create(&remotepath).map_err(excp_from_err)?;
-
-            return Ok(file.write_all(content.as_bytes()).map_err(excp_from_err)?);
-        }
-
-        Err(SFTPException::new_err("SFTP session not open".to_string()))
+    /// * `store` - The store, or `None` to go back to checking `known_hosts`.
+    pub fn set_host_key_store(&mut self, store: Option<PyObject>) {
+        self.host_key_store = store;
     }
 
-    /// Checks if the SFTP session is closed.
-    pub fn is_closed(&self) -> bool {
-        self.client.is_none()
-    }
+    /// Sets the identification string sent to servers at the start of later connections, instead
+    /// of the `libssh2` one (e.g. `"SSH-2.0-libssh2_1.11.0"`), e.g. for intrusion detection
+    /// systems that only allow specific clients.
+    ///
+    /// Fails with a `ValueError` if the banner is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `banner` - The identification string, e.g. `"SSH-2.0-MyClient_1.0"`. The `SSH-2.0-`
+    ///   prefix is added if it is missing. `None` restores the `libssh2` one.
+    #[pyo3(signature = (banner=None))]
+    pub fn set_client_banner(&mut self, banner: Option<String>) -> PyResult<()> {
+        let banner = banner.map(|banner| match banner.starts_with("SSH-") {
+            true => banner,
+            false => format!("SSH-2.0-{banner}"),
+        });
 
-    /// Closes the SFTP session.
-    pub fn close(&mut self) {
-        self.client.take();
-    }
-}
+        if let Some(banner) = &banner {
+            // The identification string is at most 255 characters long, including the final CRLF
+            // (RFC 4253, section 4.2).
+            if banner.len() > MAX_BANNER_LENGTH
+                || !banner.starts_with("SSH-2.0-")
+                || !banner.chars().all(|c| c.is_ascii_graphic() || c == ' ')
+            {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid client banner: {banner:?} (expected SSH-2.0-<software version>, of \
+                     at most {MAX_BANNER_LENGTH} printable ASCII characters)"
+                )));
+            }
+        }
 
-#[pyclass]
-/// The SSH client.
-pub struct SSHClient {
-    /// Established SSH session.
-    sess: Option<Session>,
-}
+        self.client_banner = banner;
 
-#[pymethods]
-impl SSHClient {
-    #[new]
-    /// Creates a new [`SSHClient`].
-    pub fn __new__() -> Self {
-        Self { sess: None }
+        Ok(())
     }
 
-    /// Establishes an SSH connection and sets the created session on the client.
-    ///
-    /// If multiple authentication methods are specified, then they are all attempted one at a time
-    /// (until one succeeds) in the following order:
+    /// Sets the timeout for blocking operations on the session (e.g. reading a command's output,
+    /// writing to a channel or SFTP calls), so that they fail with a `TimeoutError` instead of
+    /// hanging forever when the server stops responding. By default, there is no timeout.
     ///
-    /// [`PasswordAuth`] > [`PrivateKeyAuth`]
+    /// The timeout applies to the current session, including its open channels and SFTP clients,
+    /// and to the sessions established by later calls to [`SSHClient::connect`] (including
+    /// authentication). It is unrelated to the `timeout` argument of [`SSHClient::connect`], which
+    /// only applies to the TCP connection.
     ///
-    /// If all the authentication methods fail, the error message from the last attempted method is returned.
+    /// **NOTE**: Operations that take their own timeout (e.g. [`ExecOutput::read_stdout`]) use it
+    /// instead while they run.
     ///
     /// # Arguments
     ///
-    /// * `host` - The host name or address.
-    /// * `username` - The SSH username.
-    /// * `auth` - The authentication methods to use.
-    /// * `port` The SSH port. Defaults to 22.
-    /// * `timeout` - The timeout for the TCP connection (in seconds). Defaults to 30.
-    pub fn connect(
-        &mut self,
-        host: String,
-        username: String,
-        auth: AuthMethods,
-        port: Option<u16>,
-        timeout: Option<u32>,
-    ) -> PyResult<()> {
-        let port = port.unwrap_or(DEFAULT_PORT);
-        let timeout = timeout.unwrap_or(DEFAULT_TIMEOUT);
-        let addr: SocketAddr = format!("{host}:{port}").parse().map_err(excp_from_err)?;
-        let tcp = TcpStream::connect_timeout(&addr, Duration::from_secs(timeout as u64))
-            .map_err(excp_from_err)?;
-
-        let mut sess = Session::new().map_err(excp_from_err)?;
-        sess.set_tcp_stream(tcp);
-        sess.handshake().map_err(excp_from_err)?;
-
-        let mut last_error = None;
-
-        if let Some(password) = auth.password {
-            if let Err(err) = sess
-                .userauth_password(&username, &password.0)
-                .map_err(excp_from_err)
-            {
-                last_error = Some(err);
-            } else {
-                self.sess = Some(sess);
-
-                return Ok(());
+    /// * `seconds` - The timeout (in seconds). `None` or 0 disables the timeout.
+    #[pyo3(signature = (seconds=None))]
+    pub fn set_timeout(slf: &PyCell<Self>, py: Python<'_>, seconds: Option<f64>) -> PyResult<()> {
+        let timeout = seconds
+            .map(|seconds| duration_from_secs("timeout", seconds))
+            .transpose()?;
+        let timeout = match timeout {
+            // A timeout of 0 disables the timeout altogether, so round up to at least 1ms.
+            Some(timeout) if !timeout.is_zero() => {
+                timeout.as_millis().clamp(1, u32::MAX.into()) as u32
             }
-        }
+            _ => 0,
+        };
 
-        if let Some(private_key) = auth.private_key {
-            if let Err(err) = sess
-                .userauth_pubkey_file(
-                    &username,
-                    None,
-                    Path::new(&private_key.private_key),
-                    private_key.passphrase.as_deref(),
-                )
-                .map_err(excp_from_err)
-            {
-                last_error = Some(err);
-            } else {
-                self.sess = Some(sess);
+        // Operations of other threads may be temporarily using another timeout.
+        let _guard = Self::lock(slf, py)?;
+        let mut this = slf.try_borrow_mut()?;
+        this.operation_timeout = timeout;
 
-                return Ok(());
-            }
+        if let Some(sess) = &this.sess {
+            sess.set_timeout(timeout);
         }
 
-        if let Some(err) = last_error {
-            return Err(err);
-        }
+        Ok(())
+    }
+
+    /// Configures how [`SSHClient::connect`] retries transient failures while connecting (e.g. a
+    /// connection reset during the handshake, which is common with load-balanced bastions).
+    ///
+    /// Connection errors, timeouts and handshake failures are retried with an exponential backoff:
+    /// the delay doubles after every retry, plus a random jitter. Authentication failures are never
+    /// retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `retries` - Number of times to retry. Defaults to 0 (no retries).
+    /// * `delay` - Delay before the first retry (in seconds). Defaults to 1.
+    /// * `jitter` - Maximum random delay added to every retry delay (in seconds). Defaults to 0.
+    #[pyo3(signature = (retries=0, delay=None, jitter=None))]
+    pub fn set_connect_retry(
+        &mut self,
+        retries: u32,
+        delay: Option<f64>,
+        jitter: Option<f64>,
+    ) -> PyResult<()> {
+        self.connect_retry_delay = match delay {
+            Some(delay) => duration_from_secs("delay", delay)?,
+            None => DEFAULT_CONNECT_RETRY_DELAY,
+        };
+        self.connect_retry_jitter = match jitter {
+            Some(jitter) => duration_from_secs("jitter", jitter)?,
+            None => Duration::ZERO,
+        };
+        self.connect_retries = retries;
 
         Ok(())
     }
 
-    /// Opens an SFTP session using the SSH session.
+    /// Configures how the session is re-established when the connection to the server is lost
+    /// (e.g. after a network outage or a server restart).
     ///
-    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
-    pub fn open_sftp(&self) -> PyResult<SFTPClient> {
-        if let Some(sess) = &self.sess {
-            let client = Some(sess.sftp().map_err(excp_from_err)?);
-            return Ok(SFTPClient { client, cwd: None });
-        }
+    /// When starting a command with [`SSHClient::exec_command`] fails because the connection was
+    /// lost, the session is re-established with the arguments of the last
+    /// [`SSHClient::connect`] call (authenticating again), and the command is started once more.
+    /// SFTP clients opened afterwards with [`SSHClient::open_sftp`] likewise re-establish the
+    /// session and run the failed operation once more. Failed attempts to re-establish the session
+    /// are retried with an exponential backoff.
+    ///
+    /// **NOTE**: Channels, streams and port forwards of the lost session are not restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `retries` - Maximum number of attempts to re-establish the session. Defaults to 0 (never
+    ///   re-establish it).
+    /// * `delay` - Delay before the first attempt (in seconds), doubled for every further attempt.
+    ///   Defaults to 1.
+    #[pyo3(signature = (retries=0, delay=None))]
+    pub fn set_reconnect_policy(&mut self, retries: u32, delay: Option<f64>) -> PyResult<()> {
+        self.reconnect_delay = match delay {
+            Some(delay) => duration_from_secs("delay", delay)?,
+            None => DEFAULT_RECONNECT_DELAY,
+        };
+        self.reconnect_retries = retries;
 
-        Err(SessionException::new_err(
-            "No active SSH session".to_string(),
-        ))
+        Ok(())
     }
 
     /// Executes a command using the underlying session and returns the output.
     ///
+    /// **NOTE**: When a PTY is allocated, the command's `stderr` is written to the terminal and so
+    /// ends up in `stdout`. If [`PtyOptions`] disables `merge_stderr`, `stderr` is instead
+    /// redirected to a temporary file on the server (which requires a POSIX shell), and only becomes
+    /// readable once the command has finished.
+    ///
     /// # Arguments
     ///
     /// * `command` - The command to run.
-    pub fn exec_command(&self, command: String) -> PyResult<ExecOutput> {
+    /// * `pty` - Optional options for allocating a pseudo-terminal (PTY) for the command. By default,
+    ///   no PTY is allocated.
+    /// * `stdin_file` - Optional local file path or file-like object whose contents are streamed into
+    ///   the command's `stdin`, which is then closed. The source is read in chunks, so it is never
+    ///   loaded into memory as a whole.
+    /// * `cwd` - Optional remote directory to run the command in (which requires a POSIX shell). A
+    ///   `FileNotFoundError` is raised if it does not exist. Defaults to the user's login directory.
+    #[pyo3(signature = (command, pty=None, stdin_file=None, cwd=None))]
+    pub fn exec_command(
+        slf: &PyCell<Self>,
+        py: Python<'_>,
+        command: String,
+        pty: Option<PtyOptions>,
+        stdin_file: Option<LocalFile>,
+        cwd: Option<String>,
+    ) -> PyResult<ExecOutput> {
         let mut stdin = None;
         let mut stdout = None;
         let mut stderr = None;
         let mut channel = None;
+        let _guard = Self::lock(slf, py)?;
+        let mut client = slf.borrow();
+        let mut sess = client.sess.clone();
+
+        let mut stderr_file = None;
+        let mut audit = None;
 
-        if let Some(sess) = &self.sess {
-            let mut chan = sess.channel_session().map_err(excp_from_err)?;
-            chan.exec(&command).map_err(excp_from_err)?;
+        if let Some(current) = sess.clone() {
+            audit = client.audit(&command);
+
+            let command = match &cwd {
+                Some(cwd) => format!("cd -- {} || exit; {command}", shell_quote(cwd)),
+                None => command,
+            };
+            let command = match &pty {
+                Some(pty) if !pty.merge_stderr => {
+                    // The file is created exclusively and only readable by the user, since the
+                    // output may be sensitive.
+                    let path = temp_path("stderr");
+                    let command = format!(
+                        "(umask 077 && set -C && : >{path}) || exit; exec 2>>{path}; {command}"
+                    );
+                    stderr_file = Some(path);
+
+                    command
+                }
+                _ => command,
+            };
+            let start = |client: &Self, sess: &Session| -> PyResult<Channel> {
+                if let Some(cwd) = &cwd {
+                    client.check_remote_dir(py, sess, cwd)?;
+                }
+
+                client.start_command(py, sess, &command, pty.as_ref())
+            };
+            let chan = match start(&client, &current) {
+                Err(_) if client.reconnect_retries > 0 && is_transport_lost(&current) => {
+                    // Other threads may be using the client, so it is only borrowed mutably while
+                    // reconnecting.
+                    drop(client);
+                    slf.try_borrow_mut()?.reconnect(py)?;
+                    client = slf.borrow();
+                    sess = client.sess.clone();
+
+                    match &sess {
+                        Some(sess) => start(&client, sess)?,
+                        None => {
+                            return Err(SessionException::new_err(
+                                "No active SSH session".to_string(),
+                            ))
+                        }
+                    }
+                }
+                result => result?,
+            };
 
             stdin = Some(chan.stream(0));
             stdout = Some(chan.stream(0));
@@ -601,16 +7836,317 @@ impl SSHClient {
             channel = Some(chan);
         }
 
-        Ok(ExecOutput {
+        let mut output = ExecOutputState {
+            sess,
             channel,
             stdin,
             stdout,
             stderr,
+            stdout_buf: Vec::new(),
+            stderr_buf: Vec::new(),
+            returncode: None,
+            stderr_file,
+            audit,
+        };
+
+        if let Some(source) = stdin_file {
+            output.feed_stdin(py, source)?;
+        }
+
+        Ok(ExecOutput {
+            lock: Arc::clone(&client.lock),
+            state: LockedState::new(output),
+        })
+    }
+
+    /// Asks the server to listen on a port and forward incoming connections to the client.
+    ///
+    /// Pass port 0 to let the server choose a free port; the chosen port is available through
+    /// [`RemoteForward::port`].
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - The port to listen on. Defaults to 0.
+    /// * `address` - The address to listen on. Defaults to the server's choice (usually all
+    ///   interfaces, subject to `GatewayPorts`).
+    #[pyo3(signature = (port=0, address=None))]
+    pub fn request_port_forward(
+        slf: &PyCell<Self>,
+        py: Python<'_>,
+        port: u16,
+        address: Option<String>,
+    ) -> PyResult<RemoteForward> {
+        let _guard = Self::lock(slf, py)?;
+        let this = slf.borrow();
+        let Some(sess) = &this.sess else {
+            return Err(SessionException::new_err(
+                "No active SSH session".to_string(),
+            ));
+        };
+
+        let (listener, port) = sess
+            .channel_forward_listen(port, address.as_deref(), None)
+            .map_err(excp_from_err)?;
+
+        Ok(RemoteForward {
+            sess: sess.clone(),
+            lock: Arc::clone(&this.lock),
+            listener: LockedState::new(listener),
+            port,
         })
     }
 
-    /// Closes the underlying session.
-    pub fn close(&mut self) {
-        self.sess.take();
+    /// Returns the environment variables seen by commands run on the remote server.
+    ///
+    /// `env -0` is tried first (since it handles multi-line values unambiguously), falling back to
+    /// `printenv` and then `set` (for servers whose default shell is `cmd.exe`).
+    pub fn environ(slf: &PyCell<Self>, py: Python<'_>) -> PyResult<HashMap<String, String>> {
+        let _guard = Self::lock(slf, py)?;
+        let this = slf.borrow();
+        let (stdout, exit_status) = this.run(py, "env -0")?;
+
+        if exit_status == 0 && stdout.contains(&0) {
+            return Ok(stdout
+                .split(|b| *b == 0)
+                .filter_map(|entry| {
+                    let entry = String::from_utf8_lossy(entry);
+                    let (name, value) = entry.split_once('=')?;
+
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect());
+        }
+
+        for command in ["printenv", "set"] {
+            let (stdout, exit_status) = this.run(py, command)?;
+
+            if exit_status == 0 {
+                return Ok(parse_environ(&String::from_utf8_lossy(&stdout)));
+            }
+        }
+
+        Err(SessionException::new_err(
+            "Unable to determine the remote environment".to_string(),
+        ))
+    }
+
+    /// Returns the value of an environment variable seen by commands run on the remote server.
+    ///
+    /// Returns `None` if the variable is not set.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment variable.
+    pub fn getenv(slf: &PyCell<Self>, py: Python<'_>, name: String) -> PyResult<Option<String>> {
+        Ok(Self::environ(slf, py)?.remove(&name))
+    }
+
+    /// Disconnects from the server: sends it a disconnect message with an optional description,
+    /// then closes the connection. Channels and SFTP clients of the session stop working.
+    ///
+    /// The session is closed even if sending the message fails, in which case the error is
+    /// raised afterwards.
+    ///
+    /// Fails if there is no active SSH session (if [`SSHClient::connect`] was not called).
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Optional human-readable reason for disconnecting, which servers usually log
+    ///   (at most 256 bytes).
+    #[pyo3(signature = (message=None))]
+    pub fn disconnect(slf: &PyCell<Self>, py: Python<'_>, message: Option<String>) -> PyResult<()> {
+        let _guard = Self::lock(slf, py)?;
+        let mut this = slf.try_borrow_mut()?;
+
+        this.disconnect_session(message.as_deref())
+    }
+
+    /// Closes the underlying session, notifying the server with a disconnect message (see
+    /// [`SSHClient::disconnect`]). Does nothing if there is no active SSH session.
+    ///
+    /// Operations that other threads are running on the session are completed first.
+    pub fn close(slf: &PyCell<Self>, py: Python<'_>) -> PyResult<()> {
+        let _guard = Self::lock(slf, py)?;
+        slf.try_borrow_mut()?.close_session();
+
+        Ok(())
+    }
+
+    /// Returns the client itself, so that it can be used as a context manager
+    /// (`with SSHClient() as client: ...`).
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Closes the session when leaving the context (see [`SSHClient::close`]). Exceptions raised
+    /// in the context are propagated.
+    fn __exit__(
+        slf: &PyCell<Self>,
+        py: Python<'_>,
+        _exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<bool> {
+        Self::close(slf, py)?;
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_checksum_line_plain() {
+        assert_eq!(
+            parse_checksum_line(b"da39a3ee5e6b4b0d3255bfef95601890afd80709  /tmp/a  b"),
+            Some((
+                PathBuf::from("/tmp/a  b"),
+                "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string()
+            ))
+        );
+        assert_eq!(parse_checksum_line(b""), None);
+        assert_eq!(parse_checksum_line(b"sha1sum: /tmp/x: No such file"), None);
+    }
+
+    #[test]
+    fn parse_checksum_line_escaped() {
+        assert_eq!(
+            parse_checksum_line(b"\\da39a3ee5e6b4b0d3255bfef95601890afd80709  /tmp/a\\\\b\\nc\\rd"),
+            Some((
+                PathBuf::from("/tmp/a\\b\nc\rd"),
+                "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_checksum_line(b"\\da39a3ee5e6b4b0d3255bfef95601890afd80709  /tmp/a\\"),
+            None
+        );
+        assert_eq!(
+            parse_checksum_line(b"\\da39a3ee5e6b4b0d3255bfef95601890afd80709  /tmp/a\\t"),
+            None
+        );
+    }
+
+    #[test]
+    fn tcp_options_validate_keepalive() {
+        let options = TcpOptions::__new__(false, Some(2.5), None, None).unwrap();
+        assert_eq!(options.keepalive, Some(Duration::from_millis(2500)));
+
+        for interval in [0.5, -1.0, f64::NAN, f64::INFINITY, 1e20] {
+            assert!(TcpOptions::__new__(false, Some(interval), None, None).is_err());
+        }
+    }
+
+    /// Calls a method of an object on another thread while the current thread is in the middle of
+    /// an operation on it (holding its session lock and its state, with the GIL released), and
+    /// returns the result once the operation is over.
+    ///
+    /// Checks that the call waits for the operation rather than failing to borrow the object.
+    fn call_during_operation<T>(
+        py: Python<'_>,
+        object: &PyObject,
+        lock: &Arc<SessionLock>,
+        state: &LockedState<T>,
+        method: &'static str,
+    ) -> PyResult<PyObject> {
+        let guard = lock.acquire(py);
+        let borrowed = state.borrow_mut().unwrap();
+
+        let other = object.clone_ref(py);
+        let call =
+            std::thread::spawn(move || Python::with_gil(|py| other.call_method0(py, method)));
+        py.allow_threads(|| std::thread::sleep(Duration::from_millis(200)));
+        assert!(
+            !call.is_finished(),
+            "{method}() did not wait for the operation"
+        );
+
+        drop(borrowed);
+        drop(guard);
+
+        py.allow_threads(|| call.join().unwrap())
+    }
+
+    #[test]
+    fn exec_output_is_shared_between_threads() {
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            let output = Py::new(
+                py,
+                ExecOutput {
+                    lock: SessionLock::new(),
+                    state: LockedState::new(ExecOutputState {
+                        sess: None,
+                        channel: None,
+                        stdin: None,
+                        stdout: None,
+                        stderr: None,
+                        stdout_buf: b"out".to_vec(),
+                        stderr_buf: Vec::new(),
+                        returncode: Some(0),
+                        stderr_file: None,
+                        audit: None,
+                    }),
+                },
+            )
+            .unwrap();
+            let this = output.borrow(py);
+            let object = output.to_object(py);
+
+            let returncode =
+                call_during_operation(py, &object, &this.lock, &this.state, "poll").unwrap();
+            assert_eq!(returncode.extract::<Option<i32>>(py).unwrap(), Some(0));
+
+            let stdout =
+                call_during_operation(py, &object, &this.lock, &this.state, "read_stdout").unwrap();
+            assert_eq!(stdout.extract::<String>(py).unwrap(), "out");
+        });
+    }
+
+    #[test]
+    fn sftp_client_is_shared_between_threads() {
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            let client = Py::new(
+                py,
+                SFTPClient {
+                    lock: SessionLock::new(),
+                    state: LockedState::new(SFTPClientState {
+                        sess: Session::new().unwrap(),
+                        client: None,
+                        cwd: Some("/tmp".to_string()),
+                        stat_cache: SharedStatCache::default(),
+                        auto_reopen: false,
+                        lost: Cell::new(false),
+                        owner: None,
+                    }),
+                },
+            )
+            .unwrap();
+            let this = client.borrow(py);
+            let object = client.to_object(py);
+
+            let cwd =
+                call_during_operation(py, &object, &this.lock, &this.state, "getcwd").unwrap();
+            assert_eq!(
+                cwd.extract::<Option<String>>(py).unwrap().as_deref(),
+                Some("/tmp")
+            );
+
+            let closed =
+                call_during_operation(py, &object, &this.lock, &this.state, "is_closed").unwrap();
+            assert!(closed.extract::<bool>(py).unwrap());
+
+            // Using the client again from an operation on the same thread fails instead of
+            // deadlocking.
+            let _guard = this.lock.acquire(py);
+            let _state = this.state.borrow_mut().unwrap();
+            let err = object.call_method0(py, "getcwd").unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyRuntimeError>(py));
+        });
     }
 }
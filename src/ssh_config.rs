@@ -0,0 +1,364 @@
+//! Parsing of OpenSSH client configuration files (`~/.ssh/config`), applied by
+//! [`SSHClient::connect`](crate::ssh::SSHClient::connect) when asked to.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::defaults::{self, matches};
+use crate::jump::JumpHost;
+use crate::keys;
+use crate::ssh::excp_from_err;
+
+/// A `Host` (or `Match`) block of a configuration file.
+#[derive(Clone)]
+struct Block {
+    /// The host patterns the block applies to (none for `Match` blocks, which are not supported).
+    patterns: Vec<String>,
+    /// The options of the block, with lowercase keywords, in order.
+    options: Vec<(String, String)>,
+}
+
+impl Block {
+    /// Checks whether the block applies to a host: one of its patterns must match the host, and
+    /// none of its negated (`!`) patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name, as given by the user.
+    fn applies_to(&self, host: &str) -> bool {
+        let mut matched = false;
+
+        for pattern in &self.patterns {
+            match pattern.strip_prefix('!') {
+                Some(pattern) if matches(pattern, host) => return false,
+                Some(_) => {}
+                None => matched |= matches(pattern, host),
+            }
+        }
+
+        matched
+    }
+}
+
+/// The options that apply to a host.
+#[derive(Clone, Default)]
+pub struct HostConfig {
+    /// The real host name to connect to (`HostName`).
+    pub hostname: Option<String>,
+    /// The SSH username (`User`).
+    pub user: Option<String>,
+    /// The SSH port (`Port`).
+    pub port: Option<u16>,
+    /// The private keys to authenticate with (`IdentityFile`), in order.
+    pub identity_files: Vec<PathBuf>,
+    /// The jump hosts to go through (`ProxyJump`).
+    pub proxy_jump: Option<String>,
+}
+
+/// Splits a line into its keyword and arguments (`Keyword arguments` or `Keyword=arguments`).
+///
+/// Returns `None` for blank lines and comments.
+///
+/// # Arguments
+///
+/// * `line` - The line.
+fn split_line(line: &str) -> Option<(String, &str)> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim();
+
+    Some((keyword.to_lowercase(), rest))
+}
+
+/// Removes the double quotes around an argument, if any.
+///
+/// # Arguments
+///
+/// * `arg` - The argument.
+fn unquote(arg: &str) -> &str {
+    arg.strip_prefix('"')
+        .and_then(|arg| arg.strip_suffix('"'))
+        .unwrap_or(arg)
+}
+
+/// Expands the `%` tokens of a `HostName` or `IdentityFile` option, and a leading `~`.
+///
+/// Supports `%h` (the remote host name), `%r` (the remote username), `%u` (the local username),
+/// `%d` (the local home directory) and `%%`; other tokens are left as they are.
+///
+/// # Arguments
+///
+/// * `value` - The option's value.
+/// * `host` - The remote host name.
+/// * `user` - The remote username, if known.
+fn expand(value: &str, host: &str, user: Option<&str>) -> String {
+    let home = keys::ssh_dir()
+        .and_then(|dir| dir.parent().map(Path::to_path_buf))
+        .map(|home| home.to_string_lossy().into_owned());
+    let local_user = defaults::local_username();
+
+    let value = match (value.strip_prefix('~'), &home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{home}{rest}")
+        }
+        _ => value.to_string(),
+    };
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+
+        let token = chars.next();
+        let replacement = match token {
+            Some('%') => Some("%"),
+            Some('h') => Some(host),
+            Some('r') => user,
+            Some('u') => local_user.as_deref(),
+            Some('d') => home.as_deref(),
+            _ => None,
+        };
+
+        match (replacement, token) {
+            (Some(replacement), _) => expanded.push_str(replacement),
+            (None, Some(token)) => {
+                expanded.push('%');
+                expanded.push(token);
+            }
+            (None, None) => expanded.push('%'),
+        }
+    }
+
+    expanded
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+/// The host-specific options of OpenSSH client configuration files (`~/.ssh/config`).
+///
+/// `Host` blocks are supported, with `HostName`, `User`, `Port`, `IdentityFile` and `ProxyJump`
+/// options; other options, `Match` blocks and `Include` directives are ignored.
+pub struct SSHConfig {
+    /// The blocks of the loaded files, in order.
+    blocks: Vec<Block>,
+}
+
+impl SSHConfig {
+    /// Loads the user's configuration file (`~/.ssh/config`), if it exists.
+    pub fn load_default() -> io::Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(path) = keys::ssh_dir().map(|dir| dir.join("config")) {
+            if path.is_file() {
+                config.load_file(&path)?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Reads a configuration file, adding its blocks to the loaded ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the configuration file.
+    pub fn load_file(&mut self, path: &Path) -> io::Result<()> {
+        let contents = fs::read_to_string(path)?;
+        // Options before the first `Host` line apply to all hosts.
+        let mut block = Block {
+            patterns: vec!["*".to_string()],
+            options: Vec::new(),
+        };
+
+        for line in contents.lines() {
+            let Some((keyword, args)) = split_line(line) else {
+                continue;
+            };
+
+            match keyword.as_str() {
+                "host" => {
+                    self.blocks.push(block);
+                    block = Block {
+                        patterns: args
+                            .split_whitespace()
+                            .map(|p| unquote(p).to_string())
+                            .collect(),
+                        options: Vec::new(),
+                    };
+                }
+                "match" => {
+                    self.blocks.push(block);
+                    block = Block {
+                        patterns: Vec::new(),
+                        options: Vec::new(),
+                    };
+                }
+                _ => block.options.push((keyword, unquote(args).to_string())),
+            }
+        }
+
+        self.blocks.push(block);
+
+        Ok(())
+    }
+
+    /// Returns the options that apply to a host.
+    ///
+    /// As with OpenSSH, the first value found for an option wins, except for `IdentityFile`, whose
+    /// values accumulate.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name, as given by the user.
+    pub fn host_config(&self, host: &str) -> HostConfig {
+        let mut config = HostConfig::default();
+        let mut identity_files = Vec::new();
+
+        for block in self.blocks.iter().filter(|block| block.applies_to(host)) {
+            for (keyword, value) in &block.options {
+                match keyword.as_str() {
+                    "hostname" if config.hostname.is_none() => {
+                        config.hostname = Some(value.clone())
+                    }
+                    "user" if config.user.is_none() => config.user = Some(value.clone()),
+                    "port" if config.port.is_none() => config.port = value.parse().ok(),
+                    "identityfile" => identity_files.push(value.clone()),
+                    "proxyjump" if config.proxy_jump.is_none() => {
+                        config.proxy_jump = Some(value.clone())
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        config.hostname = config
+            .hostname
+            .map(|hostname| expand(&hostname, host, None));
+        let hostname = config.hostname.as_deref().unwrap_or(host);
+        config.identity_files = identity_files
+            .iter()
+            .map(|path| PathBuf::from(expand(path, hostname, config.user.as_deref())))
+            .collect();
+
+        config
+    }
+
+    /// Returns the jump hosts of a `ProxyJump` value (a comma-separated list of
+    /// `[user@]host[:port]`), each with the `HostName`, `User` and `Port` options that apply to it.
+    ///
+    /// Returns an empty list for `none`.
+    ///
+    /// # Arguments
+    ///
+    /// * `proxy_jump` - The `ProxyJump` value.
+    pub fn jump_hosts(&self, proxy_jump: &str) -> Vec<JumpHost> {
+        if proxy_jump.eq_ignore_ascii_case("none") {
+            return Vec::new();
+        }
+
+        proxy_jump
+            .split(',')
+            .map(str::trim)
+            .filter(|spec| !spec.is_empty())
+            .map(|spec| {
+                let spec = spec.strip_prefix("ssh://").unwrap_or(spec);
+                let (username, address) = match spec.rsplit_once('@') {
+                    Some((username, address)) => (Some(username.to_string()), address),
+                    None => (None, spec),
+                };
+                let (host, port) = match address.rsplit_once(':') {
+                    Some((host, port)) if !port.contains(']') => (host, port.parse().ok()),
+                    _ => (address, None),
+                };
+                let config = self.host_config(host);
+
+                JumpHost {
+                    host: config.hostname.unwrap_or_else(|| host.to_string()),
+                    username: username.or(config.user),
+                    auth: None,
+                    port: port.or(config.port),
+                }
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl SSHConfig {
+    #[new]
+    /// Creates a new [`SSHConfig`], optionally loading a configuration file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Optional path to a configuration file (e.g. `~/.ssh/config`).
+    #[pyo3(signature = (filename=None))]
+    pub fn __new__(filename: Option<String>) -> PyResult<Self> {
+        let mut config = Self::default();
+
+        if let Some(filename) = filename {
+            config.load(filename)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Loads a configuration file. Its blocks are consulted after those of the already loaded
+    /// files.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the configuration file.
+    pub fn load(&mut self, filename: String) -> PyResult<()> {
+        self.load_file(Path::new(&filename)).map_err(excp_from_err)
+    }
+
+    /// Returns the options that apply to a host, as a dictionary with the keys `hostname` (the
+    /// host itself if no `HostName` is set), and `user`, `port`, `identityfile` (a list of paths)
+    /// and `proxyjump` if they are set.
+    ///
+    /// `%h`, `%r`, `%u`, `%d`, `%%` and `~` are expanded in `HostName` and `IdentityFile` values.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name, as given by the user.
+    pub fn lookup<'py>(&self, py: Python<'py>, host: &str) -> PyResult<&'py PyDict> {
+        let config = self.host_config(host);
+        let dict = PyDict::new(py);
+
+        dict.set_item("hostname", config.hostname.as_deref().unwrap_or(host))?;
+
+        if let Some(user) = config.user {
+            dict.set_item("user", user)?;
+        }
+
+        if let Some(port) = config.port {
+            dict.set_item("port", port)?;
+        }
+
+        if !config.identity_files.is_empty() {
+            dict.set_item("identityfile", config.identity_files)?;
+        }
+
+        if let Some(proxy_jump) = config.proxy_jump {
+            dict.set_item("proxyjump", proxy_jump)?;
+        }
+
+        Ok(dict)
+    }
+}